@@ -0,0 +1,98 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::api::RequireAdmin;
+use crate::error::AppError;
+use crate::song::{DuplicateFinder, DuplicateGroup};
+use crate::state::{AppState, RescanStatus};
+
+/// Find songs that are in the library more than once
+///
+/// Songs are grouped by normalized artist and title, identical audio files
+/// and near-identical melodies. Each group lists the copy with the fewest
+/// validation problems first. Requires the admin token.
+#[utoipa::path(
+    get,
+    path = "/api/admin/duplicates",
+    responses(
+        (status = 200, description = "Groups of duplicate songs", body = Vec<DuplicateGroup>),
+        (status = 401, description = "Missing or invalid admin token"),
+        (status = 403, description = "Admin endpoints are disabled")
+    ),
+    security(("admin_token" = [])),
+    tag = "admin"
+)]
+pub async fn find_duplicates(
+    _admin: RequireAdmin,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<DuplicateGroup>>, AppError> {
+    let songs = state.get_all_songs().await;
+    // Hashes audio files and validates songs, so keep it off the async workers
+    let groups = tokio::task::spawn_blocking(move || DuplicateFinder::find(&songs))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(Json(groups))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct RescanQuery {
+    /// Reparse every song instead of reusing the index cache
+    #[serde(default)]
+    pub rebuild: bool,
+}
+
+/// Rescan the songs directory
+///
+/// Indexes the library in the background and swaps the result in once it
+/// is complete; follow it with `GET /api/admin/rescan/status`. Connected
+/// clients get a `library` event from `/api/events` when it is done.
+/// Requires the admin token.
+#[utoipa::path(
+    post,
+    path = "/api/admin/rescan",
+    params(RescanQuery),
+    responses(
+        (status = 202, description = "Rescan started", body = RescanStatus),
+        (status = 401, description = "Missing or invalid admin token"),
+        (status = 403, description = "Admin endpoints are disabled"),
+        (status = 409, description = "A rescan is already running")
+    ),
+    security(("admin_token" = [])),
+    tag = "admin"
+)]
+pub async fn start_rescan(
+    _admin: RequireAdmin,
+    State(state): State<AppState>,
+    Query(query): Query<RescanQuery>,
+) -> Result<(StatusCode, Json<RescanStatus>), AppError> {
+    let status = state
+        .start_rescan(query.rebuild)
+        .ok_or_else(|| AppError::Conflict("A rescan is already running".to_string()))?;
+    Ok((StatusCode::ACCEPTED, Json(status)))
+}
+
+/// Get the progress of the running or latest rescan
+///
+/// Requires the admin token.
+#[utoipa::path(
+    get,
+    path = "/api/admin/rescan/status",
+    responses(
+        (status = 200, description = "Rescan progress", body = RescanStatus),
+        (status = 401, description = "Missing or invalid admin token"),
+        (status = 403, description = "Admin endpoints are disabled")
+    ),
+    security(("admin_token" = [])),
+    tag = "admin"
+)]
+pub async fn rescan_status(
+    _admin: RequireAdmin,
+    State(state): State<AppState>,
+) -> Json<RescanStatus> {
+    Json(state.rescan_status())
+}
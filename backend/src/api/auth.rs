@@ -0,0 +1,35 @@
+use axum::{extract::FromRequestParts, http::header, http::request::Parts};
+
+use crate::error::AppError;
+use crate::state::AppState;
+
+/// Extractor guarding admin endpoints
+///
+/// Requests must send `Authorization: Bearer <ADMIN_TOKEN>`. Without a
+/// configured token every admin request is rejected.
+pub struct RequireAdmin;
+
+impl FromRequestParts<AppState> for RequireAdmin {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, AppError> {
+        let expected = state
+            .config()
+            .admin_token
+            .as_deref()
+            .ok_or(AppError::AdminDisabled)?;
+
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(AppError::Unauthorized)?;
+
+        if token.trim() == expected {
+            Ok(RequireAdmin)
+        } else {
+            Err(AppError::Unauthorized)
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+
+use crate::state::{AppState, LibraryChange};
+
+/// Stream library changes as server-sent events
+///
+/// Sends a `library` event whenever songs are added, changed or removed,
+/// e.g. after files in the songs directory were edited. Clients that fall
+/// behind get an event with empty lists and should reload the song list.
+#[utoipa::path(
+    get,
+    path = "/api/events",
+    responses(
+        (status = 200, description = "Stream of `library` events", content_type = "text/event-stream", body = LibraryChange)
+    ),
+    tag = "songs"
+)]
+pub async fn library_events(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let changes = BroadcastStream::new(state.subscribe_library_changes()).map(|change| {
+        Event::default()
+            .event("library")
+            .json_data(change.unwrap_or_default())
+    });
+    Sse::new(changes).keep_alive(KeepAlive::default())
+}
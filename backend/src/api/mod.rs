@@ -1,5 +1,11 @@
+pub mod admin;
+pub mod auth;
+pub mod events;
 pub mod queue;
 pub mod songs;
 
+pub use admin::*;
+pub use auth::RequireAdmin;
+pub use events::*;
 pub use queue::*;
 pub use songs::*;
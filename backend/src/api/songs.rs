@@ -5,13 +5,16 @@ use axum::{
     response::IntoResponse,
     Json,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tokio_util::io::ReaderStream;
-use utoipa::IntoParams;
+use utoipa::{IntoParams, ToSchema};
 
+use crate::api::RequireAdmin;
 use crate::error::AppError;
-use crate::song::{Song, SongSummary};
+use crate::song::export::{MidiExporter, SubtitleExporter, SubtitleFormat};
+use crate::song::transform::Transform;
+use crate::song::{Lyrics, Parser, Song, SongSummary, SongTimings, SourceFormat, Writer};
 use crate::state::AppState;
 
 /// List all songs
@@ -50,6 +53,14 @@ pub async fn search_songs(
     Json(state.search_songs(&query.q).await)
 }
 
+/// A song with its note data and precomputed millisecond timings
+#[derive(Serialize, ToSchema)]
+pub struct SongDetail {
+    #[serde(flatten)]
+    pub song: Song,
+    pub timings: SongTimings,
+}
+
 /// Get a specific song with full note data
 #[utoipa::path(
     get,
@@ -58,7 +69,7 @@ pub async fn search_songs(
         ("id" = String, Path, description = "Song ID")
     ),
     responses(
-        (status = 200, description = "Song found", body = Song),
+        (status = 200, description = "Song found", body = SongDetail),
         (status = 404, description = "Song not found")
     ),
     tag = "songs"
@@ -72,10 +83,241 @@ pub async fn get_song(
         .await
         .ok_or(AppError::SongNotFound(id))?;
 
-    Ok(Json(song))
+    let timings = song.timings();
+    Ok(Json(SongDetail { song, timings }))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TransformRequest {
+    /// Operations, applied in order
+    pub operations: Vec<Transform>,
+    /// Return the result without writing it to disk
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Shift, transpose or retime a song and write it back to its TXT file
+///
+/// The original file is kept as `<name>.bak`. Requires the admin token.
+#[utoipa::path(
+    post,
+    path = "/api/songs/{id}/transform",
+    params(
+        ("id" = String, Path, description = "Song ID")
+    ),
+    request_body = TransformRequest,
+    responses(
+        (status = 200, description = "Transformed song", body = SongDetail),
+        (status = 400, description = "Invalid operation or song is not a TXT file"),
+        (status = 401, description = "Missing or invalid admin token"),
+        (status = 403, description = "Admin endpoints are disabled"),
+        (status = 404, description = "Song not found")
+    ),
+    security(("admin_token" = [])),
+    tag = "songs"
+)]
+pub async fn transform_song(
+    _admin: RequireAdmin,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<TransformRequest>,
+) -> Result<Json<SongDetail>, AppError> {
+    let mut song = state
+        .get_song(&id)
+        .await
+        .ok_or(AppError::SongNotFound(id))?;
+
+    Transform::apply_all(&mut song, &request.operations)?;
+
+    if !request.dry_run {
+        if song.source_format != SourceFormat::UltraStar {
+            return Err(AppError::BadRequest(
+                "Only UltraStar TXT songs can be written back".to_string(),
+            ));
+        }
+
+        // Writes the file, so keep it off the async workers
+        song = tokio::task::spawn_blocking(move || -> Result<Song, AppError> {
+            let text = Writer::write(&song);
+            // Only replace the file if what is written reads back as a song,
+            // and index that so it matches what is on disk
+            let mut written = Parser::parse(&text, &song.files.txt_path)?;
+            written.files = song.files;
+            Writer::write_file_with_backup(&written.files.txt_path, text.as_bytes())?;
+            Ok(written)
+        })
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))??;
+        state.update_song(song.clone()).await;
+    }
+
+    let timings = song.timings();
+    Ok(Json(SongDetail { song, timings }))
+}
+
+/// Get a song's lyrics grouped into lines and words, with millisecond timestamps
+#[utoipa::path(
+    get,
+    path = "/api/songs/{id}/lyrics",
+    params(
+        ("id" = String, Path, description = "Song ID")
+    ),
+    responses(
+        (status = 200, description = "Song lyrics", body = Lyrics),
+        (status = 404, description = "Song not found")
+    ),
+    tag = "songs"
+)]
+pub async fn get_lyrics(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Lyrics>, AppError> {
+    let song = state
+        .get_song(&id)
+        .await
+        .ok_or(AppError::SongNotFound(id))?;
+
+    Ok(Json(Lyrics::from_song(&song)))
+}
+
+/// Export a song's vocal melody as a Standard MIDI file
+///
+/// One track per singer with lyric events; golden, freestyle and rap notes
+/// are marked by velocity
+#[utoipa::path(
+    get,
+    path = "/api/songs/{id}/export.mid",
+    params(
+        ("id" = String, Path, description = "Song ID")
+    ),
+    responses(
+        (status = 200, description = "MIDI file", content_type = "audio/midi"),
+        (status = 404, description = "Song not found")
+    ),
+    tag = "songs"
+)]
+pub async fn export_midi(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let song = state
+        .get_song(&id)
+        .await
+        .ok_or(AppError::SongNotFound(id))?;
+
+    Ok(download(
+        &song,
+        "mid",
+        "audio/midi",
+        MidiExporter::export(&song),
+    ))
+}
+
+/// Export timed lyrics as enhanced LRC with word-level timestamps
+#[utoipa::path(
+    get,
+    path = "/api/songs/{id}/lyrics.lrc",
+    params(
+        ("id" = String, Path, description = "Song ID")
+    ),
+    responses(
+        (status = 200, description = "LRC file", content_type = "text/plain"),
+        (status = 404, description = "Song not found")
+    ),
+    tag = "songs"
+)]
+pub async fn export_lrc(
+    state: State<AppState>,
+    id: Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    export_subtitles(state, id, SubtitleFormat::Lrc).await
+}
+
+/// Export timed lyrics as WebVTT, e.g. for a `<track>` on the video element
+#[utoipa::path(
+    get,
+    path = "/api/songs/{id}/lyrics.vtt",
+    params(
+        ("id" = String, Path, description = "Song ID")
+    ),
+    responses(
+        (status = 200, description = "WebVTT file", content_type = "text/vtt"),
+        (status = 404, description = "Song not found")
+    ),
+    tag = "songs"
+)]
+pub async fn export_vtt(
+    state: State<AppState>,
+    id: Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    export_subtitles(state, id, SubtitleFormat::WebVtt).await
+}
+
+/// Export timed lyrics as ASS karaoke subtitles with `\k` tags per syllable
+#[utoipa::path(
+    get,
+    path = "/api/songs/{id}/lyrics.ass",
+    params(
+        ("id" = String, Path, description = "Song ID")
+    ),
+    responses(
+        (status = 200, description = "ASS file", content_type = "text/x-ssa"),
+        (status = 404, description = "Song not found")
+    ),
+    tag = "songs"
+)]
+pub async fn export_ass(
+    state: State<AppState>,
+    id: Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    export_subtitles(state, id, SubtitleFormat::Ass).await
+}
+
+async fn export_subtitles(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    format: SubtitleFormat,
+) -> Result<impl IntoResponse, AppError> {
+    let song = state
+        .get_song(&id)
+        .await
+        .ok_or(AppError::SongNotFound(id))?;
+
+    Ok(download(
+        &song,
+        format.extension(),
+        format.content_type(),
+        SubtitleExporter::export(&song, format).into_bytes(),
+    ))
+}
+
+/// Response for a file generated from a song, named after artist and title
+fn download(song: &Song, extension: &str, content_type: &str, body: Vec<u8>) -> impl IntoResponse {
+    // Keep the header value plain ASCII
+    let name: String = format!("{} - {}", song.metadata.artist, song.metadata.title)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || " -_.,()".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.{}\"", name, extension),
+            ),
+        ],
+        body,
+    )
 }
 
-/// Serve song files (audio, video, cover, background)
+/// Serve song files (audio, video, cover, background, vocals, instrumental)
 ///
 /// Supports HTTP Range requests for seeking in media files
 #[utoipa::path(
@@ -83,7 +325,7 @@ pub async fn get_song(
     path = "/files/{song_id}/{file_type}",
     params(
         ("song_id" = String, Path, description = "Song ID"),
-        ("file_type" = String, Path, description = "File type: audio, video, cover, or background")
+        ("file_type" = String, Path, description = "File type: audio, video, cover, background, vocals, or instrumental")
     ),
     responses(
         (status = 200, description = "File content"),
@@ -107,6 +349,8 @@ pub async fn serve_file(
         "video" => song.files.video_path.as_ref(),
         "cover" => song.files.cover_path.as_ref(),
         "background" => song.files.background_path.as_ref(),
+        "vocals" => song.files.vocals_path.as_ref(),
+        "instrumental" => song.files.instrumental_path.as_ref(),
         _ => None,
     };
 
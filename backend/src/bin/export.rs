@@ -0,0 +1,46 @@
+use clap::{Parser, Subcommand};
+use frank::song::export::MidiExporter;
+use frank::song::indexer::Indexer;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(name = "frank-export")]
+#[command(about = "Export UltraStar songs to other formats")]
+#[command(version)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Export the vocal melody as a Standard MIDI file
+    Midi {
+        /// UltraStar TXT file to export
+        input: PathBuf,
+
+        /// Output file (defaults to the input with a .mid extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+fn main() {
+    let args = Args::parse();
+
+    match args.command {
+        Command::Midi { input, output } => {
+            let song = Indexer::index_song(&input).unwrap_or_else(|e| {
+                eprintln!("Error: Could not load {:?}: {}", input, e);
+                std::process::exit(1);
+            });
+
+            let output = output.unwrap_or_else(|| input.with_extension("mid"));
+            if let Err(e) = std::fs::write(&output, MidiExporter::export(&song)) {
+                eprintln!("Error: Could not write {:?}: {}", output, e);
+                std::process::exit(1);
+            }
+            println!("Exported {} tracks to {:?}", song.tracks.len(), output);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use clap::{Parser, Subcommand};
+use frank::song::import::{MidiImportOptions, MidiImporter};
+use frank::song::Writer;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(name = "frank-import")]
+#[command(about = "Convert other song formats into UltraStar TXT files")]
+#[command(version)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Import a melody from a Standard MIDI (.mid) or karaoke (.kar) file
+    Midi {
+        /// MIDI file to import
+        input: PathBuf,
+
+        /// Output TXT file (prints to stdout if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Track holding the vocal melody (see --list-tracks)
+        #[arg(short, long)]
+        track: Option<usize>,
+
+        /// BPM to quantize notes to (defaults to the file's tempo)
+        #[arg(short, long)]
+        bpm: Option<f64>,
+
+        /// Song title (defaults to the sequence name or file name)
+        #[arg(long)]
+        title: Option<String>,
+
+        /// Song artist
+        #[arg(long)]
+        artist: Option<String>,
+
+        /// Audio file to reference in the #MP3/#AUDIO tag
+        #[arg(long)]
+        audio: Option<String>,
+
+        /// List the file's tracks instead of importing
+        #[arg(long)]
+        list_tracks: bool,
+    },
+}
+
+fn main() {
+    let args = Args::parse();
+
+    match args.command {
+        Command::Midi {
+            input,
+            output,
+            track,
+            bpm,
+            title,
+            artist,
+            audio,
+            list_tracks,
+        } => {
+            let bytes = std::fs::read(&input).unwrap_or_else(|e| {
+                eprintln!("Error: Could not read {:?}: {}", input, e);
+                std::process::exit(1);
+            });
+
+            if list_tracks {
+                let tracks = MidiImporter::tracks(&bytes).unwrap_or_else(|e| {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                });
+                for info in tracks {
+                    println!(
+                        "{:>3}  {:<30} {:>5} notes {:>5} lyrics",
+                        info.index,
+                        info.name.unwrap_or_default(),
+                        info.note_count,
+                        info.lyric_count
+                    );
+                }
+                return;
+            }
+
+            let options = MidiImportOptions {
+                track,
+                bpm,
+                title,
+                artist,
+            };
+            let mut song = MidiImporter::import(&bytes, &input, &options).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            });
+            song.metadata.audio_file = audio;
+
+            let content = Writer::write(&song);
+            match output {
+                Some(path) => {
+                    if let Err(e) = std::fs::write(&path, content) {
+                        eprintln!("Error: Could not write {:?}: {}", path, e);
+                        std::process::exit(1);
+                    }
+                    println!(
+                        "Imported {} notes into {:?}",
+                        song.tracks[0].notes.len(),
+                        path
+                    );
+                }
+                None => print!("{}", content),
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use clap::Parser;
+use frank::song::indexer::Indexer;
+use frank::song::{SourceFormat, Transform, Writer};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(name = "frank-transform")]
+#[command(about = "Shift, transpose or retime UltraStar songs")]
+#[command(long_about = "Shift, transpose or retime UltraStar songs.\n\n\
+    Operations are applied in the order of the options below. The song is \
+    written back in place, keeping the original file as <name>.bak.")]
+#[command(version)]
+struct Args {
+    /// Song file to transform (TXT or MelodyXML)
+    input: PathBuf,
+
+    /// Move #GAP by this many milliseconds
+    #[arg(long, value_name = "MS", allow_hyphen_values = true)]
+    shift_gap: Option<f64>,
+
+    /// Move notes and line breaks by this many beats
+    #[arg(long, value_name = "BEATS", allow_hyphen_values = true)]
+    shift_beats: Option<i32>,
+
+    /// Move pitches by this many semitones (12 for an octave)
+    #[arg(long, value_name = "SEMITONES", allow_hyphen_values = true)]
+    transpose: Option<i32>,
+
+    /// Only shift or transpose this player's notes (1-indexed)
+    #[arg(long)]
+    player: Option<usize>,
+
+    /// Multiply the BPM, rescaling beats so the timing stays the same
+    #[arg(long, value_name = "FACTOR")]
+    rescale_bpm: Option<f64>,
+
+    /// Split note INDEX of PLAYER, AT beats after its start
+    #[arg(long, value_name = "PLAYER:INDEX:AT", value_parser = parse_triple)]
+    split: Vec<(usize, usize, usize)>,
+
+    /// Merge COUNT notes of PLAYER, starting at note INDEX
+    #[arg(long, value_name = "PLAYER:INDEX:COUNT", value_parser = parse_triple)]
+    merge: Vec<(usize, usize, usize)>,
+
+    /// Output file (defaults to the input; XML songs get a .txt next to them)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Print the result instead of writing it
+    #[arg(long)]
+    dry_run: bool,
+}
+
+fn parse_triple(value: &str) -> Result<(usize, usize, usize), String> {
+    let parts: Vec<usize> = value
+        .split(':')
+        .map(|part| part.trim().parse::<usize>())
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    match parts[..] {
+        [a, b, c] => Ok((a, b, c)),
+        _ => Err("expected three numbers separated by colons".to_string()),
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let mut transforms = Vec::new();
+    if let Some(ms) = args.shift_gap {
+        transforms.push(Transform::ShiftGap { ms });
+    }
+    if let Some(beats) = args.shift_beats {
+        transforms.push(Transform::ShiftBeats {
+            beats,
+            player: args.player,
+        });
+    }
+    if let Some(semitones) = args.transpose {
+        transforms.push(Transform::Transpose {
+            semitones,
+            player: args.player,
+        });
+    }
+    if let Some(factor) = args.rescale_bpm {
+        transforms.push(Transform::RescaleBpm { factor });
+    }
+    for &(player, index, at) in &args.split {
+        transforms.push(Transform::SplitNote {
+            player,
+            index,
+            at: at as i32,
+        });
+    }
+    for &(player, index, count) in &args.merge {
+        transforms.push(Transform::MergeNotes {
+            player,
+            index,
+            count,
+        });
+    }
+
+    let mut song = Indexer::index_song(&args.input).unwrap_or_else(|e| {
+        eprintln!("Error: Could not load {:?}: {}", args.input, e);
+        std::process::exit(1);
+    });
+    if let Err(e) = Transform::apply_all(&mut song, &transforms) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+
+    if args.dry_run {
+        print!("{}", Writer::write(&song));
+        return;
+    }
+
+    let output = args.output.unwrap_or_else(|| match song.source_format {
+        SourceFormat::UltraStar => args.input.clone(),
+        _ => args.input.with_extension("txt"),
+    });
+    match Writer::write_with_backup(&song, &output) {
+        Ok(Some(backup)) => println!("Wrote {:?} (backup at {:?})", output, backup),
+        Ok(None) => println!("Wrote {:?}", output),
+        Err(e) => {
+            eprintln!("Error: Could not write {:?}: {}", output, e);
+            std::process::exit(1);
+        }
+    }
+}
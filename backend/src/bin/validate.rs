@@ -1,5 +1,8 @@
 use clap::Parser;
-use frank::song::{indexer::Indexer, Validator};
+use frank::song::{
+    indexer::Indexer, DuplicateFinder, Fixer, ReportFormat, Reporter, RuleConfig,
+    ValidationOptions, ValidationResult, Validator,
+};
 use rayon::prelude::*;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -21,20 +24,41 @@ struct Args {
     #[arg(short, long)]
     verbose: bool,
 
-    /// Output format
+    /// Output format (text, json, sarif, junit, markdown)
     #[arg(short, long, default_value = "text")]
     format: OutputFormat,
 
     /// Only validate specific file types (comma-separated: encoding,metadata,notes,files)
     #[arg(long)]
     check: Option<String>,
+
+    /// Apply safe repairs before validating (BOM, encoding, end marker,
+    /// note order, decimal separators, cover), keeping originals as .bak
+    #[arg(long)]
+    fix: bool,
+
+    /// With --fix, print the changes as unified diffs instead of writing them
+    #[arg(long, requires = "fix")]
+    dry_run: bool,
+
+    /// Decode audio and video files and check them against the song's timing (slow)
+    #[arg(long)]
+    media: bool,
+
+    /// Report songs that are in the library more than once instead of validating
+    #[arg(long, conflicts_with = "fix")]
+    duplicates: bool,
+
+    /// Rule severity config; defaults to frank-validate.toml in the songs directory
+    #[arg(long)]
+    config: Option<PathBuf>,
 }
 
 #[derive(Clone, Copy, Default)]
 enum OutputFormat {
     #[default]
     Text,
-    Json,
+    Report(ReportFormat),
 }
 
 impl std::str::FromStr for OutputFormat {
@@ -42,7 +66,10 @@ impl std::str::FromStr for OutputFormat {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
             "text" => Ok(OutputFormat::Text),
-            "json" => Ok(OutputFormat::Json),
+            "json" => Ok(OutputFormat::Report(ReportFormat::Json)),
+            "sarif" => Ok(OutputFormat::Report(ReportFormat::Sarif)),
+            "junit" => Ok(OutputFormat::Report(ReportFormat::Junit)),
+            "markdown" | "md" => Ok(OutputFormat::Report(ReportFormat::Markdown)),
             _ => Err(format!("Unknown format: {}", s)),
         }
     }
@@ -56,6 +83,23 @@ fn main() {
         std::process::exit(1);
     }
 
+    if args.duplicates {
+        report_duplicates(&args);
+        return;
+    }
+
+    let config = match &args.config {
+        Some(config) => RuleConfig::load(config, &args.path).map(Some),
+        None => RuleConfig::discover(&args.path),
+    };
+    let config = match config {
+        Ok(config) => config.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
     // Collect all txt files
     let txt_files = collect_txt_files(&args.path);
     let total_files = txt_files.len();
@@ -65,8 +109,16 @@ fn main() {
         return;
     }
 
-    println!("Validating {} files...\n", total_files);
+    if args.fix {
+        fix_files(&txt_files, args.dry_run);
+    }
+
+    let text = matches!(args.format, OutputFormat::Text);
+    if text {
+        println!("Validating {} files...\n", total_files);
+    }
 
+    let options = ValidationOptions { media: args.media };
     let error_count = AtomicUsize::new(0);
     let warning_count = AtomicUsize::new(0);
     let valid_count = AtomicUsize::new(0);
@@ -75,7 +127,8 @@ fn main() {
     let results: Vec<_> = txt_files
         .par_iter()
         .map(|path| {
-            let result = Validator::validate(path);
+            let mut result = Validator::validate_with(path, &options);
+            config.apply(&mut result);
 
             if result.is_valid() {
                 valid_count.fetch_add(1, Ordering::Relaxed);
@@ -96,38 +149,133 @@ fn main() {
         OutputFormat::Text => {
             output_text(&results, &args);
         }
-        OutputFormat::Json => {
-            output_json(&results, &args);
+        OutputFormat::Report(format) => {
+            output_report(results, &args, format);
         }
     }
 
     // Summary
+    if text {
+        print_summary(
+            total_files,
+            valid_count.load(Ordering::Relaxed),
+            error_count.load(Ordering::Relaxed),
+            args.warnings.then(|| warning_count.load(Ordering::Relaxed)),
+        );
+    }
+
+    // Exit with error code if any files have errors
+    if error_count.load(Ordering::Relaxed) > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn print_summary(total_files: usize, valid: usize, errors: usize, warnings: Option<usize>) {
     println!("\n{}", "=".repeat(60));
     println!("Summary:");
     println!("  Total files:  {}", total_files);
     println!(
         "  Valid:        {} ({:.1}%)",
-        valid_count.load(Ordering::Relaxed),
-        (valid_count.load(Ordering::Relaxed) as f64 / total_files as f64) * 100.0
+        valid,
+        (valid as f64 / total_files as f64) * 100.0
     );
     println!(
         "  With errors:  {} ({:.1}%)",
-        error_count.load(Ordering::Relaxed),
-        (error_count.load(Ordering::Relaxed) as f64 / total_files as f64) * 100.0
+        errors,
+        (errors as f64 / total_files as f64) * 100.0
     );
-    if args.warnings {
-        println!(
-            "  Total warnings: {}",
-            warning_count.load(Ordering::Relaxed)
-        );
+    if let Some(warnings) = warnings {
+        println!("  Total warnings: {}", warnings);
     }
+}
 
-    // Exit with error code if any files have errors
-    if error_count.load(Ordering::Relaxed) > 0 {
+fn report_duplicates(args: &Args) {
+    let songs: Vec<_> = match Indexer::scan_directory(&args.path) {
+        Ok(songs) => songs.into_values().collect(),
+        Err(e) => {
+            eprintln!("Error: Could not index {:?}: {}", args.path, e);
+            std::process::exit(1);
+        }
+    };
+    let groups = DuplicateFinder::find(&songs);
+
+    match args.format {
+        OutputFormat::Text => {
+            for group in &groups {
+                let best = &group.songs[0];
+                let reasons: Vec<_> = group.reasons.iter().map(|r| format!("{:?}", r)).collect();
+                println!(
+                    "\x1b[33m⚠\x1b[0m {} - {} ({})",
+                    best.artist,
+                    best.title,
+                    reasons.join(", ")
+                );
+                for (i, song) in group.songs.iter().enumerate() {
+                    let marker = if i == 0 { "keep" } else { "    " };
+                    let problems = match (song.errors, song.warnings) {
+                        (Some(errors), Some(warnings)) => {
+                            format!("{} errors, {} warnings", errors, warnings)
+                        }
+                        _ => "not validated".to_string(),
+                    };
+                    println!("  {} {} ({})", marker, song.path, problems);
+                }
+            }
+            println!(
+                "\n{} duplicate groups among {} songs",
+                groups.len(),
+                songs.len()
+            );
+        }
+        OutputFormat::Report(ReportFormat::Json) => {
+            println!("{}", serde_json::to_string_pretty(&groups).unwrap());
+        }
+        OutputFormat::Report(_) => {
+            eprintln!("Error: --duplicates supports the text and json formats");
+            std::process::exit(2);
+        }
+    }
+
+    if !groups.is_empty() {
         std::process::exit(1);
     }
 }
 
+fn fix_files(files: &[PathBuf], dry_run: bool) {
+    let results: Vec<_> = files
+        .par_iter()
+        .map(|path| (path, Fixer::fix_file(path, dry_run)))
+        .collect();
+
+    let mut fixed = 0;
+    for (path, result) in results {
+        match result {
+            Ok(result) if result.is_changed() => {
+                fixed += 1;
+                if dry_run {
+                    for fix in &result.fixes {
+                        println!("# {}", fix);
+                    }
+                    print!("{}", result.diff(path));
+                } else {
+                    println!("\x1b[36m✎\x1b[0m {:?}", path);
+                    for fix in &result.fixes {
+                        println!("  {}", fix);
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Error: Could not fix {:?}: {}", path, e),
+        }
+    }
+
+    if dry_run {
+        println!("\n{} files would be fixed\n", fixed);
+    } else {
+        println!("\nFixed {} files (originals kept as .bak)\n", fixed);
+    }
+}
+
 fn collect_txt_files(path: &Path) -> Vec<PathBuf> {
     let mut files = Vec::new();
     collect_txt_files_recursive(path, &mut files);
@@ -174,7 +322,11 @@ fn output_text(
 
         // Print errors
         for error in &result.errors {
-            print!("  \x1b[31mERROR:\x1b[0m {}", error.kind);
+            print!(
+                "  \x1b[31mERROR:\x1b[0m [{}] {}",
+                error.kind.rule_id(),
+                error.kind
+            );
             if let Some(line) = error.line {
                 print!(" (line {})", line);
             }
@@ -187,7 +339,11 @@ fn output_text(
         // Print warnings if requested
         if args.warnings {
             for warning in &result.warnings {
-                print!("  \x1b[33mWARN:\x1b[0m {}", warning.kind);
+                print!(
+                    "  \x1b[33mWARN:\x1b[0m [{}] {}",
+                    warning.kind.rule_id(),
+                    warning.kind
+                );
                 if let Some(line) = warning.line {
                     print!(" (line {})", line);
                 }
@@ -197,44 +353,19 @@ fn output_text(
     }
 }
 
-fn output_json(
-    results: &[frank::song::ValidationResult],
-    args: &Args,
-) {
-    use serde_json::json;
-
-    let json_results: Vec<_> = results
-        .iter()
-        .filter(|r| {
-            !r.errors.is_empty() || (args.warnings && !r.warnings.is_empty()) || args.verbose
-        })
-        .map(|r| {
-            json!({
-                "path": r.path.to_string_lossy(),
-                "valid": r.is_valid(),
-                "errors": r.errors.iter().map(|e| {
-                    json!({
-                        "kind": format!("{:?}", e.kind),
-                        "message": e.kind.to_string(),
-                        "line": e.line,
-                        "context": e.context,
-                    })
-                }).collect::<Vec<_>>(),
-                "warnings": if args.warnings {
-                    r.warnings.iter().map(|w| {
-                        json!({
-                            "kind": format!("{:?}", w.kind),
-                            "message": w.kind.to_string(),
-                            "line": w.line,
-                            "context": w.context,
-                        })
-                    }).collect::<Vec<_>>()
-                } else {
-                    vec![]
-                },
-            })
-        })
-        .collect();
+fn output_report(mut results: Vec<ValidationResult>, args: &Args, format: ReportFormat) {
+    if !args.warnings {
+        for result in &mut results {
+            result.warnings.clear();
+        }
+    }
+    // JUnit and Markdown count passing files, JSON and SARIF only list problems
+    if matches!(format, ReportFormat::Json | ReportFormat::Sarif) && !args.verbose {
+        results.retain(|r| !r.errors.is_empty() || !r.warnings.is_empty());
+    }
 
-    println!("{}", serde_json::to_string_pretty(&json_results).unwrap());
+    print!("{}", Reporter::render(&results, &args.path, format));
+    if format == ReportFormat::Json {
+        println!();
+    }
 }
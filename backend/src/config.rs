@@ -1,10 +1,18 @@
+use crate::song::aliases::ALIAS_FILE_NAME;
+use crate::song::cache::CACHE_FILE_NAME;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub songs_directory: PathBuf,
+    /// Where the server keeps its own files, such as the song index cache
+    pub data_directory: PathBuf,
     pub host: String,
     pub port: u16,
+    /// Bearer token for admin endpoints; they are disabled without one
+    pub admin_token: Option<String>,
+    /// Reload songs when files in the songs directory change
+    pub watch_songs: bool,
 }
 
 impl Config {
@@ -13,14 +21,33 @@ impl Config {
             songs_directory: std::env::var("SONGS_DIRECTORY")
                 .map(PathBuf::from)
                 .unwrap_or_else(|_| PathBuf::from("./songs")),
+            data_directory: std::env::var("DATA_DIRECTORY")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("./data")),
             host: std::env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
             port: std::env::var("PORT")
                 .ok()
                 .and_then(|p| p.parse().ok())
                 .unwrap_or(3001),
+            admin_token: std::env::var("ADMIN_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+            watch_songs: std::env::var("WATCH_SONGS")
+                .map(|v| !matches!(v.to_lowercase().as_str(), "0" | "false" | "no" | "off"))
+                .unwrap_or(true),
         }
     }
 
+    /// Cached song index, reused across restarts
+    pub fn index_cache_path(&self) -> PathBuf {
+        self.data_directory.join(CACHE_FILE_NAME)
+    }
+
+    /// Earlier song IDs and the songs they belong to now
+    pub fn alias_table_path(&self) -> PathBuf {
+        self.data_directory.join(ALIAS_FILE_NAME)
+    }
+
     pub fn address(&self) -> String {
         format!("{}:{}", self.host, self.port)
     }
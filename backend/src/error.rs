@@ -5,13 +5,35 @@ use axum::{
 };
 use serde_json::json;
 
+use crate::song::import::XmlImportError;
+use crate::song::parser::ParseError;
+use crate::song::transform::TransformError;
+
 #[derive(Debug, thiserror::Error)]
 pub enum AppError {
     #[error("Song not found: {0}")]
     SongNotFound(String),
 
     #[error("Failed to parse song file: {0}")]
-    ParseError(String),
+    ParseError(#[from] ParseError),
+
+    #[error("Failed to import song file: {0}")]
+    ImportError(#[from] XmlImportError),
+
+    #[error("Invalid transform: {0}")]
+    TransformError(#[from] TransformError),
+
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+
+    #[error("Missing or invalid admin token")]
+    Unauthorized,
+
+    #[error("Admin endpoints are disabled (set ADMIN_TOKEN to enable them)")]
+    AdminDisabled,
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
 
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
@@ -22,15 +44,31 @@ pub enum AppError {
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, message) = match &self {
-            AppError::SongNotFound(_) => (StatusCode::NOT_FOUND, self.to_string()),
-            AppError::ParseError(_) => (StatusCode::BAD_REQUEST, self.to_string()),
-            AppError::IoError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
-            AppError::Internal(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+        let status = match &self {
+            AppError::SongNotFound(_) => StatusCode::NOT_FOUND,
+            AppError::ParseError(_) => StatusCode::BAD_REQUEST,
+            AppError::ImportError(_) => StatusCode::BAD_REQUEST,
+            AppError::TransformError(_) => StatusCode::BAD_REQUEST,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::AdminDisabled => StatusCode::FORBIDDEN,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::IoError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let body = match &self {
+            AppError::ParseError(e) => json!({
+                "error": self.to_string(),
+                "kind": e.kind.code(),
+                "line": e.line,
+                "column": e.column,
+                "snippet": e.snippet,
+            }),
+            _ => json!({ "error": self.to_string() }),
         };
 
-        let body = Json(json!({ "error": message }));
-        (status, body).into_response()
+        (status, Json(body)).into_response()
     }
 }
 
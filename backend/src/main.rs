@@ -1,44 +1,79 @@
 use axum::{
-    routing::{delete, get},
+    routing::{delete, get, post},
     Router,
 };
+use clap::Parser;
 use tower_http::cors::{Any, CorsLayer};
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use utoipa::OpenApi;
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::{Modify, OpenApi};
 use utoipa_swagger_ui::SwaggerUi;
 
 use frank::api;
 use frank::config::Config;
-use frank::song::{LineBreak, Note, NoteType, Song, SongMetadata, SongSummary};
-use frank::state::{AppState, QueueEntry};
+use frank::song::{
+    DuplicateCandidate, DuplicateGroup, DuplicateReason, Line, LineBreak, Lyrics, LyricsTrack,
+    Note, NoteType, Song, SongMetadata, SongSummary, SongTimings, SourceFormat, Timing, Transform,
+    Word,
+};
+use frank::state::{AppState, LibraryChange, QueueEntry, RescanState, RescanStatus};
+use frank::watcher::LibraryWatcher;
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         api::list_songs,
         api::get_song,
+        api::get_lyrics,
+        api::transform_song,
+        api::export_midi,
+        api::export_lrc,
+        api::export_vtt,
+        api::export_ass,
         api::search_songs,
+        api::library_events,
         api::serve_file,
         api::list_queue,
         api::add_to_queue,
         api::remove_from_queue,
         api::remove_by_song,
+        api::find_duplicates,
+        api::start_rescan,
+        api::rescan_status,
     ),
     components(schemas(
         Song,
         SongMetadata,
         SongSummary,
+        LibraryChange,
         Note,
         NoteType,
+        SourceFormat,
         LineBreak,
+        Timing,
+        SongTimings,
+        Lyrics,
+        LyricsTrack,
+        Line,
+        Word,
+        api::SongDetail,
+        api::TransformRequest,
+        Transform,
         QueueEntry,
         api::queue::AddToQueueRequest,
+        DuplicateGroup,
+        DuplicateCandidate,
+        DuplicateReason,
+        RescanStatus,
+        RescanState,
     )),
+    modifiers(&SecurityAddon),
     tags(
         (name = "songs", description = "Song management endpoints"),
         (name = "queue", description = "Queue management endpoints"),
         (name = "files", description = "File serving endpoints"),
+        (name = "admin", description = "Library maintenance endpoints (require ADMIN_TOKEN)"),
     ),
     info(
         title = "Frank Karaoke API",
@@ -48,8 +83,33 @@ use frank::state::{AppState, QueueEntry};
 )]
 struct ApiDoc;
 
+#[derive(Parser)]
+#[command(name = "frank")]
+#[command(about = "Frank karaoke server")]
+#[command(version)]
+struct Args {
+    /// Reparse every song instead of reusing the index cache
+    #[arg(long)]
+    rebuild_index: bool,
+}
+
+/// Registers the bearer token used by admin endpoints
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "admin_token",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
     // Initialize tracing
     tracing_subscriber::registry()
         .with(
@@ -63,12 +123,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = Config::from_env();
     info!("Starting Frank server");
     info!("Songs directory: {:?}", config.songs_directory);
+    info!("Data directory: {:?}", config.data_directory);
 
     // Create application state
     let state = AppState::new(config.clone());
 
     // Index songs
-    state.init_song_index().await?;
+    state.init_song_index(args.rebuild_index).await?;
+
+    // Reload songs when the library changes; dropping the watcher stops it
+    let _watcher = if config.watch_songs {
+        LibraryWatcher::spawn(state.clone())
+            .inspect_err(|e| warn!("Not watching songs directory: {}", e))
+            .ok()
+    } else {
+        None
+    };
 
     // Build CORS layer
     let cors = CorsLayer::new()
@@ -81,11 +151,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .merge(SwaggerUi::new("/").url("/api-docs/openapi.json", ApiDoc::openapi()))
         .route("/api/songs", get(api::list_songs))
         .route("/api/songs/{id}", get(api::get_song))
+        .route("/api/songs/{id}/lyrics", get(api::get_lyrics))
+        .route("/api/songs/{id}/transform", post(api::transform_song))
+        .route("/api/songs/{id}/export.mid", get(api::export_midi))
+        .route("/api/songs/{id}/lyrics.lrc", get(api::export_lrc))
+        .route("/api/songs/{id}/lyrics.vtt", get(api::export_vtt))
+        .route("/api/songs/{id}/lyrics.ass", get(api::export_ass))
         .route("/api/search", get(api::search_songs))
+        .route("/api/events", get(api::library_events))
         .route("/api/queue", get(api::list_queue).post(api::add_to_queue))
         .route("/api/queue/{id}", delete(api::remove_from_queue))
         .route("/api/queue/song/{song_id}", delete(api::remove_by_song))
         .route("/files/{song_id}/{file_type}", get(api::serve_file))
+        .route("/api/admin/duplicates", get(api::find_duplicates))
+        .route("/api/admin/rescan", post(api::start_rescan))
+        .route("/api/admin/rescan/status", get(api::rescan_status))
         .layer(cors)
         .with_state(state);
 
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::warn;
+
+/// File name of the alias table inside the data directory
+pub const ALIAS_FILE_NAME: &str = "aliases.json";
+
+/// Earlier IDs of songs mapped to their current ID
+///
+/// Covers path-based IDs from before IDs were derived from song content,
+/// and IDs that changed because a song's notes were edited, so queue
+/// entries and links saved by clients keep working.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AliasTable {
+    aliases: HashMap<String, String>,
+}
+
+impl AliasTable {
+    /// Load the table; a missing or unreadable file gives an empty one
+    pub fn load(path: &Path) -> Self {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                warn!("Could not read song aliases {:?}: {}", path, e);
+                return Self::default();
+            }
+        };
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!("Could not read song aliases {:?}: {}", path, e);
+            Self::default()
+        })
+    }
+
+    /// Write the table, replacing the previous one only once it is complete
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec(self)?)?;
+        std::fs::rename(&tmp_path, path)
+    }
+
+    /// Make `old` resolve to `new`; returns whether the table changed
+    pub fn insert(&mut self, old: &str, new: &str) -> bool {
+        if old == new || self.resolve(old) == Some(new) {
+            return false;
+        }
+        // Keep lookups to one step when an ID changes again
+        for target in self.aliases.values_mut() {
+            if target == old {
+                *target = new.to_string();
+            }
+        }
+        self.aliases.insert(old.to_string(), new.to_string());
+        // A song that got its old ID back is no longer an alias
+        self.aliases.remove(new);
+        true
+    }
+
+    /// Current ID of a song that was known as `id`
+    pub fn resolve(&self, id: &str) -> Option<&str> {
+        self.aliases.get(id).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.aliases.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_aliases() {
+        let mut aliases = AliasTable::default();
+        assert!(aliases.insert("path", "a"));
+        assert!(!aliases.insert("path", "a"));
+        assert!(!aliases.insert("a", "a"));
+
+        // The song was edited twice; every earlier ID points at the latest
+        assert!(aliases.insert("a", "b"));
+        assert!(aliases.insert("b", "c"));
+        assert_eq!(aliases.resolve("path"), Some("c"));
+        assert_eq!(aliases.resolve("a"), Some("c"));
+        assert_eq!(aliases.resolve("b"), Some("c"));
+
+        // Reverting the last edit brings back the earlier ID
+        assert!(aliases.insert("c", "b"));
+        assert_eq!(aliases.resolve("path"), Some("b"));
+        assert_eq!(aliases.resolve("b"), None);
+        assert_eq!(aliases.resolve("c"), Some("b"));
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("data").join(ALIAS_FILE_NAME);
+        aliases.save(&path).unwrap();
+        assert_eq!(AliasTable::load(&path), aliases);
+        assert!(AliasTable::load(&dir.path().join("missing.json")).is_empty());
+    }
+}
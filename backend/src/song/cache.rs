@@ -0,0 +1,342 @@
+use crate::song::types::{Song, SongFiles, SongMetadata, SourceFormat, Track};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+/// File name of the cache inside the data directory
+pub const CACHE_FILE_NAME: &str = "index.json.gz";
+
+/// Bump whenever parser output or the cached layout changes, so old caches
+/// are rebuilt instead of serving stale songs
+const CACHE_VERSION: u32 = 4;
+
+/// Modification time and size of a song file when it was parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub modified: SystemTime,
+    pub size: u64,
+}
+
+impl FileStamp {
+    pub fn of(path: &Path) -> std::io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(Self {
+            modified: metadata.modified()?,
+            size: metadata.len(),
+        })
+    }
+}
+
+/// A parsed song without its ID, which is recomputed from the content, and
+/// its resolved files, which can change without the song file being touched
+#[derive(Serialize, Deserialize)]
+struct CachedSong {
+    metadata: SongMetadata,
+    /// Not serialized as part of the metadata, but needed to write songs back
+    tag_order: Vec<String>,
+    tracks: Vec<Track>,
+    source_format: SourceFormat,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    stamp: FileStamp,
+    /// ID the song had after the scan, to notice when it changes
+    id: String,
+    song: CachedSong,
+}
+
+/// Parsed songs from a previous scan, keyed by song file path
+///
+/// Stored as gzipped JSON; files that failed to parse are not cached and
+/// are retried on every scan.
+#[derive(Serialize, Deserialize)]
+pub struct IndexCache {
+    version: u32,
+    songs_directory: PathBuf,
+    entries: HashMap<PathBuf, CacheEntry>,
+}
+
+impl IndexCache {
+    pub fn new(songs_directory: &Path) -> Self {
+        Self {
+            version: CACHE_VERSION,
+            songs_directory: songs_directory.to_path_buf(),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Load the cache for a songs directory; a missing, unreadable or
+    /// outdated cache gives an empty one
+    pub fn load(path: &Path, songs_directory: &Path) -> Self {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Self::new(songs_directory)
+            }
+            Err(e) => {
+                warn!("Could not open index cache {:?}: {}", path, e);
+                return Self::new(songs_directory);
+            }
+        };
+
+        let reader = BufReader::new(GzDecoder::new(BufReader::new(file)));
+        match serde_json::from_reader::<_, Self>(reader) {
+            Ok(cache)
+                if cache.version == CACHE_VERSION && cache.songs_directory == songs_directory =>
+            {
+                cache
+            }
+            Ok(_) => {
+                info!("Index cache {:?} is outdated, rebuilding", path);
+                Self::new(songs_directory)
+            }
+            Err(e) => {
+                warn!("Could not read index cache {:?}: {}", path, e);
+                Self::new(songs_directory)
+            }
+        }
+    }
+
+    /// Write the cache, replacing the previous one only once it is complete
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let tmp_path = path.with_extension("tmp");
+        let file = File::create(&tmp_path)?;
+        let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::fast());
+        serde_json::to_writer(&mut encoder, self)?;
+        encoder.finish()?.flush()?;
+        std::fs::rename(&tmp_path, path)
+    }
+
+    /// The cached song for a file, if it has not changed since
+    pub fn get(&self, path: &Path, stamp: FileStamp) -> Option<Song> {
+        let entry = self.entries.get(path).filter(|e| e.stamp == stamp)?;
+        let cached = &entry.song;
+        let mut metadata = cached.metadata.clone();
+        metadata.tag_order = cached.tag_order.clone();
+
+        let mut song = Song {
+            id: String::new(),
+            metadata,
+            tracks: cached.tracks.clone(),
+            source_format: cached.source_format,
+            files: SongFiles::default(),
+        };
+        song.id = song.content_id();
+        Some(song)
+    }
+
+    /// ID the song at `path` had when the cache was written, changed or not
+    pub fn previous_id(&self, path: &Path) -> Option<&str> {
+        self.entries.get(path).map(|e| e.id.as_str())
+    }
+
+    pub fn insert(&mut self, path: PathBuf, stamp: FileStamp, song: &Song) {
+        let cached = CachedSong {
+            metadata: song.metadata.clone(),
+            tag_order: song.metadata.tag_order.clone(),
+            tracks: song.tracks.clone(),
+            source_format: song.source_format,
+        };
+        let entry = CacheEntry {
+            stamp,
+            id: song.id.clone(),
+            song: cached,
+        };
+        self.entries.insert(path, entry);
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::song::fixtures::SIMPLE;
+    use crate::song::indexer::{Indexer, ScanProgress};
+    use std::collections::BTreeSet;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn titles(songs: &HashMap<String, Song>) -> BTreeSet<String> {
+        songs.values().map(|s| s.metadata.title.clone()).collect()
+    }
+
+    #[test]
+    fn test_incremental_scan() {
+        let dir = TempDir::new().unwrap();
+        let songs_dir = dir.path().join("songs");
+        let cache_path = dir.path().join("data").join(CACHE_FILE_NAME);
+        for name in ["one", "two", "three"] {
+            let folder = songs_dir.join(name);
+            std::fs::create_dir_all(&folder).unwrap();
+            let content = SIMPLE.replace("Test Song", &format!("Song {}", name));
+            std::fs::write(folder.join("song.txt"), content).unwrap();
+        }
+
+        let progress = ScanProgress::default();
+        let scan =
+            Indexer::scan_directory_cached(&songs_dir, &cache_path, false, &progress).unwrap();
+        assert_eq!(scan.songs.len(), 3);
+        assert_eq!(progress.found.into_inner(), 3);
+        assert_eq!(progress.indexed.into_inner(), 3);
+        assert!(cache_path.exists());
+
+        // Same size and mtime: the cached song is used without reading the file
+        let one = songs_dir.join("one/song.txt");
+        let stamp = FileStamp::of(&one).unwrap();
+        let content = std::fs::read_to_string(&one).unwrap();
+        std::fs::write(&one, content.replace("Song one", "Song uno")).unwrap();
+        File::options()
+            .write(true)
+            .open(&one)
+            .unwrap()
+            .set_modified(stamp.modified)
+            .unwrap();
+
+        let two = songs_dir.join("two/song.txt");
+        let content = std::fs::read_to_string(&two).unwrap();
+        std::fs::write(&two, content.replace("Song two", "Song 2")).unwrap();
+        File::options()
+            .write(true)
+            .open(&two)
+            .unwrap()
+            .set_modified(stamp.modified + Duration::from_secs(60))
+            .unwrap();
+
+        std::fs::remove_dir_all(songs_dir.join("three")).unwrap();
+
+        let songs = Indexer::scan_directory_cached(
+            &songs_dir,
+            &cache_path,
+            false,
+            &ScanProgress::default(),
+        )
+        .unwrap()
+        .songs;
+        assert_eq!(
+            titles(&songs),
+            BTreeSet::from(["Song one".to_string(), "Song 2".to_string()])
+        );
+        let cached = songs.values().find(|s| s.metadata.title == "Song one");
+        assert_eq!(cached.unwrap().files.txt_path, one);
+        assert!(!cached.unwrap().metadata.tag_order.is_empty());
+
+        let songs =
+            Indexer::scan_directory_cached(&songs_dir, &cache_path, true, &ScanProgress::default())
+                .unwrap()
+                .songs;
+        assert_eq!(
+            titles(&songs),
+            BTreeSet::from(["Song uno".to_string(), "Song 2".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_duplicate_ids() {
+        let dir = TempDir::new().unwrap();
+        let songs_dir = dir.path().join("songs");
+        let cache_path = dir.path().join("data").join(CACHE_FILE_NAME);
+        for name in ["b", "a", "c"] {
+            let folder = songs_dir.join(name);
+            std::fs::create_dir_all(&folder).unwrap();
+            std::fs::write(folder.join("song.txt"), SIMPLE).unwrap();
+        }
+        let scan = |rebuild| {
+            Indexer::scan_directory_cached(
+                &songs_dir,
+                &cache_path,
+                rebuild,
+                &ScanProgress::default(),
+            )
+            .unwrap()
+        };
+        let id_of = |songs: &HashMap<String, Song>, name: &str| {
+            let path = songs_dir.join(name).join("song.txt");
+            songs
+                .values()
+                .find(|s| s.files.txt_path == path)
+                .unwrap()
+                .id
+                .clone()
+        };
+
+        let first = scan(false);
+        assert_eq!(first.songs.len(), 3);
+        assert!(first.renamed.is_empty());
+        // The first copy by path keeps the content-based ID
+        let content_id = Indexer::index_song(&songs_dir.join("a/song.txt"))
+            .unwrap()
+            .id;
+        assert_eq!(id_of(&first.songs, "a"), content_id);
+
+        // Removing it moves the other copies up, whether or not they are cached
+        std::fs::remove_dir_all(songs_dir.join("a")).unwrap();
+        let cached = scan(false);
+        assert_eq!(id_of(&cached.songs, "b"), content_id);
+        assert_eq!(id_of(&cached.songs, "c"), id_of(&first.songs, "b"));
+        assert_eq!(
+            BTreeSet::from_iter(cached.renamed),
+            BTreeSet::from([
+                (id_of(&first.songs, "b"), id_of(&cached.songs, "b")),
+                (id_of(&first.songs, "c"), id_of(&cached.songs, "c")),
+            ])
+        );
+
+        let rebuilt = scan(true);
+        assert_eq!(
+            BTreeSet::from_iter(rebuilt.songs.keys()),
+            BTreeSet::from_iter(cached.songs.keys())
+        );
+        assert!(rebuilt.renamed.is_empty());
+    }
+
+    #[test]
+    fn test_load_rejects_other_cache() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(CACHE_FILE_NAME);
+        let mut cache = IndexCache::new(Path::new("/songs"));
+        cache.insert(
+            PathBuf::from("/songs/a.txt"),
+            FileStamp {
+                modified: SystemTime::UNIX_EPOCH,
+                size: 1,
+            },
+            &Song {
+                id: "a".to_string(),
+                metadata: SongMetadata::default(),
+                tracks: vec![],
+                source_format: SourceFormat::default(),
+                files: SongFiles::default(),
+            },
+        );
+        cache.save(&path).unwrap();
+
+        assert_eq!(IndexCache::load(&path, Path::new("/songs")).len(), 1);
+        assert!(IndexCache::load(&path, Path::new("/other")).is_empty());
+
+        std::fs::write(&path, b"garbage").unwrap();
+        assert!(IndexCache::load(&path, Path::new("/songs")).is_empty());
+    }
+}
@@ -0,0 +1,369 @@
+use crate::song::types::{Song, SourceFormat};
+use crate::song::validator::Validator;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{DefaultHasher, Hasher};
+use std::io::Read;
+use std::path::Path;
+use utoipa::ToSchema;
+
+/// Share of matching intervals above which two melodies count as the same
+const MIN_NOTE_SIMILARITY: f32 = 0.9;
+
+/// Intervals used to bucket songs with the same melody but different names
+const MELODY_PREFIX: usize = 16;
+
+/// Why songs were grouped as duplicates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+    /// Same artist and title after normalizing spelling
+    SameName,
+    /// Byte-identical audio files
+    SameAudio,
+    /// Same title or opening with nearly the same melody
+    SimilarNotes,
+}
+
+/// One copy of a duplicated song
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DuplicateCandidate {
+    pub id: String,
+    pub artist: String,
+    pub title: String,
+    /// Song file the copy was read from
+    pub path: String,
+    /// Validation errors; `None` for songs not read from a TXT file
+    pub errors: Option<usize>,
+    pub warnings: Option<usize>,
+}
+
+/// Songs that are probably the same, best copy first
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DuplicateGroup {
+    pub reasons: Vec<DuplicateReason>,
+    pub songs: Vec<DuplicateCandidate>,
+}
+
+/// Finds songs that appear more than once in a library
+pub struct DuplicateFinder;
+
+impl DuplicateFinder {
+    pub fn find(songs: &[Song]) -> Vec<DuplicateGroup> {
+        let mut matches = Vec::new();
+
+        let names = songs.iter().map(|song| {
+            let metadata = &song.metadata;
+            (normalize(&metadata.artist), normalize(&metadata.title))
+        });
+        matches.extend(pairs(bucket(names.map(Some)), DuplicateReason::SameName));
+
+        // Only files of equal size can be equal, so most are never read
+        let sizes = songs.iter().map(|song| {
+            let path = song.files.audio_path.as_ref()?;
+            Some(std::fs::metadata(path).ok()?.len())
+        });
+        let same_size: Vec<usize> = bucket(sizes).into_iter().flatten().collect();
+        let hashes: HashMap<usize, u64> = same_size
+            .par_iter()
+            .filter_map(|&i| Some((i, hash_file(songs[i].files.audio_path.as_ref()?)?)))
+            .collect();
+        let hashes = (0..songs.len()).map(|i| hashes.get(&i).copied());
+        matches.extend(pairs(bucket(hashes), DuplicateReason::SameAudio));
+
+        let melodies: Vec<Vec<i32>> = songs.iter().map(melody).collect();
+        let titles = songs
+            .iter()
+            .map(|song| Some(normalize(&song.metadata.title)));
+        let openings = melodies
+            .iter()
+            .map(|m| (m.len() >= MELODY_PREFIX).then(|| m[..MELODY_PREFIX].to_vec()));
+        for group in bucket(titles).into_iter().chain(bucket(openings)) {
+            for (a, b) in all_pairs(&group) {
+                if similarity(&melodies[a], &melodies[b]) >= MIN_NOTE_SIMILARITY {
+                    matches.push((a, b, DuplicateReason::SimilarNotes));
+                }
+            }
+        }
+
+        let mut groups = Self::group(songs.len(), &matches);
+        let members: Vec<usize> = groups
+            .iter()
+            .flat_map(|(songs, _)| songs)
+            .copied()
+            .collect();
+        let validation: HashMap<usize, (usize, usize)> = members
+            .par_iter()
+            .filter(|&&i| songs[i].source_format == SourceFormat::UltraStar)
+            .map(|&i| {
+                let result = Validator::validate(&songs[i].files.txt_path);
+                (i, (result.errors.len(), result.warnings.len()))
+            })
+            .collect();
+
+        let mut result: Vec<DuplicateGroup> = groups
+            .iter_mut()
+            .map(|(members, reasons)| {
+                // Fewest problems first, then the copy with the most media
+                members.sort_by_key(|&i| {
+                    let song = &songs[i];
+                    let (errors, warnings) = validation.get(&i).copied().unwrap_or_default();
+                    (
+                        errors,
+                        warnings,
+                        song.files.video_path.is_none(),
+                        song.files.cover_path.is_none(),
+                        song.files.txt_path.clone(),
+                    )
+                });
+                DuplicateGroup {
+                    reasons: reasons.iter().copied().collect(),
+                    songs: members
+                        .iter()
+                        .map(|&i| candidate(&songs[i], validation.get(&i).copied()))
+                        .collect(),
+                }
+            })
+            .collect();
+        result.sort_by(|a, b| {
+            let (a, b) = (&a.songs[0], &b.songs[0]);
+            (&a.artist, &a.title).cmp(&(&b.artist, &b.title))
+        });
+        result
+    }
+
+    /// Merge matching pairs into groups of two or more songs
+    fn group(
+        count: usize,
+        matches: &[(usize, usize, DuplicateReason)],
+    ) -> Vec<(Vec<usize>, BTreeSet<DuplicateReason>)> {
+        let mut parent: Vec<usize> = (0..count).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        for &(a, b, _) in matches {
+            let (a, b) = (root(&mut parent, a), root(&mut parent, b));
+            parent[a] = b;
+        }
+
+        let mut groups: HashMap<usize, (Vec<usize>, BTreeSet<DuplicateReason>)> = HashMap::new();
+        for i in 0..count {
+            let r = root(&mut parent, i);
+            groups.entry(r).or_default().0.push(i);
+        }
+        for &(a, _, reason) in matches {
+            let r = root(&mut parent, a);
+            groups.get_mut(&r).unwrap().1.insert(reason);
+        }
+        groups
+            .into_values()
+            .filter(|(members, _)| members.len() > 1)
+            .collect()
+    }
+}
+
+fn candidate(song: &Song, validation: Option<(usize, usize)>) -> DuplicateCandidate {
+    DuplicateCandidate {
+        id: song.id.clone(),
+        artist: song.metadata.artist.clone(),
+        title: song.metadata.title.clone(),
+        path: song.files.txt_path.to_string_lossy().into_owned(),
+        errors: validation.map(|(errors, _)| errors),
+        warnings: validation.map(|(_, warnings)| warnings),
+    }
+}
+
+/// Indices of items sharing a key, for keys with more than one item
+fn bucket<K: std::hash::Hash + Eq>(keys: impl Iterator<Item = Option<K>>) -> Vec<Vec<usize>> {
+    let mut buckets: HashMap<K, Vec<usize>> = HashMap::new();
+    for (i, key) in keys.enumerate() {
+        if let Some(key) = key {
+            buckets.entry(key).or_default().push(i);
+        }
+    }
+    buckets.into_values().filter(|b| b.len() > 1).collect()
+}
+
+fn all_pairs(items: &[usize]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    items
+        .iter()
+        .enumerate()
+        .flat_map(|(n, &a)| items[n + 1..].iter().map(move |&b| (a, b)))
+}
+
+fn pairs(
+    buckets: Vec<Vec<usize>>,
+    reason: DuplicateReason,
+) -> Vec<(usize, usize, DuplicateReason)> {
+    buckets
+        .iter()
+        .flat_map(|b| {
+            all_pairs(b)
+                .map(|(a, b)| (a, b, reason))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn hash_file(path: &Path) -> Option<u64> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).ok()?;
+        if read == 0 {
+            return Some(hasher.finish());
+        }
+        hasher.write(&buffer[..read]);
+    }
+}
+
+/// Pitch steps between the first singer's notes, independent of key and tempo
+fn melody(song: &Song) -> Vec<i32> {
+    let Some(track) = song.tracks.first() else {
+        return Vec::new();
+    };
+    track
+        .notes
+        .windows(2)
+        .map(|pair| pair[1].pitch.saturating_sub(pair[0].pitch))
+        .collect()
+}
+
+fn similarity(a: &[i32], b: &[i32]) -> f32 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let ops = similar::capture_diff_slices(similar::Algorithm::Myers, a, b);
+    similar::get_diff_ratio(&ops, a.len(), b.len())
+}
+
+/// Artist or title reduced to what survives different spellings: lower case,
+/// without accents, punctuation, a leading "The", bracketed notes like
+/// "(Radio Edit)" or featured artists
+pub fn normalize(text: &str) -> String {
+    let lower = text.to_lowercase();
+    let main = [" feat.", " feat ", " ft.", " featuring "]
+        .iter()
+        .filter_map(|marker| lower.find(marker))
+        .min()
+        .map_or(lower.as_str(), |end| &lower[..end]);
+
+    let mut words = String::new();
+    let mut depth = 0usize;
+    for c in main.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            _ if depth > 0 => {}
+            '&' => words.push_str(" and "),
+            c if c.is_alphanumeric() => words.push_str(&fold_accent(c)),
+            _ => words.push(' '),
+        }
+    }
+
+    let mut words: Vec<&str> = words.split_whitespace().collect();
+    if words.len() > 1 && words[0] == "the" {
+        words.remove(0);
+    }
+    words.concat()
+}
+
+fn fold_accent(c: char) -> String {
+    let folded = match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => 'a',
+        'ç' => 'c',
+        'è' | 'é' | 'ê' | 'ë' => 'e',
+        'ì' | 'í' | 'î' | 'ï' => 'i',
+        'ñ' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => 'o',
+        'ù' | 'ú' | 'û' | 'ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'ß' => return "ss".to_string(),
+        c => c,
+    };
+    folded.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::song::indexer::Indexer;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("The Beatles"), normalize("Beatles"));
+        assert_eq!(normalize("Beyoncé feat. Jay-Z"), "beyonce");
+        assert_eq!(normalize("Don't Stop Me Now (Live)"), "dontstopmenow");
+        assert_eq!(normalize("Simon & Garfunkel"), "simonandgarfunkel");
+        assert_eq!(normalize("The The"), "the");
+    }
+
+    #[test]
+    fn test_melody_extreme_pitches() {
+        let content = "#TITLE:T\n#ARTIST:A\n#BPM:300\n: 0 2 -2147483648 a\n: 4 2 2147483647 b\n: 8 2 -2147483648 c\nE\n";
+        let song = crate::song::Parser::parse(content, Path::new("song.txt")).unwrap();
+        assert_eq!(melody(&song), vec![i32::MAX, i32::MIN]);
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let dir = TempDir::new().unwrap();
+        let notes = ": 0 2 0 a\n: 4 2 2 b\n: 8 2 4 c\n: 12 2 5 d\n: 16 2 7 e\n- 20\n: 24 2 5 f\n: 28 2 4 g\nE\n";
+        let songs = [
+            ("a", "The Beatles", "Yesterday", "one.mp3", ""),
+            // Ranked last because of the BOM warning
+            (
+                "b",
+                "Beatles",
+                "Yesterday (Remastered)",
+                "two.mp3",
+                "\u{FEFF}",
+            ),
+            ("c", "Unknown", "Track 01", "one.mp3", ""),
+            ("d", "Beetles", "Yesterday", "three.mp3", ""),
+            ("e", "Other", "Song", "four.mp3", ""),
+        ];
+        for (folder, artist, title, audio, bom) in songs {
+            let path = dir.path().join(folder);
+            std::fs::create_dir(&path).unwrap();
+            std::fs::write(path.join(audio), format!("audio {}", audio)).unwrap();
+            let content = format!(
+                "{}#TITLE:{}\n#ARTIST:{}\n#BPM:300\n#AUDIO:{}\n{}",
+                bom, title, artist, audio, notes
+            );
+            std::fs::write(path.join("song.txt"), content).unwrap();
+        }
+        // "Other - Song" has a different melody
+        let other = dir.path().join("e/song.txt");
+        let content = std::fs::read_to_string(&other).unwrap();
+        std::fs::write(&other, content.replace(": 4 2 2 b", ": 4 2 9 b")).unwrap();
+
+        let mut songs: Vec<Song> = Indexer::scan_directory(dir.path())
+            .unwrap()
+            .into_values()
+            .collect();
+        songs.sort_by(|a, b| a.files.txt_path.cmp(&b.files.txt_path));
+        let groups = DuplicateFinder::find(&songs);
+
+        assert_eq!(groups.len(), 1);
+        let group = &groups[0];
+        assert_eq!(
+            group.reasons,
+            vec![
+                DuplicateReason::SameName,
+                DuplicateReason::SameAudio,
+                DuplicateReason::SimilarNotes
+            ]
+        );
+        let artists: Vec<_> = group.songs.iter().map(|s| s.artist.as_str()).collect();
+        assert_eq!(artists, ["The Beatles", "Unknown", "Beetles", "Beatles"]);
+        assert_eq!(group.songs[3].warnings, Some(1));
+    }
+}
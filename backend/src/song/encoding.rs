@@ -0,0 +1,156 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// Text decoded from a song file, with details on how it was decoded
+#[derive(Debug, Clone)]
+pub struct DecodedText {
+    pub text: String,
+    pub encoding: &'static Encoding,
+    /// The file started with a byte order mark (stripped from `text`)
+    pub had_bom: bool,
+    /// Some bytes were invalid for the chosen encoding and were replaced
+    pub had_errors: bool,
+}
+
+impl DecodedText {
+    pub fn is_utf8(&self) -> bool {
+        self.encoding == UTF_8
+    }
+}
+
+/// Decode raw song file bytes into text
+///
+/// Detection order follows what UltraStar players do:
+/// 1. A byte order mark (UTF-8, UTF-16 LE/BE) wins and is stripped.
+/// 2. A legacy `#ENCODING:` header selects the encoding.
+/// 3. Valid UTF-8 is taken as is.
+/// 4. Anything else is read as Windows-1252, the de-facto encoding of old song packs.
+pub fn decode(bytes: &[u8]) -> DecodedText {
+    let (encoding, had_bom, body) = if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+        (UTF_8, true, rest)
+    } else if let Some(rest) = bytes.strip_prefix(UTF16LE_BOM) {
+        (UTF_16LE, true, rest)
+    } else if let Some(rest) = bytes.strip_prefix(UTF16BE_BOM) {
+        (UTF_16BE, true, rest)
+    } else if let Some(encoding) = encoding_from_header(bytes) {
+        (encoding, false, bytes)
+    } else if std::str::from_utf8(bytes).is_ok() {
+        (UTF_8, false, bytes)
+    } else {
+        (WINDOWS_1252, false, bytes)
+    };
+
+    let (text, had_errors) = encoding.decode_without_bom_handling(body);
+
+    DecodedText {
+        text: text.into_owned(),
+        encoding,
+        had_bom,
+        had_errors,
+    }
+}
+
+/// Look for an `#ENCODING:` tag in the header lines
+///
+/// Header lines are ASCII in every supported single-byte encoding, so the
+/// raw bytes can be scanned before the encoding is known.
+fn encoding_from_header(bytes: &[u8]) -> Option<&'static Encoding> {
+    for line in bytes.split(|&b| b == b'\n') {
+        let line = line.trim_ascii();
+        if line.is_empty() {
+            continue;
+        }
+        // Headers end where the notes begin
+        let header = line.strip_prefix(b"#")?;
+        let Some(colon) = header.iter().position(|&b| b == b':') else {
+            continue;
+        };
+        let (tag, value) = (&header[..colon], &header[colon + 1..]);
+        if tag.trim_ascii().eq_ignore_ascii_case(b"ENCODING") {
+            return encoding_for_label(value.trim_ascii());
+        }
+    }
+
+    None
+}
+
+/// Map `#ENCODING` values used by UltraStar tools onto encodings
+fn encoding_for_label(label: &[u8]) -> Option<&'static Encoding> {
+    match label.to_ascii_uppercase().as_slice() {
+        // "AUTO" and "LOCALE" defer to detection
+        b"AUTO" | b"LOCALE" => None,
+        b"UTF8" => Some(UTF_8),
+        b"CP1250" => Some(encoding_rs::WINDOWS_1250),
+        b"CP1252" => Some(WINDOWS_1252),
+        _ => Encoding::for_label(label),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_utf8() {
+        let decoded = decode("#TITLE:Café\n".as_bytes());
+
+        assert_eq!(decoded.text, "#TITLE:Café\n");
+        assert!(decoded.is_utf8());
+        assert!(!decoded.had_bom);
+    }
+
+    #[test]
+    fn test_strips_utf8_bom() {
+        let decoded = decode(b"\xEF\xBB\xBF#TITLE:Song\n");
+
+        assert_eq!(decoded.text, "#TITLE:Song\n");
+        assert!(decoded.is_utf8());
+        assert!(decoded.had_bom);
+    }
+
+    #[test]
+    fn test_utf16_boms() {
+        let mut le = vec![0xFF, 0xFE];
+        let mut be = vec![0xFE, 0xFF];
+        for unit in "#TITLE:Ä€".encode_utf16() {
+            le.extend_from_slice(&unit.to_le_bytes());
+            be.extend_from_slice(&unit.to_be_bytes());
+        }
+
+        assert_eq!(decode(&le).text, "#TITLE:Ä€");
+        assert_eq!(decode(&le).encoding, UTF_16LE);
+        assert_eq!(decode(&be).text, "#TITLE:Ä€");
+        assert_eq!(decode(&be).encoding, UTF_16BE);
+    }
+
+    #[test]
+    fn test_cp1252_fallback() {
+        // 0x80 is the euro sign and 0x93/0x94 are curly quotes in CP1252
+        let decoded = decode(b"#TITLE:\x93Caf\xE9\x94 \x80\n");
+
+        assert_eq!(decoded.text, "#TITLE:\u{201C}Café\u{201D} €\n");
+        assert_eq!(decoded.encoding, WINDOWS_1252);
+    }
+
+    #[test]
+    fn test_encoding_header() {
+        // Valid UTF-8 bytes, but the header says CP1252
+        let decoded = decode(b"#ENCODING:CP1252\n#TITLE:Caf\xC3\xA9\n");
+        assert_eq!(decoded.encoding, WINDOWS_1252);
+        assert_eq!(decoded.text, "#ENCODING:CP1252\n#TITLE:CafÃ©\n");
+
+        let decoded = decode(b"#TITLE:Caf\xC3\xA9\n#ENCODING:UTF8\n: 0 1 2 x\n");
+        assert!(decoded.is_utf8());
+        assert_eq!(decoded.text, "#TITLE:Café\n#ENCODING:UTF8\n: 0 1 2 x\n");
+    }
+
+    #[test]
+    fn test_encoding_header_after_notes_is_ignored() {
+        let decoded = decode(b"#TITLE:Song\n: 0 1 2 x\n#ENCODING:CP1250\n");
+
+        assert!(decoded.is_utf8());
+    }
+}
@@ -0,0 +1,257 @@
+use crate::song::lyrics::split_lines;
+use crate::song::types::{NoteType, Song};
+use midly::num::{u15, u24, u28, u4, u7};
+use midly::{Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+
+/// MIDI ticks per quarter note; an UltraStar beat is a sixteenth of that
+const TICKS_PER_QUARTER: u16 = 480;
+const TICKS_PER_BEAT: i64 = TICKS_PER_QUARTER as i64 / 4;
+
+/// MIDI key of UltraStar pitch 0 (middle C)
+const MIDDLE_C: i32 = 60;
+
+/// Note types are told apart by velocity
+fn velocity(note_type: NoteType) -> u8 {
+    match note_type {
+        NoteType::Normal => 90,
+        NoteType::Golden => 127,
+        NoteType::Freestyle => 40,
+        NoteType::Rap => 64,
+        NoteType::GoldenRap => 110,
+    }
+}
+
+/// Events sharing a tick are ordered note-off, lyric, note-on so that
+/// consecutive notes never overlap and lyrics precede their note
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum EventOrder {
+    NoteOff,
+    Lyric,
+    NoteOn,
+}
+
+/// Exporter for Standard MIDI files, the counterpart of
+/// [`MidiImporter`](crate::song::import::MidiImporter)
+pub struct MidiExporter;
+
+impl MidiExporter {
+    /// Render a song as a format 1 MIDI file
+    ///
+    /// The first track carries title and tempo; every singer gets a track
+    /// and channel of its own with one lyric event per syllable. Lines start
+    /// with a carriage return, and note types are encoded in the velocity.
+    /// `#GAP` becomes leading silence so notes line up with the audio.
+    pub fn export(song: &Song) -> Vec<u8> {
+        let bpm = song.metadata.bpm;
+        let tempo = (60_000_000.0 / bpm).round().clamp(1.0, 16_777_215.0) as u32;
+        let ms_per_tick = 60_000.0 / (bpm * TICKS_PER_QUARTER as f64);
+        let gap_ticks = (song.metadata.gap / ms_per_tick).round() as i64;
+        let to_tick = |beat: i32| (gap_ticks + beat as i64 * TICKS_PER_BEAT).max(0) as u32;
+
+        // Lyric texts are borrowed by the events, so build them up front
+        let lyrics: Vec<Vec<String>> = song
+            .tracks
+            .iter()
+            .map(|track| {
+                split_lines(&track.notes, &track.line_breaks)
+                    .into_iter()
+                    .enumerate()
+                    .flat_map(|(line, notes)| {
+                        notes.iter().enumerate().map(move |(i, note)| {
+                            if line > 0 && i == 0 {
+                                format!("\r{}", note.text)
+                            } else {
+                                note.text.clone()
+                            }
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+        let singers: Vec<String> = song
+            .tracks
+            .iter()
+            .enumerate()
+            .map(|(i, track)| {
+                track
+                    .singer
+                    .clone()
+                    .unwrap_or_else(|| format!("Player {}", i + 1))
+            })
+            .collect();
+        let title = format!("{} - {}", song.metadata.artist, song.metadata.title);
+
+        let mut smf = Smf::new(Header::new(
+            Format::Parallel,
+            Timing::Metrical(u15::new(TICKS_PER_QUARTER)),
+        ));
+
+        smf.tracks.push(Self::to_track(vec![
+            (
+                0,
+                EventOrder::Lyric,
+                TrackEventKind::Meta(MetaMessage::TrackName(title.as_bytes())),
+            ),
+            (
+                0,
+                EventOrder::Lyric,
+                TrackEventKind::Meta(MetaMessage::Tempo(u24::new(tempo))),
+            ),
+            (
+                0,
+                EventOrder::Lyric,
+                TrackEventKind::Meta(MetaMessage::TimeSignature(4, 2, 24, 8)),
+            ),
+        ]));
+
+        for (index, track) in song.tracks.iter().enumerate() {
+            // Skip channel 10, which is reserved for percussion
+            let channel = match index {
+                0..=8 => index,
+                _ => (index + 1).min(15),
+            };
+            let channel = u4::new(channel as u8);
+            let mut events = vec![(
+                0,
+                EventOrder::Lyric,
+                TrackEventKind::Meta(MetaMessage::TrackName(singers[index].as_bytes())),
+            )];
+
+            for (note, lyric) in track.notes.iter().zip(&lyrics[index]) {
+                let key = u7::new((note.pitch + MIDDLE_C).clamp(0, 127) as u8);
+                let start = to_tick(note.start_beat);
+                let end = to_tick(note.start_beat + note.length.max(1));
+
+                events.push((
+                    start,
+                    EventOrder::Lyric,
+                    TrackEventKind::Meta(MetaMessage::Lyric(lyric.as_bytes())),
+                ));
+                events.push((
+                    start,
+                    EventOrder::NoteOn,
+                    TrackEventKind::Midi {
+                        channel,
+                        message: MidiMessage::NoteOn {
+                            key,
+                            vel: u7::new(velocity(note.note_type)),
+                        },
+                    },
+                ));
+                events.push((
+                    end,
+                    EventOrder::NoteOff,
+                    TrackEventKind::Midi {
+                        channel,
+                        message: MidiMessage::NoteOff {
+                            key,
+                            vel: u7::new(0),
+                        },
+                    },
+                ));
+            }
+
+            smf.tracks.push(Self::to_track(events));
+        }
+
+        let mut bytes = Vec::new();
+        smf.write_std(&mut bytes)
+            .expect("writing to a Vec cannot fail");
+        bytes
+    }
+
+    /// Sort absolute-time events and turn them into a delta-time track
+    fn to_track(mut events: Vec<(u32, EventOrder, TrackEventKind)>) -> Vec<TrackEvent> {
+        events.sort_by_key(|(tick, order, _)| (*tick, *order));
+
+        let mut last_tick = 0;
+        let mut track: Vec<TrackEvent> = events
+            .into_iter()
+            .map(|(tick, _, kind)| {
+                let delta = tick - last_tick;
+                last_tick = tick;
+                TrackEvent {
+                    delta: u28::new(delta),
+                    kind,
+                }
+            })
+            .collect();
+        track.push(TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+        track
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::song::fixtures;
+    use crate::song::import::{MidiImportOptions, MidiImporter};
+    use crate::song::parser::Parser;
+    use std::path::Path;
+
+    fn parse(content: &str) -> Song {
+        Parser::parse(content, Path::new("test.txt")).unwrap()
+    }
+
+    #[test]
+    fn test_export_tracks_and_tempo() {
+        let song = parse(fixtures::DUET);
+        let bytes = MidiExporter::export(&song);
+        let smf = Smf::parse(&bytes).unwrap();
+
+        assert_eq!(smf.tracks.len(), 3);
+        assert!(smf.tracks[0]
+            .iter()
+            .any(|e| e.kind == TrackEventKind::Meta(MetaMessage::Tempo(u24::new(150_000)))));
+        assert!(smf.tracks[2]
+            .iter()
+            .any(|e| e.kind == TrackEventKind::Meta(MetaMessage::TrackName(b"Singer Two"))));
+        let channels: Vec<u8> = smf.tracks[2]
+            .iter()
+            .filter_map(|e| match e.kind {
+                TrackEventKind::Midi { channel, .. } => Some(channel.as_int()),
+                _ => None,
+            })
+            .collect();
+        assert!(channels.iter().all(|&c| c == 1));
+    }
+
+    #[test]
+    fn test_note_types_use_velocity() {
+        let song = parse(fixtures::NOTE_TYPES);
+        let smf_bytes = MidiExporter::export(&song);
+        let smf = Smf::parse(&smf_bytes).unwrap();
+
+        let velocities: Vec<u8> = smf.tracks[1]
+            .iter()
+            .filter_map(|e| match e.kind {
+                TrackEventKind::Midi {
+                    message: MidiMessage::NoteOn { vel, .. },
+                    ..
+                } => Some(vel.as_int()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(velocities, vec![90, 127, 40, 64, 110]);
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let song = parse(fixtures::SIMPLE);
+        let bytes = MidiExporter::export(&song);
+
+        let options = MidiImportOptions {
+            track: Some(1),
+            bpm: Some(song.metadata.bpm),
+            ..Default::default()
+        };
+        let imported = MidiImporter::import(&bytes, Path::new("test.mid"), &options).unwrap();
+
+        assert_eq!(imported.metadata.gap, song.metadata.gap);
+        assert_eq!(imported.tracks[0].notes, song.tracks[0].notes);
+        assert_eq!(imported.tracks[0].line_breaks.len(), 1);
+    }
+}
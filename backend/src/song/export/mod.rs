@@ -0,0 +1,5 @@
+pub mod midi;
+pub mod subtitles;
+
+pub use midi::MidiExporter;
+pub use subtitles::{SubtitleExporter, SubtitleFormat};
@@ -0,0 +1,272 @@
+use crate::song::lyrics::{Line, Lyrics};
+use crate::song::types::Song;
+use std::fmt::Write as _;
+
+/// Subtitle formats lyrics can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleFormat {
+    /// Enhanced LRC with word-level timestamps
+    Lrc,
+    /// WebVTT with inline word timestamps, for `<track>` elements
+    WebVtt,
+    /// Advanced SubStation Alpha with `\k` karaoke tags per syllable
+    Ass,
+}
+
+impl SubtitleFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SubtitleFormat::Lrc => "lrc",
+            SubtitleFormat::WebVtt => "vtt",
+            SubtitleFormat::Ass => "ass",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            SubtitleFormat::Lrc => "text/plain; charset=utf-8",
+            SubtitleFormat::WebVtt => "text/vtt; charset=utf-8",
+            SubtitleFormat::Ass => "text/x-ssa; charset=utf-8",
+        }
+    }
+}
+
+/// Exporter for timed lyrics in subtitle formats
+///
+/// All timestamps are relative to the start of the audio file.
+pub struct SubtitleExporter;
+
+impl SubtitleExporter {
+    pub fn export(song: &Song, format: SubtitleFormat) -> String {
+        let lyrics = Lyrics::from_song(song);
+        match format {
+            SubtitleFormat::Lrc => Self::lrc(song, &lyrics),
+            SubtitleFormat::WebVtt => Self::webvtt(&lyrics),
+            SubtitleFormat::Ass => Self::ass(song, &lyrics),
+        }
+    }
+
+    /// Lines of all tracks in time order, with the track index
+    fn timed_lines(lyrics: &Lyrics) -> Vec<(usize, &Line)> {
+        let mut lines: Vec<_> = lyrics
+            .tracks
+            .iter()
+            .enumerate()
+            .flat_map(|(i, track)| track.lines.iter().map(move |line| (i, line)))
+            .collect();
+        lines.sort_by(|(_, a), (_, b)| a.start.total_cmp(&b.start));
+        lines
+    }
+
+    fn lrc(song: &Song, lyrics: &Lyrics) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "[ti:{}]", song.metadata.title);
+        let _ = writeln!(out, "[ar:{}]", song.metadata.artist);
+
+        let mut lines = Self::timed_lines(lyrics);
+        // Duets repeat lines both singers sing together
+        lines.dedup_by(|(_, a), (_, b)| a.start == b.start && a.text == b.text);
+
+        for (_, line) in lines {
+            let _ = write!(out, "[{}]", lrc_time(line.start));
+            for word in &line.words {
+                let _ = write!(out, "<{}>{} ", lrc_time(word.start), word.text);
+            }
+            let _ = writeln!(out, "<{}>", lrc_time(line.end));
+        }
+
+        out
+    }
+
+    fn webvtt(lyrics: &Lyrics) -> String {
+        let mut out = String::from("WEBVTT\n");
+        let multi_track = lyrics.tracks.len() > 1;
+
+        for (track, line) in Self::timed_lines(lyrics) {
+            let _ = write!(
+                out,
+                "\n{} --> {}\n",
+                vtt_time(line.start),
+                vtt_time(line.end)
+            );
+            if multi_track {
+                let singer = lyrics.tracks[track]
+                    .singer
+                    .clone()
+                    .unwrap_or_else(|| format!("Player {}", track + 1));
+                let _ = write!(out, "<v {}>", vtt_escape(&singer));
+            }
+            for (i, word) in line.words.iter().enumerate() {
+                if i > 0 {
+                    let _ = write!(out, " <{}>", vtt_time(word.start));
+                }
+                out.push_str(&vtt_escape(&word.text));
+            }
+            out.push('\n');
+        }
+
+        out
+    }
+
+    fn ass(song: &Song, lyrics: &Lyrics) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "[Script Info]\n\
+             Title: {} - {}\n\
+             ScriptType: v4.00+\n\
+             WrapStyle: 0\n\
+             PlayResX: 1920\n\
+             PlayResY: 1080\n\
+             \n\
+             [V4+ Styles]\n\
+             Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, \
+             BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, \
+             BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n",
+            song.metadata.artist, song.metadata.title
+        );
+        for (i, _) in lyrics.tracks.iter().enumerate() {
+            // First singer at the bottom, second at the top, others centered
+            let alignment = match i {
+                0 => 2,
+                1 => 8,
+                _ => 5,
+            };
+            let _ = writeln!(
+                out,
+                "Style: P{},Arial,64,&H00FFFFFF,&H0000A5FF,&H00000000,&H80000000,\
+                 -1,0,0,0,100,100,0,0,1,3,1,{},40,40,60,1",
+                i + 1,
+                alignment
+            );
+        }
+        out.push_str(
+            "\n[Events]\n\
+             Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+        );
+
+        for (track, line) in Self::timed_lines(lyrics) {
+            let singer = lyrics.tracks[track].singer.as_deref().unwrap_or_default();
+            let mut text = String::new();
+            let mut cursor = line.start;
+            for note in &line.notes {
+                let timing = song.note_timing(note);
+                // Pauses between syllables are highlighted as empty syllables
+                let pause = centiseconds(timing.start_ms - cursor);
+                if pause > 0 {
+                    let _ = write!(text, "{{\\k{}}}", pause);
+                }
+                let syllable = note.text.replace(['{', '}'], "").replace('~', "");
+                let _ = write!(
+                    text,
+                    "{{\\k{}}}{}",
+                    centiseconds(timing.end_ms - timing.start_ms),
+                    syllable
+                );
+                cursor = timing.end_ms;
+            }
+            let _ = writeln!(
+                out,
+                "Dialogue: 0,{},{},P{},{},0,0,0,,{}",
+                ass_time(line.start),
+                ass_time(line.end),
+                track + 1,
+                singer.replace(',', " "),
+                text.trim_start()
+            );
+        }
+
+        out
+    }
+}
+
+fn centiseconds(ms: f64) -> i64 {
+    (ms.max(0.0) / 10.0).round() as i64
+}
+
+/// `mm:ss.xx`
+fn lrc_time(ms: f64) -> String {
+    let cs = centiseconds(ms);
+    format!("{:02}:{:02}.{:02}", cs / 6000, cs / 100 % 60, cs % 100)
+}
+
+/// `hh:mm:ss.mmm`
+fn vtt_time(ms: f64) -> String {
+    let ms = ms.max(0.0).round() as i64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+/// `h:mm:ss.cc`
+fn ass_time(ms: f64) -> String {
+    let cs = centiseconds(ms);
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        cs / 360_000,
+        cs / 6000 % 60,
+        cs / 100 % 60,
+        cs % 100
+    )
+}
+
+fn vtt_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::song::fixtures;
+    use crate::song::parser::Parser;
+    use std::path::Path;
+
+    fn parse(content: &str) -> Song {
+        Parser::parse(content, Path::new("test.txt")).unwrap()
+    }
+
+    #[test]
+    fn test_time_formats() {
+        assert_eq!(lrc_time(61_234.0), "01:01.23");
+        assert_eq!(vtt_time(3_723_456.0), "01:02:03.456");
+        assert_eq!(ass_time(3_723_456.0), "1:02:03.46");
+    }
+
+    #[test]
+    fn test_lrc() {
+        let lrc = SubtitleExporter::export(&parse(fixtures::SIMPLE), SubtitleFormat::Lrc);
+
+        assert_eq!(
+            lrc,
+            "[ti:Test Song]\n[ar:Test Artist]\n\
+             [00:01.00]<00:01.00>Hello <00:01.40>world <00:01.55>\n\
+             [00:02.00]<00:02.00>Test <00:02.20>\n"
+        );
+    }
+
+    #[test]
+    fn test_webvtt_voices() {
+        let vtt = SubtitleExporter::export(&parse(fixtures::DUET), SubtitleFormat::WebVtt);
+
+        assert!(vtt.starts_with("WEBVTT\n"));
+        assert!(vtt.contains("\n00:00:00.450 --> 00:00:00.638\n<v Singer One>again\n"));
+        assert!(vtt.contains("<v Singer Two>"));
+    }
+
+    #[test]
+    fn test_ass_karaoke_tags() {
+        let ass = SubtitleExporter::export(&parse(fixtures::SIMPLE), SubtitleFormat::Ass);
+
+        assert!(ass.contains("[Events]"));
+        // 50ms per beat: "Hello" 5 beats, a 3 beat pause, then " world" 3 beats
+        assert!(ass.contains(
+            "Dialogue: 0,0:00:01.00,0:00:01.55,P1,,0,0,0,,{\\k25}Hello{\\k15}{\\k15} world\n"
+        ));
+    }
+}
@@ -0,0 +1,325 @@
+use crate::song::encoding;
+use crate::song::indexer::Indexer;
+use crate::song::parser::ParseError;
+use crate::song::tokenizer::{Token, Tokenizer};
+use crate::song::writer::Writer;
+use similar::TextDiff;
+use std::path::Path;
+
+/// Header tags holding decimal numbers
+const DECIMAL_TAGS: &[&str] = &["BPM", "GAP", "VIDEOGAP", "START", "END", "PREVIEWSTART"];
+
+/// A safe, mechanical repair of a song file
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fix {
+    StripBom,
+    /// Transcoded from the named encoding; drops a stale `#ENCODING` tag
+    TranscodeToUtf8(String),
+    AddEndMarker,
+    /// Notes of a track were sorted by start beat
+    SortNotes,
+    /// Decimal commas in numeric headers replaced by points
+    NormalizeDecimals,
+    /// `#COVER` set to an image found next to the file
+    FillCover(String),
+}
+
+impl std::fmt::Display for Fix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::StripBom => write!(f, "Strip BOM"),
+            Self::TranscodeToUtf8(from) => write!(f, "Transcode from {} to UTF-8", from),
+            Self::AddEndMarker => write!(f, "Add 'E' end marker"),
+            Self::SortNotes => write!(f, "Sort notes by start beat"),
+            Self::NormalizeDecimals => write!(f, "Use '.' as decimal separator"),
+            Self::FillCover(cover) => write!(f, "Set #COVER to {}", cover),
+        }
+    }
+}
+
+/// Outcome of [`Fixer::fix`]
+#[derive(Debug, Clone)]
+pub struct FixResult {
+    /// The file as decoded before fixing
+    pub original: String,
+    /// The repaired file, to be written as UTF-8
+    pub fixed: String,
+    pub fixes: Vec<Fix>,
+}
+
+impl FixResult {
+    pub fn is_changed(&self) -> bool {
+        !self.fixes.is_empty()
+    }
+
+    /// Unified diff between the original and the fixed text
+    pub fn diff(&self, path: &Path) -> String {
+        let name = path.to_string_lossy();
+        TextDiff::from_lines(&self.original, &self.fixed)
+            .unified_diff()
+            .header(&name, &name)
+            .to_string()
+    }
+}
+
+/// Repairs common problems in UltraStar TXT files without touching anything else
+///
+/// Works on the raw lines rather than re-writing the parsed song, so
+/// formatting and unknown lines are kept as they are.
+pub struct Fixer;
+
+impl Fixer {
+    /// Fix a file in place, keeping the original as `<name>.bak`
+    ///
+    /// Nothing is written if there is nothing to fix or `dry_run` is set.
+    pub fn fix_file(txt_path: &Path, dry_run: bool) -> std::io::Result<FixResult> {
+        let bytes = std::fs::read(txt_path)?;
+        let result = Self::fix(&bytes, txt_path);
+
+        if result.is_changed() && !dry_run {
+            Writer::write_file_with_backup(txt_path, result.fixed.as_bytes())?;
+        }
+        Ok(result)
+    }
+
+    pub fn fix(bytes: &[u8], txt_path: &Path) -> FixResult {
+        let decoded = encoding::decode(bytes);
+        let mut fixes = Vec::new();
+        if decoded.had_bom {
+            fixes.push(Fix::StripBom);
+        }
+        let transcoded = !decoded.is_utf8();
+        if transcoded {
+            fixes.push(Fix::TranscodeToUtf8(decoded.encoding.name().to_string()));
+        }
+
+        let original = decoded.text;
+        let newline = if original.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        let mut lines: Vec<String> = original.split_inclusive('\n').map(String::from).collect();
+        let tokens: Vec<_> = Tokenizer::new(&original)
+            .map(|(line, token)| (line.number - 1, token))
+            .collect();
+
+        let mut relative = false;
+        let mut has_end = false;
+        let mut last_header = None;
+        let mut cover = None;
+        let mut removed = Vec::new();
+        for (index, token) in &tokens {
+            match token {
+                Ok(Token::Header { tag, value, .. }) => {
+                    last_header = Some(*index);
+                    match tag.as_str() {
+                        // The file is written as UTF-8 from now on
+                        "ENCODING" if transcoded => removed.push(*index),
+                        "RELATIVE" => relative = value.eq_ignore_ascii_case("yes"),
+                        "COVER" => cover = Some((*index, value.to_string())),
+                        tag if DECIMAL_TAGS.contains(&tag) && value.contains(',') => {
+                            let normalized = value.replace(',', ".");
+                            if normalized.parse::<f64>().is_ok() {
+                                let line = &lines[*index];
+                                lines[*index] = line.replacen(value, &normalized, 1);
+                                if !fixes.contains(&Fix::NormalizeDecimals) {
+                                    fixes.push(Fix::NormalizeDecimals);
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(Token::End) => has_end = true,
+                _ => {}
+            }
+        }
+
+        // Relative beats restart every line, so their order cannot be checked
+        if !relative && Self::sort_notes(&mut lines, &tokens, newline) {
+            fixes.push(Fix::SortNotes);
+        }
+
+        // Lines are only inserted and removed from here on, back to front
+        if !has_end {
+            if lines.last().is_some_and(|line| !line.ends_with('\n')) {
+                lines.last_mut().unwrap().push_str(newline);
+            }
+            lines.push(format!("E{}", newline));
+            fixes.push(Fix::AddEndMarker);
+        }
+
+        let dir = txt_path.parent().unwrap_or(Path::new("."));
+        let cover_missing = match &cover {
+            Some((_, value)) => value.is_empty() || !dir.join(value).exists(),
+            None => true,
+        };
+        if cover_missing {
+            let found = Indexer::find_cover_image(dir)
+                .and_then(|path| path.file_name().map(|n| n.to_string_lossy().into_owned()));
+            if let Some(name) = found {
+                let line = format!("#COVER:{}{}", name, newline);
+                match cover {
+                    Some((index, _)) => lines[index] = line,
+                    None => lines.insert(last_header.map_or(0, |i| i + 1), line),
+                }
+                fixes.push(Fix::FillCover(name));
+            }
+        }
+
+        // Removed lines are all headers, so they precede an inserted #COVER
+        for index in removed.into_iter().rev() {
+            lines.remove(index);
+        }
+
+        FixResult {
+            fixed: lines.concat(),
+            original,
+            fixes,
+        }
+    }
+
+    /// Sort the note and line break lines of every track whose notes are out
+    /// of order, keeping the set of line positions used by the track
+    ///
+    /// Tracks containing lines that cannot be read are left alone.
+    fn sort_notes(
+        lines: &mut [String],
+        tokens: &[(usize, Result<Token, ParseError>)],
+        newline: &str,
+    ) -> bool {
+        // (line index, start beat, is a note) per track
+        let mut tracks: Vec<Option<Vec<(usize, i32, bool)>>> = vec![Some(Vec::new())];
+        for (index, token) in tokens {
+            let entry = match token {
+                Ok(Token::Player(_)) => {
+                    tracks.push(Some(Vec::new()));
+                    continue;
+                }
+                Ok(Token::Note(note)) => (*index, note.start_beat, true),
+                Ok(Token::LineBreak(line_break)) => (*index, line_break.start_beat, false),
+                Ok(Token::Header { .. }) | Ok(Token::End) => continue,
+                Ok(Token::Unknown) | Err(_) => {
+                    *tracks.last_mut().unwrap() = None;
+                    continue;
+                }
+            };
+            if let Some(track) = tracks.last_mut().unwrap() {
+                track.push(entry);
+            }
+        }
+
+        let mut changed = false;
+        for track in tracks.into_iter().flatten() {
+            let note_beats: Vec<i32> = track
+                .iter()
+                .filter(|(_, _, is_note)| *is_note)
+                .map(|(_, beat, _)| *beat)
+                .collect();
+            if note_beats.windows(2).all(|w| w[0] <= w[1]) {
+                continue;
+            }
+
+            let positions: Vec<usize> = track.iter().map(|(index, _, _)| *index).collect();
+            let mut sorted = track;
+            // A line break on a note's beat ends the line before it
+            sorted.sort_by_key(|(_, beat, is_note)| (*beat, *is_note));
+            let contents: Vec<String> = sorted
+                .iter()
+                .map(|(index, _, _)| {
+                    let line = lines[*index].trim_end_matches(['\r', '\n']);
+                    format!("{}{}", line, newline)
+                })
+                .collect();
+            for (position, content) in positions.into_iter().zip(contents) {
+                lines[position] = content;
+            }
+            changed = true;
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::song::parser::Parser;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_bom_decimals_and_end_marker() {
+        let content = "\u{FEFF}#TITLE:Test\n#ARTIST:Artist\n#BPM:300,5\n#GAP:1000\n: 0 5 7 Hello\n";
+        let result = Fixer::fix(content.as_bytes(), Path::new("song.txt"));
+
+        assert_eq!(
+            result.fixes,
+            vec![Fix::StripBom, Fix::NormalizeDecimals, Fix::AddEndMarker]
+        );
+        assert_eq!(
+            result.fixed,
+            "#TITLE:Test\n#ARTIST:Artist\n#BPM:300.5\n#GAP:1000\n: 0 5 7 Hello\nE\n"
+        );
+        assert!(result
+            .diff(Path::new("song.txt"))
+            .contains("-#BPM:300,5\n+#BPM:300.5\n"));
+    }
+
+    #[test]
+    fn test_transcode_drops_encoding_tag() {
+        let mut bytes = b"#TITLE:Caf".to_vec();
+        bytes.push(0xE9);
+        bytes.extend_from_slice(
+            b"\r\n#ENCODING:CP1252\r\n#ARTIST:A\r\n#BPM:300\r\n: 0 5 7 a\r\nE\r\n",
+        );
+        let result = Fixer::fix(&bytes, Path::new("song.txt"));
+
+        assert_eq!(
+            result.fixes,
+            vec![Fix::TranscodeToUtf8("windows-1252".to_string())]
+        );
+        assert_eq!(
+            result.fixed,
+            "#TITLE:Café\r\n#ARTIST:A\r\n#BPM:300\r\n: 0 5 7 a\r\nE\r\n"
+        );
+    }
+
+    #[test]
+    fn test_sort_notes() {
+        let content = "#TITLE:T\n#ARTIST:A\n#BPM:300\n: 8 3 5  world\n: 0 5 7 Hello\n- 15\n: 20 4 7 Test\nE\n";
+        let result = Fixer::fix(content.as_bytes(), Path::new("song.txt"));
+
+        assert_eq!(result.fixes, vec![Fix::SortNotes]);
+        assert_eq!(
+            result.fixed,
+            "#TITLE:T\n#ARTIST:A\n#BPM:300\n: 0 5 7 Hello\n: 8 3 5  world\n- 15\n: 20 4 7 Test\nE\n"
+        );
+
+        // Already sorted files are left alone
+        let again = Fixer::fix(result.fixed.as_bytes(), Path::new("song.txt"));
+        assert!(!again.is_changed());
+        assert_eq!(again.fixed, result.fixed);
+    }
+
+    #[test]
+    fn test_fill_cover() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("cover.jpg"), b"").unwrap();
+        let path = dir.path().join("song.txt");
+        let content = "#TITLE:T\n#ARTIST:A\n#COVER:missing.jpg\n#BPM:300\n: 0 5 7 a\nE\n";
+
+        let result = Fixer::fix(content.as_bytes(), &path);
+        assert_eq!(result.fixes, vec![Fix::FillCover("cover.jpg".to_string())]);
+        assert!(result.fixed.contains("#COVER:cover.jpg\n#BPM"));
+
+        let content = "#TITLE:T\n#ARTIST:A\n#ENCODING:CP1252\n#BPM:300\n: 0 5 7 \u{E9}\nE\n";
+        let bytes: Vec<u8> = content.chars().map(|c| c as u8).collect();
+        let result = Fixer::fix(&bytes, &path);
+        assert_eq!(
+            result.fixed,
+            "#TITLE:T\n#ARTIST:A\n#BPM:300\n#COVER:cover.jpg\n: 0 5 7 é\nE\n"
+        );
+        let song = Parser::parse(&result.fixed, &path).unwrap();
+        assert_eq!(song.metadata.cover_file.as_deref(), Some("cover.jpg"));
+    }
+}
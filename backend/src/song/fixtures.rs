@@ -0,0 +1,139 @@
+//! Sample UltraStar files shared by the song module tests
+
+pub const SIMPLE: &str = r#"#TITLE:Test Song
+#ARTIST:Test Artist
+#MP3:test.mp3
+#BPM:300
+#GAP:1000
+: 0 5 7 Hello
+: 8 3 5  world
+- 15
+: 20 4 7 Test
+E
+"#;
+
+pub const NOTE_TYPES: &str = r#"#TITLE:Note Types
+#ARTIST:Test Artist
+#BPM:400
+: 0 5 7 Normal
+* 8 3 5  Golden
+F 15 2 3 Free
+- 20 22
+R 24 5 7 Rap~
+G 30 3 -5 Golden rap
+E
+"#;
+
+pub const DUET: &str = r#"#TITLE:Duet Song
+#ARTIST:Test Artists
+#BPM:400
+#DUETSINGERP1:Singer One
+#DUETSINGERP2:Singer Two
+P1
+: 0 5 7 Player one
+- 10
+: 12 5 7  again
+P2
+: 0 5 5 Player two
+- 10
+: 12 5 5  too
+E
+"#;
+
+pub const COMMA_DECIMALS: &str = r#"#TITLE:Comma BPM
+#ARTIST:Test
+#BPM:312,5
+#GAP:1234,56
+#VIDEOGAP:0,5
+: 0 5 7 Test
+E
+"#;
+
+pub const FORMAT_1_1: &str = r#"#VERSION:1.1.0
+#TITLE:Version Test
+#ARTIST:Test
+#MP3:legacy.mp3
+#AUDIO:song.ogg
+#VOCALS:vocals.ogg
+#INSTRUMENTAL:instrumental.ogg
+#BPM:400
+#START:12.5
+#END:183000
+#PREVIEWSTART:45,5
+#MEDLEYSTARTBEAT:120
+#MEDLEYENDBEAT:480
+#P1:Singer One
+#P2:Singer Two
+P1
+: 0 5 7 One
+P2
+: 0 5 7 Two
+E
+"#;
+
+pub const RELATIVE: &str = r#"#TITLE:Relative Test
+#ARTIST:Test
+#BPM:400
+#RELATIVE:yes
+: 0 5 7 One
+: 6 3 5 Two
+- 12 20
+: 0 4 7 Three
+E
+"#;
+
+pub const CUSTOM_TAGS: &str = r#"#TITLE:Custom
+#ARTIST:Test
+#AUTHOR:Someone
+#TAGS:rock, live
+#BPM:250
+#COMMENT:First
+#COMMENT:Second
+#GENRE:Rock
+#YEAR:1999
+: 0 5 7 Hi
+E
+"#;
+
+pub const DUET_BOTH: &str = r#"#TITLE:Both
+#ARTIST:Test
+#BPM:300
+#P1:Singer One
+#P2:Singer Two
+P1
+: 0 4 7 One
+P2
+: 8 4 5 Two
+P3
+- 14
+: 16 4 6 Together
+E
+"#;
+
+pub const THREE_TRACKS: &str = r#"#TITLE:Trio
+#ARTIST:Test
+#BPM:300
+#P1:Soprano
+#P2:Alto
+#P3:Tenor
+P1
+: 0 4 7 High
+P2
+: 0 4 3 Mid
+P3
+: 0 4 -5 Low
+E
+"#;
+
+/// All fixtures with a short name for assertion messages
+pub const FIXTURES: &[(&str, &str)] = &[
+    ("simple", SIMPLE),
+    ("note_types", NOTE_TYPES),
+    ("duet", DUET),
+    ("comma_decimals", COMMA_DECIMALS),
+    ("format_1_1", FORMAT_1_1),
+    ("relative", RELATIVE),
+    ("custom_tags", CUSTOM_TAGS),
+    ("duet_both", DUET_BOTH),
+    ("three_tracks", THREE_TRACKS),
+];
@@ -0,0 +1,603 @@
+use super::normalize_word_spacing;
+use crate::song::types::{
+    LineBreak, Note, NoteType, Song, SongFiles, SongMetadata, SourceFormat, Track,
+};
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+use std::path::Path;
+use thiserror::Error;
+
+/// MIDI key of UltraStar pitch 0 (middle C)
+const MIDDLE_C: i32 = 60;
+
+/// Tempo assumed until the first tempo event (120 quarter notes per minute)
+const DEFAULT_TEMPO_US: u32 = 500_000;
+
+/// Without lyric line markers, rests of at least this many beats start a new line
+const LINE_BREAK_REST_BEATS: i32 = 16;
+
+#[derive(Debug, Error)]
+pub enum MidiImportError {
+    #[error("Invalid MIDI file: {0}")]
+    Parse(#[from] midly::Error),
+
+    #[error("Track {0} does not exist (the file has {1} tracks)")]
+    TrackNotFound(usize, usize),
+
+    #[error("No track with notes found")]
+    NoNotes,
+
+    #[error("Invalid BPM: {0}")]
+    InvalidBpm(f64),
+}
+
+/// Options for [`MidiImporter::import`]
+#[derive(Debug, Clone, Default)]
+pub struct MidiImportOptions {
+    /// Track to read the melody from; defaults to the first track with notes,
+    /// preferring one that also carries lyrics
+    pub track: Option<usize>,
+    /// BPM to quantize to; defaults to the file's initial tempo
+    pub bpm: Option<f64>,
+    /// Title; defaults to the sequence name, then the file name
+    pub title: Option<String>,
+    pub artist: Option<String>,
+}
+
+/// Overview of one MIDI track, for picking the vocal track
+#[derive(Debug, Clone, PartialEq)]
+pub struct MidiTrackInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub note_count: usize,
+    pub lyric_count: usize,
+}
+
+/// A note as read from the MIDI file, in seconds
+#[derive(Debug, Clone)]
+struct RawNote {
+    key: i32,
+    start_tick: u64,
+    start: f64,
+    end: f64,
+    text: String,
+    line_break_before: bool,
+}
+
+/// A lyric or KAR text event
+#[derive(Debug, Clone)]
+struct Syllable {
+    tick: u64,
+    text: String,
+    line_break_before: bool,
+}
+
+/// Importer for Standard MIDI and KAR files
+pub struct MidiImporter;
+
+impl MidiImporter {
+    /// List the tracks of a MIDI file
+    pub fn tracks(bytes: &[u8]) -> Result<Vec<MidiTrackInfo>, MidiImportError> {
+        let smf = Smf::parse(bytes)?;
+
+        Ok(smf
+            .tracks
+            .iter()
+            .enumerate()
+            .map(|(index, track)| MidiTrackInfo {
+                index,
+                name: Self::track_name(track),
+                note_count: track
+                    .iter()
+                    .filter(|e| {
+                        matches!(
+                            e.kind,
+                            TrackEventKind::Midi {
+                                message: MidiMessage::NoteOn { vel, .. },
+                                ..
+                            } if vel > 0
+                        )
+                    })
+                    .count(),
+                lyric_count: Self::syllables(track).len(),
+            })
+            .collect())
+    }
+
+    /// Build a single-track song from a MIDI melody
+    ///
+    /// Notes are quantized to UltraStar beats at the chosen BPM, with `#GAP`
+    /// set to the start of the first note. Lyric events (or KAR text events)
+    /// become syllables of the notes they coincide with.
+    pub fn import(
+        bytes: &[u8],
+        path: &Path,
+        options: &MidiImportOptions,
+    ) -> Result<Song, MidiImportError> {
+        let smf = Smf::parse(bytes)?;
+        let tempo_map = TempoMap::new(&smf);
+
+        let track_index = match options.track {
+            Some(index) if index >= smf.tracks.len() => {
+                return Err(MidiImportError::TrackNotFound(index, smf.tracks.len()))
+            }
+            Some(index) => index,
+            None => Self::pick_track(&smf).ok_or(MidiImportError::NoNotes)?,
+        };
+
+        let mut raw_notes = Self::raw_notes(&smf.tracks[track_index], &tempo_map);
+        if raw_notes.is_empty() {
+            return Err(MidiImportError::NoNotes);
+        }
+
+        // Lyrics usually share the melody track, but KAR files keep them apart
+        let mut syllables = Self::syllables(&smf.tracks[track_index]);
+        if syllables.is_empty() {
+            syllables = smf
+                .tracks
+                .iter()
+                .map(|track| Self::syllables(track))
+                .find(|s| !s.is_empty())
+                .unwrap_or_default();
+        }
+        let has_lyrics = !syllables.is_empty();
+        Self::attach_syllables(&mut raw_notes, syllables);
+
+        let bpm = match options.bpm {
+            Some(bpm) if bpm > 0.0 && bpm.is_finite() => bpm,
+            Some(bpm) => return Err(MidiImportError::InvalidBpm(bpm)),
+            None => tempo_map.initial_bpm(),
+        };
+        let gap = (raw_notes[0].start * 1000.0).round();
+        let ms_per_beat = 60000.0 / (bpm * 4.0);
+        let to_beat = |seconds: f64| ((seconds * 1000.0 - gap) / ms_per_beat).round() as i32;
+
+        let mut notes: Vec<Note> = Vec::with_capacity(raw_notes.len());
+        let mut line_breaks = Vec::new();
+        let mut line_starts = Vec::with_capacity(raw_notes.len());
+        for raw in &raw_notes {
+            let previous_end = notes.last().map(|n: &Note| n.start_beat + n.length);
+            let start_beat = to_beat(raw.start).max(previous_end.unwrap_or(0));
+            let length = (to_beat(raw.end) - start_beat).max(1);
+
+            let new_line = match previous_end {
+                None => true,
+                Some(_) if has_lyrics => raw.line_break_before,
+                Some(previous_end) => start_beat - previous_end >= LINE_BREAK_REST_BEATS,
+            };
+            if let Some(previous_end) = previous_end.filter(|_| new_line) {
+                line_breaks.push(LineBreak {
+                    start_beat: previous_end,
+                    end_beat: None,
+                });
+            }
+            line_starts.push(new_line);
+
+            notes.push(Note {
+                note_type: NoteType::Normal,
+                start_beat,
+                length,
+                pitch: raw.key - MIDDLE_C,
+                text: raw.text.clone(),
+            });
+        }
+        normalize_word_spacing(&mut notes, &line_starts);
+
+        let title = options
+            .title
+            .clone()
+            .or_else(|| smf.tracks.first().and_then(|t| Self::track_name(t)))
+            .or_else(|| path.file_stem().map(|s| s.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "Untitled".to_string());
+
+        let mut song = Song {
+            id: String::new(),
+            metadata: SongMetadata {
+                title,
+                artist: options
+                    .artist
+                    .clone()
+                    .unwrap_or_else(|| "Unknown".to_string()),
+                bpm,
+                gap,
+                ..Default::default()
+            },
+            tracks: vec![Track {
+                singer: None,
+                notes,
+                line_breaks,
+            }],
+            source_format: SourceFormat::Midi,
+            files: SongFiles {
+                txt_path: path.to_path_buf(),
+                ..Default::default()
+            },
+        };
+        song.id = song.content_id();
+        Ok(song)
+    }
+
+    /// First track with notes, preferring tracks that also carry lyrics
+    fn pick_track(smf: &Smf) -> Option<usize> {
+        let has_notes = |track: &Vec<TrackEvent>| {
+            track.iter().any(|e| {
+                matches!(
+                    e.kind,
+                    TrackEventKind::Midi {
+                        message: MidiMessage::NoteOn { .. },
+                        ..
+                    }
+                )
+            })
+        };
+
+        smf.tracks
+            .iter()
+            .position(|t| has_notes(t) && !Self::syllables(t).is_empty())
+            .or_else(|| smf.tracks.iter().position(has_notes))
+    }
+
+    fn track_name(track: &[TrackEvent]) -> Option<String> {
+        track.iter().find_map(|e| match e.kind {
+            TrackEventKind::Meta(MetaMessage::TrackName(name)) => {
+                Some(decode_text(name).trim().to_string()).filter(|n| !n.is_empty())
+            }
+            _ => None,
+        })
+    }
+
+    /// Notes of a track, made monophonic by cutting overlapping notes short
+    fn raw_notes(track: &[TrackEvent], tempo_map: &TempoMap) -> Vec<RawNote> {
+        let mut notes: Vec<RawNote> = Vec::new();
+        let mut sounding: Vec<(u8, usize)> = Vec::new();
+        let mut tick = 0u64;
+
+        for event in track {
+            tick += event.delta.as_int() as u64;
+            let TrackEventKind::Midi { message, .. } = event.kind else {
+                continue;
+            };
+            match message {
+                MidiMessage::NoteOn { key, vel } if vel > 0 => {
+                    sounding.push((key.as_int(), notes.len()));
+                    let time = tempo_map.seconds(tick);
+                    notes.push(RawNote {
+                        key: key.as_int() as i32,
+                        start_tick: tick,
+                        start: time,
+                        end: time,
+                        text: String::new(),
+                        line_break_before: false,
+                    });
+                }
+                MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                    if let Some(pos) = sounding.iter().position(|(k, _)| *k == key.as_int()) {
+                        let (_, index) = sounding.remove(pos);
+                        notes[index].end = tempo_map.seconds(tick);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        notes.sort_by_key(|n| n.start_tick);
+        for i in 1..notes.len() {
+            let next_start = notes[i].start;
+            let previous = &mut notes[i - 1];
+            previous.end = previous.end.min(next_start);
+        }
+        notes.retain(|n| n.end > n.start);
+        notes
+    }
+
+    /// Lyric events of a track, or its text events if it is a KAR lyrics track
+    ///
+    /// KAR marks new lines with a leading `/` and paragraphs with `\`;
+    /// lyric events often end lines with a carriage return instead.
+    fn syllables(track: &[TrackEvent]) -> Vec<Syllable> {
+        let mut lyrics = Vec::new();
+        let mut texts = Vec::new();
+        let mut is_kar = false;
+        let mut tick = 0u64;
+
+        for event in track {
+            tick += event.delta.as_int() as u64;
+            match event.kind {
+                TrackEventKind::Meta(MetaMessage::Lyric(text)) => lyrics.push((tick, text)),
+                // KAR information lines start with '@'
+                TrackEventKind::Meta(MetaMessage::Text(text)) if text.starts_with(b"@") => {
+                    is_kar = true
+                }
+                TrackEventKind::Meta(MetaMessage::Text(text)) => texts.push((tick, text)),
+                TrackEventKind::Meta(MetaMessage::TrackName(name)) => {
+                    is_kar |= name.eq_ignore_ascii_case(b"words")
+                }
+                _ => {}
+            }
+        }
+
+        let events = match (lyrics.is_empty(), is_kar) {
+            (false, _) => lyrics,
+            (true, true) => texts,
+            (true, false) => return Vec::new(),
+        };
+        let mut syllables: Vec<Syllable> = Vec::new();
+        let mut break_pending = false;
+
+        for (tick, raw) in events {
+            let text = decode_text(raw);
+            let mut line_break_before = break_pending;
+            let mut text = text.as_str();
+            if let Some(rest) = text.strip_prefix(['/', '\\', '\r', '\n']) {
+                line_break_before = true;
+                text = rest;
+            }
+            break_pending = text.ends_with(['\r', '\n']);
+            let text = text.trim_end_matches(['\r', '\n']);
+
+            if text.is_empty() {
+                // Pure line markers apply to the next syllable
+                break_pending |= line_break_before;
+                continue;
+            }
+            syllables.push(Syllable {
+                tick,
+                text: text.to_string(),
+                line_break_before,
+            });
+        }
+
+        syllables
+    }
+
+    /// Give every syllable to the note it starts on (or the next one)
+    fn attach_syllables(notes: &mut [RawNote], syllables: Vec<Syllable>) {
+        let has_lyrics = !syllables.is_empty();
+
+        for syllable in syllables {
+            let index = notes
+                .partition_point(|n| n.start_tick < syllable.tick)
+                .min(notes.len() - 1);
+            let note = &mut notes[index];
+            note.text.push_str(&syllable.text);
+            note.line_break_before |= syllable.line_break_before;
+        }
+
+        if has_lyrics {
+            for note in notes.iter_mut().filter(|n| n.text.is_empty()) {
+                // Notes without a syllable hold the previous one
+                note.text = "~".to_string();
+            }
+        }
+    }
+}
+
+/// Decode MIDI text, which is UTF-8 in newer files and Windows-1252 in old ones
+fn decode_text(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => encoding_rs::WINDOWS_1252.decode(bytes).0.into_owned(),
+    }
+}
+
+/// Converts MIDI ticks to seconds, following tempo changes
+struct TempoMap {
+    timing: Timing,
+    /// (tick, microseconds per quarter note), sorted by tick
+    changes: Vec<(u64, u32)>,
+}
+
+impl TempoMap {
+    fn new(smf: &Smf) -> Self {
+        let mut changes = Vec::new();
+        for track in &smf.tracks {
+            let mut tick = 0u64;
+            for event in track {
+                tick += event.delta.as_int() as u64;
+                if let TrackEventKind::Meta(MetaMessage::Tempo(tempo)) = event.kind {
+                    changes.push((tick, tempo.as_int()));
+                }
+            }
+        }
+        changes.sort_by_key(|(tick, _)| *tick);
+
+        Self {
+            timing: smf.header.timing,
+            changes,
+        }
+    }
+
+    /// Tempo at the start of the file in quarter notes per minute
+    fn initial_bpm(&self) -> f64 {
+        let tempo = match self.changes.first() {
+            Some((0, tempo)) => *tempo,
+            _ => DEFAULT_TEMPO_US,
+        };
+        60_000_000.0 / tempo as f64
+    }
+
+    fn seconds(&self, tick: u64) -> f64 {
+        match self.timing {
+            Timing::Metrical(ticks_per_beat) => {
+                let ticks_per_beat = ticks_per_beat.as_int().max(1) as f64;
+                let mut seconds = 0.0;
+                let mut last_tick = 0u64;
+                let mut tempo = DEFAULT_TEMPO_US;
+                for &(change_tick, change_tempo) in &self.changes {
+                    if change_tick >= tick {
+                        break;
+                    }
+                    seconds += (change_tick - last_tick) as f64 * tempo as f64
+                        / 1_000_000.0
+                        / ticks_per_beat;
+                    last_tick = change_tick;
+                    tempo = change_tempo;
+                }
+                seconds + (tick - last_tick) as f64 * tempo as f64 / 1_000_000.0 / ticks_per_beat
+            }
+            Timing::Timecode(fps, subframes) => {
+                tick as f64 / (fps.as_f32() as f64 * subframes.max(1) as f64)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::song::parser::Parser;
+    use crate::song::writer::Writer;
+    use midly::{Format, Header, TrackEvent};
+
+    /// A 480 ticks-per-quarter file with a tempo track and one melody track
+    fn midi_file(tempo_us: u32, melody: Vec<TrackEvent<'static>>) -> Vec<u8> {
+        let mut smf = Smf::new(Header::new(Format::Parallel, Timing::Metrical(480.into())));
+        smf.tracks.push(vec![
+            TrackEvent {
+                delta: 0.into(),
+                kind: TrackEventKind::Meta(MetaMessage::TrackName(b"Sequence")),
+            },
+            TrackEvent {
+                delta: 0.into(),
+                kind: TrackEventKind::Meta(MetaMessage::Tempo(tempo_us.into())),
+            },
+        ]);
+        smf.tracks.push(melody);
+
+        let mut bytes = Vec::new();
+        smf.write_std(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn note(delta: u32, key: u8, on: bool) -> TrackEvent<'static> {
+        let message = if on {
+            MidiMessage::NoteOn {
+                key: key.into(),
+                vel: 100.into(),
+            }
+        } else {
+            MidiMessage::NoteOff {
+                key: key.into(),
+                vel: 0.into(),
+            }
+        };
+        TrackEvent {
+            delta: delta.into(),
+            kind: TrackEventKind::Midi {
+                channel: 0.into(),
+                message,
+            },
+        }
+    }
+
+    fn meta(delta: u32, message: MetaMessage<'static>) -> TrackEvent<'static> {
+        TrackEvent {
+            delta: delta.into(),
+            kind: TrackEventKind::Meta(message),
+        }
+    }
+
+    #[test]
+    fn test_import_melody_with_lyrics() {
+        // 120 qpm: one quarter note is 500ms, i.e. 4 UltraStar beats
+        let bytes = midi_file(
+            500_000,
+            vec![
+                meta(480, MetaMessage::Lyric(b"Hel")),
+                note(0, 60, true),
+                note(480, 60, false),
+                meta(0, MetaMessage::Lyric(b"lo ")),
+                note(0, 62, true),
+                note(240, 62, false),
+                meta(240, MetaMessage::Lyric(b"world\r")),
+                note(0, 55, true),
+                note(480, 55, false),
+                meta(480, MetaMessage::Lyric(b"Next")),
+                note(0, 72, true),
+                note(960, 72, false),
+            ],
+        );
+
+        let song = MidiImporter::import(
+            &bytes,
+            Path::new("melody.mid"),
+            &MidiImportOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(song.metadata.title, "Sequence");
+        assert_eq!(song.metadata.bpm, 120.0);
+        assert_eq!(song.metadata.gap, 500.0);
+        let notes = &song.tracks[0].notes;
+        let summary: Vec<_> = notes
+            .iter()
+            .map(|n| (n.start_beat, n.length, n.pitch, n.text.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, 4, 0, "Hel"),
+                (4, 2, 2, "lo"),
+                (8, 4, -5, " world"),
+                (16, 8, 12, "Next"),
+            ]
+        );
+        assert_eq!(song.tracks[0].line_breaks.len(), 1);
+        assert_eq!(song.tracks[0].line_breaks[0].start_beat, 12);
+
+        // The result must survive the TXT writer and parser
+        let written = Writer::write(&song);
+        let reparsed = Parser::parse(&written, Path::new("melody.txt")).unwrap();
+        assert_eq!(reparsed.tracks[0].notes, *notes);
+        assert_eq!(reparsed.id, song.id);
+    }
+
+    #[test]
+    fn test_import_kar_text_events() {
+        let bytes = midi_file(
+            500_000,
+            vec![
+                meta(0, MetaMessage::Text(b"@TKaraoke Song")),
+                meta(0, MetaMessage::Text(b"\\Fa")),
+                note(0, 64, true),
+                note(480, 64, false),
+                meta(0, MetaMessage::Text(b"/La")),
+                note(0, 65, true),
+                note(480, 65, false),
+                note(0, 67, true),
+                note(480, 67, false),
+            ],
+        );
+
+        let options = MidiImportOptions {
+            track: Some(1),
+            bpm: Some(240.0),
+            title: Some("Title".to_string()),
+            artist: Some("Artist".to_string()),
+        };
+        let song = MidiImporter::import(&bytes, Path::new("song.kar"), &options).unwrap();
+
+        let track = &song.tracks[0];
+        let texts: Vec<_> = track.notes.iter().map(|n| n.text.as_str()).collect();
+        assert_eq!(texts, vec!["Fa", "La", "~"]);
+        // 240 BPM: a 500ms quarter note spans 8 beats
+        assert_eq!(track.notes[1].start_beat, 8);
+        assert_eq!(track.line_breaks.len(), 1);
+        assert_eq!(song.metadata.title, "Title");
+    }
+
+    #[test]
+    fn test_missing_track() {
+        let bytes = midi_file(500_000, vec![note(0, 60, true), note(480, 60, false)]);
+        let options = MidiImportOptions {
+            track: Some(5),
+            ..Default::default()
+        };
+
+        let err = MidiImporter::import(&bytes, Path::new("x.mid"), &options).unwrap_err();
+        assert!(matches!(err, MidiImportError::TrackNotFound(5, 2)));
+
+        let tracks = MidiImporter::tracks(&bytes).unwrap();
+        assert_eq!(tracks[1].note_count, 1);
+        assert_eq!(tracks[0].name.as_deref(), Some("Sequence"));
+    }
+}
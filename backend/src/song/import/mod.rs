@@ -0,0 +1,24 @@
+use crate::song::types::Note;
+
+pub mod midi;
+pub mod xml;
+
+pub use midi::{MidiImportError, MidiImportOptions, MidiImporter, MidiTrackInfo};
+pub use xml::{XmlImportError, XmlImporter};
+
+/// Move word-separating spaces to the start of the following syllable,
+/// as UltraStar expects, and drop them at line starts
+fn normalize_word_spacing(notes: &mut [Note], line_starts: &[bool]) {
+    let ends_word: Vec<bool> = notes.iter().map(|n| n.text.ends_with(' ')).collect();
+
+    for (i, note) in notes.iter_mut().enumerate() {
+        let text = note.text.trim_end();
+        note.text = if line_starts[i] {
+            text.trim_start().to_string()
+        } else if i > 0 && ends_word[i - 1] && !text.starts_with(' ') {
+            format!(" {}", text)
+        } else {
+            text.to_string()
+        };
+    }
+}
@@ -0,0 +1,469 @@
+use super::normalize_word_spacing;
+use crate::song::types::{
+    LineBreak, Note, NoteType, Song, SongFiles, SongMetadata, SourceFormat, Track,
+};
+use roxmltree::{Document, Node};
+use std::collections::HashMap;
+use std::path::Path;
+use thiserror::Error;
+
+/// MIDI key of UltraStar pitch 0 (middle C)
+const MIDDLE_C: i32 = 60;
+
+#[derive(Debug, Error)]
+pub enum XmlImportError {
+    #[error("Invalid XML: {0}")]
+    Parse(#[from] roxmltree::Error),
+
+    #[error("Not a MelodyXML file (root element is <{0}>)")]
+    NotMelody(String),
+
+    #[error("Invalid tempo: {0:?}")]
+    InvalidTempo(String),
+
+    #[error("Unknown resolution: {0:?}")]
+    UnknownResolution(String),
+
+    #[error("Invalid note on line {0}: {1}")]
+    InvalidNote(u32, String),
+
+    #[error("No notes found")]
+    NoNotes,
+}
+
+/// A sung note in MelodyXML ticks
+#[derive(Debug, Clone)]
+struct XmlNote {
+    tick: u32,
+    duration: u32,
+    key: i32,
+    note_type: NoteType,
+    lyric: String,
+}
+
+/// A `<SENTENCE>` and the singer part it belongs to in single-timeline duets
+#[derive(Debug, Clone)]
+struct Sentence {
+    part: Option<String>,
+    notes: Vec<XmlNote>,
+}
+
+/// The sentences of one singer
+#[derive(Debug, Clone, Default)]
+struct XmlTrack {
+    singer: Option<String>,
+    sentences: Vec<Vec<XmlNote>>,
+}
+
+/// Importer for SingStar MelodyXML files, including Performous `notes.xml`
+pub struct XmlImporter;
+
+impl XmlImporter {
+    /// Convert a MelodyXML document into a song
+    ///
+    /// `Tempo` counts quarter notes and notes are measured in ticks of the
+    /// `Resolution`, so the song's BPM is picked to make one beat one tick.
+    /// Notes with `MidiNote="0"` are rests, every `<SENTENCE>` becomes a
+    /// line, and a trailing `-` joins a syllable to the next one. Duets are
+    /// read from `<TRACK>` elements or from the `Singer` attribute of
+    /// sentences, where "Group" sentences are sung by both players.
+    pub fn import(content: &str, path: &Path) -> Result<Song, XmlImportError> {
+        let doc = Document::parse(content)?;
+        let root = doc.root_element();
+        if !root.tag_name().name().eq_ignore_ascii_case("MELODY") {
+            return Err(XmlImportError::NotMelody(
+                root.tag_name().name().to_string(),
+            ));
+        }
+
+        let tempo_value = attribute(root, "Tempo").unwrap_or_default();
+        let tempo = tempo_value
+            .trim()
+            .replace(',', ".")
+            .parse::<f64>()
+            .ok()
+            .filter(|t| *t > 0.0 && t.is_finite())
+            .ok_or_else(|| XmlImportError::InvalidTempo(tempo_value.to_string()))?;
+        let ticks_per_sixteenth = match attribute(root, "Resolution") {
+            None => 1.0,
+            Some(r) if r.eq_ignore_ascii_case("Semiquaver") => 1.0,
+            Some(r) if r.eq_ignore_ascii_case("Demisemiquaver") => 2.0,
+            Some(r) => return Err(XmlImportError::UnknownResolution(r.to_string())),
+        };
+        let bpm = tempo * ticks_per_sixteenth;
+
+        let xml_tracks = Self::read_tracks(&doc, root)?;
+        let origin = xml_tracks
+            .iter()
+            .flat_map(|t| t.sentences.iter().flatten())
+            .map(|n| n.tick)
+            .min()
+            .ok_or(XmlImportError::NoNotes)?;
+        let gap = (origin as f64 * 60000.0 / (bpm * 4.0)).round();
+
+        let metadata = Self::metadata(&doc, root, path, bpm, gap);
+        let tracks = xml_tracks
+            .into_iter()
+            .enumerate()
+            .map(|(i, track)| Track {
+                singer: track
+                    .singer
+                    .or_else(|| metadata.singer(i + 1).map(String::from)),
+                ..Self::convert_track(&track.sentences, origin)
+            })
+            .collect();
+
+        let is_performous = path
+            .file_name()
+            .is_some_and(|name| name.eq_ignore_ascii_case("notes.xml"));
+
+        let mut song = Song {
+            id: String::new(),
+            metadata,
+            tracks,
+            source_format: if is_performous {
+                SourceFormat::Performous
+            } else {
+                SourceFormat::SingStar
+            },
+            files: SongFiles {
+                txt_path: path.to_path_buf(),
+                ..Default::default()
+            },
+        };
+        song.id = song.content_id();
+        Ok(song)
+    }
+
+    /// Sentences per singer, from `<TRACK>` elements or `Singer` attributes
+    fn read_tracks(doc: &Document, root: Node) -> Result<Vec<XmlTrack>, XmlImportError> {
+        let track_elements: Vec<Node> = elements(root, "TRACK").collect();
+        if !track_elements.is_empty() {
+            let mut tracks = Vec::new();
+            for element in track_elements {
+                let sentences = Self::read_sentences(doc, element)?;
+                tracks.push(XmlTrack {
+                    singer: attribute(element, "Artist").map(String::from),
+                    sentences: sentences.into_iter().map(|s| s.notes).collect(),
+                });
+            }
+            return Ok(tracks);
+        }
+
+        let sentences = Self::read_sentences(doc, root)?;
+        let part = |sentence: &Sentence| {
+            sentence
+                .part
+                .as_deref()
+                .map(|p| p.replace(' ', "").to_lowercase())
+        };
+        let is_duet = sentences
+            .iter()
+            .any(|s| part(s).as_deref() == Some("solo2"));
+
+        let mut tracks = vec![XmlTrack::default(); if is_duet { 2 } else { 1 }];
+        for sentence in &sentences {
+            match part(sentence).as_deref() {
+                Some("solo2") => tracks[1].sentences.push(sentence.notes.clone()),
+                Some("solo1") | None => tracks[0].sentences.push(sentence.notes.clone()),
+                // "Group" and anything unknown is sung by everyone
+                Some(_) => {
+                    for track in &mut tracks {
+                        track.sentences.push(sentence.notes.clone());
+                    }
+                }
+            }
+        }
+        Ok(tracks)
+    }
+
+    /// Read the `<SENTENCE>` children of an element on one timeline
+    fn read_sentences(doc: &Document, parent: Node) -> Result<Vec<Sentence>, XmlImportError> {
+        let mut tick = 0u32;
+        let mut sentences = Vec::new();
+
+        for element in elements(parent, "SENTENCE") {
+            let mut notes = Vec::new();
+            for note in elements(element, "NOTE") {
+                let line = doc.text_pos_at(note.range().start).row;
+                let number = |name: &str| {
+                    let value = attribute(note, name).unwrap_or_default();
+                    value.trim().parse::<i64>().map_err(|_| {
+                        XmlImportError::InvalidNote(line, format!("{}={:?}", name, value))
+                    })
+                };
+                let duration = u32::try_from(number("Duration")?).map_err(|_| {
+                    XmlImportError::InvalidNote(line, "negative Duration".to_string())
+                })?;
+                let key = number("MidiNote")? as i32;
+
+                if key > 0 {
+                    let golden = is_yes(note, "Bonus");
+                    let note_type = if is_yes(note, "Rap") {
+                        if golden {
+                            NoteType::GoldenRap
+                        } else {
+                            NoteType::Rap
+                        }
+                    } else if is_yes(note, "FreeStyle") {
+                        NoteType::Freestyle
+                    } else if golden {
+                        NoteType::Golden
+                    } else {
+                        NoteType::Normal
+                    };
+                    notes.push(XmlNote {
+                        tick,
+                        duration,
+                        key,
+                        note_type,
+                        lyric: attribute(note, "Lyric").unwrap_or_default().to_string(),
+                    });
+                }
+                tick = tick.saturating_add(duration);
+            }
+
+            sentences.push(Sentence {
+                part: attribute(element, "Singer").map(String::from),
+                notes,
+            });
+        }
+
+        Ok(sentences)
+    }
+
+    /// Turn sentences into UltraStar notes relative to the first note of the song
+    fn convert_track(sentences: &[Vec<XmlNote>], origin: u32) -> Track {
+        let mut notes: Vec<Note> = Vec::new();
+        let mut line_breaks = Vec::new();
+        let mut line_starts = Vec::new();
+
+        for sentence in sentences.iter().filter(|s| !s.is_empty()) {
+            if let Some(previous) = notes.last() {
+                line_breaks.push(LineBreak {
+                    start_beat: previous.start_beat + previous.length,
+                    end_beat: None,
+                });
+            }
+
+            for (i, note) in sentence.iter().enumerate() {
+                let lyric = note.lyric.trim();
+                // A trailing hyphen continues the word on the next note
+                let text = match lyric.strip_suffix('-') {
+                    _ if lyric.is_empty() || lyric == "-" => "~".to_string(),
+                    Some(syllable) => syllable.to_string(),
+                    None => format!("{} ", lyric),
+                };
+
+                line_starts.push(i == 0);
+                notes.push(Note {
+                    note_type: note.note_type,
+                    start_beat: (note.tick - origin) as i32,
+                    length: note.duration.max(1) as i32,
+                    pitch: note.key - MIDDLE_C,
+                    text,
+                });
+            }
+        }
+        normalize_word_spacing(&mut notes, &line_starts);
+
+        Track {
+            singer: None,
+            notes,
+            line_breaks,
+        }
+    }
+
+    /// Song metadata from `Key: Value` comments and `<MELODY>` attributes,
+    /// falling back to an "Artist - Title" folder name
+    fn metadata(doc: &Document, root: Node, path: &Path, bpm: f64, gap: f64) -> SongMetadata {
+        let mut fields: HashMap<String, String> = HashMap::new();
+        for comment in doc.descendants().filter(|n| n.is_comment()) {
+            for line in comment.text().unwrap_or_default().lines() {
+                if let Some((key, value)) = line.split_once(':') {
+                    let value = value.trim();
+                    if !value.is_empty() {
+                        fields
+                            .entry(key.trim().to_lowercase())
+                            .or_insert_with(|| value.to_string());
+                    }
+                }
+            }
+        }
+        for attr in root.attributes() {
+            fields
+                .entry(attr.name().to_lowercase())
+                .or_insert_with(|| attr.value().trim().to_string());
+        }
+
+        let folder = path
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().into_owned());
+        let (folder_artist, folder_title) =
+            match folder.as_deref().and_then(|f| f.split_once(" - ")) {
+                Some((artist, title)) => (
+                    Some(artist.trim().to_string()),
+                    Some(title.trim().to_string()),
+                ),
+                None => (None, folder),
+            };
+
+        SongMetadata {
+            title: fields
+                .remove("title")
+                .or(folder_title)
+                .unwrap_or_else(|| "Untitled".to_string()),
+            artist: fields
+                .remove("artist")
+                .or(folder_artist)
+                .unwrap_or_else(|| "Unknown".to_string()),
+            bpm,
+            gap,
+            genre: fields.remove("genre"),
+            year: fields.get("year").and_then(|y| y.parse().ok()),
+            language: fields.remove("language"),
+            edition: fields.remove("edition"),
+            creator: fields.remove("creator"),
+            ..Default::default()
+        }
+    }
+}
+
+/// Child elements with the given tag name, ignoring case and namespaces
+fn elements<'a, 'input: 'a>(
+    parent: Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    parent
+        .children()
+        .filter(move |n| n.is_element() && n.tag_name().name().eq_ignore_ascii_case(name))
+}
+
+/// Attribute value, matching the name case-insensitively
+fn attribute<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes()
+        .find(|a| a.name().eq_ignore_ascii_case(name))
+        .map(|a| a.value())
+}
+
+fn is_yes(node: Node, name: &str) -> bool {
+    attribute(node, name).is_some_and(|v| v.trim().eq_ignore_ascii_case("yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MELODY: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- Artist: Test Artist -->
+<!-- Title: Test Song -->
+<MELODY xmlns="http://www.singstargame.com" Version="1" Tempo="120" FixedTempo="Yes" Resolution="Semiquaver" Genre="Pop" Year="2006">
+  <SENTENCE>
+    <NOTE MidiNote="0" Duration="8" Lyric="" />
+    <NOTE MidiNote="67" Duration="4" Lyric="Hel-" />
+    <NOTE MidiNote="65" Duration="2" Lyric="lo" />
+    <NOTE MidiNote="67" Duration="4" Lyric="world" Bonus="Yes" />
+  </SENTENCE>
+  <SENTENCE>
+    <NOTE MidiNote="0" Duration="6" Lyric="" />
+    <NOTE MidiNote="60" Duration="2" Lyric="Again" FreeStyle="Yes" />
+  </SENTENCE>
+</MELODY>
+"#;
+
+    #[test]
+    fn test_import_melody() {
+        let song = XmlImporter::import(MELODY, Path::new("Songs/Folder/melody.xml")).unwrap();
+
+        assert_eq!(song.source_format, SourceFormat::SingStar);
+        assert_eq!(song.metadata.artist, "Test Artist");
+        assert_eq!(song.metadata.title, "Test Song");
+        assert_eq!(song.metadata.genre.as_deref(), Some("Pop"));
+        assert_eq!(song.metadata.year, Some(2006));
+        assert_eq!(song.metadata.bpm, 120.0);
+        // 8 sixteenths at 120 BPM
+        assert_eq!(song.metadata.gap, 1000.0);
+
+        let track = &song.tracks[0];
+        let texts: Vec<&str> = track.notes.iter().map(|n| n.text.as_str()).collect();
+        assert_eq!(texts, vec!["Hel", "lo", " world", "Again"]);
+        let starts: Vec<i32> = track.notes.iter().map(|n| n.start_beat).collect();
+        assert_eq!(starts, vec![0, 4, 6, 16]);
+        assert_eq!(track.notes[0].pitch, 7);
+        assert_eq!(track.notes[2].note_type, NoteType::Golden);
+        assert_eq!(track.notes[3].note_type, NoteType::Freestyle);
+        assert_eq!(track.line_breaks.len(), 1);
+        assert_eq!(track.line_breaks[0].start_beat, 10);
+    }
+
+    #[test]
+    fn test_duet_singer_attributes() {
+        let content = r#"<MELODY Tempo="100" Resolution="Demisemiquaver">
+  <SENTENCE Singer="Solo 1"><NOTE MidiNote="60" Duration="4" Lyric="one" /></SENTENCE>
+  <SENTENCE Singer="Solo 2"><NOTE MidiNote="62" Duration="4" Lyric="two" /></SENTENCE>
+  <SENTENCE Singer="Group"><NOTE MidiNote="64" Duration="4" Lyric="both" /></SENTENCE>
+</MELODY>"#;
+        let song = XmlImporter::import(content, Path::new("Artist - Title/notes.xml")).unwrap();
+
+        assert_eq!(song.source_format, SourceFormat::Performous);
+        assert_eq!(song.metadata.artist, "Artist");
+        assert_eq!(song.metadata.title, "Title");
+        assert_eq!(song.metadata.bpm, 200.0);
+        assert_eq!(song.tracks.len(), 2);
+        let texts = |i: usize| -> Vec<String> {
+            song.tracks[i]
+                .notes
+                .iter()
+                .map(|n| n.text.clone())
+                .collect()
+        };
+        assert_eq!(texts(0), vec!["one", "both"]);
+        assert_eq!(texts(1), vec!["two", "both"]);
+        assert_eq!(song.tracks[1].notes[0].start_beat, 4);
+    }
+
+    #[test]
+    fn test_duet_track_elements() {
+        let content = r#"<MELODY Tempo="100">
+  <TRACK Name="Player1" Artist="Singer One">
+    <SENTENCE><NOTE MidiNote="60" Duration="4" Lyric="one" /></SENTENCE>
+  </TRACK>
+  <TRACK Name="Player2" Artist="Singer Two">
+    <SENTENCE><NOTE MidiNote="0" Duration="2" Lyric="" /><NOTE MidiNote="62" Duration="4" Lyric="two" /></SENTENCE>
+  </TRACK>
+</MELODY>"#;
+        let song = XmlImporter::import(content, Path::new("notes.xml")).unwrap();
+
+        assert_eq!(song.tracks.len(), 2);
+        assert_eq!(song.tracks[0].singer.as_deref(), Some("Singer One"));
+        assert_eq!(song.tracks[1].singer.as_deref(), Some("Singer Two"));
+        assert_eq!(song.tracks[1].notes[0].start_beat, 2);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            XmlImporter::import("<song/>", Path::new("a.xml")),
+            Err(XmlImportError::NotMelody(_))
+        ));
+        assert!(matches!(
+            XmlImporter::import(r#"<MELODY Tempo="fast"/>"#, Path::new("a.xml")),
+            Err(XmlImportError::InvalidTempo(_))
+        ));
+        assert!(matches!(
+            XmlImporter::import(
+                r#"<MELODY Tempo="120"><SENTENCE/></MELODY>"#,
+                Path::new("a.xml")
+            ),
+            Err(XmlImportError::NoNotes)
+        ));
+        assert!(matches!(
+            XmlImporter::import(
+                "<MELODY Tempo=\"120\">\n<SENTENCE><NOTE MidiNote=\"x\" Duration=\"1\"/></SENTENCE></MELODY>",
+                Path::new("a.xml")
+            ),
+            Err(XmlImportError::InvalidNote(2, _))
+        ));
+    }
+}
@@ -1,47 +1,157 @@
 use crate::error::{AppError, Result};
+use crate::song::cache::{FileStamp, IndexCache};
+use crate::song::encoding;
+use crate::song::import::XmlImporter;
 use crate::song::parser::Parser;
-use crate::song::types::{Song, SongFiles};
+use crate::song::types::{Song, SongFiles, SongMetadata};
 use rayon::prelude::*;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use tracing::{info, warn};
 
+/// Counters a running scan updates, for reporting its progress
+#[derive(Debug, Default)]
+pub struct ScanProgress {
+    /// Song files found in the directory
+    pub found: AtomicUsize,
+    /// Files indexed so far, parsed or taken from the cache
+    pub indexed: AtomicUsize,
+    /// Files that could not be read or parsed
+    pub failed: AtomicUsize,
+}
+
+/// Songs found by [`Indexer::scan_directory_cached`]
+#[derive(Debug, Default)]
+pub struct CachedScan {
+    pub songs: HashMap<String, Song>,
+    /// `(old, new)` IDs of songs whose ID changed since the cache was written
+    pub renamed: Vec<(String, String)>,
+}
+
 /// Indexes songs from a directory
 pub struct Indexer;
 
 impl Indexer {
-    /// Scan a directory recursively and index all UltraStar TXT files
+    /// Scan a directory recursively and index all UltraStar TXT files, plus
+    /// MelodyXML files in folders without one
     /// Uses parallel processing to speed up indexing of large song libraries
     pub fn scan_directory(path: &Path) -> Result<HashMap<String, Song>> {
+        let progress = ScanProgress::default();
+        let (scan, _) = Self::scan_with_cache(path, &IndexCache::new(path), true, &progress)?;
+        Ok(scan.songs)
+    }
+
+    /// Like [`Self::scan_directory`], but only parse files that changed since
+    /// the cache at `cache_path` was written, then update it.
+    /// With `rebuild`, every file is parsed again.
+    pub fn scan_directory_cached(
+        path: &Path,
+        cache_path: &Path,
+        rebuild: bool,
+        progress: &ScanProgress,
+    ) -> Result<CachedScan> {
+        if rebuild {
+            info!("Rebuilding song index");
+        }
+        // Loaded even when rebuilding, to notice songs whose ID changes
+        let cache = IndexCache::load(cache_path, path);
+
+        let (scan, cache) = Self::scan_with_cache(path, &cache, !rebuild, progress)?;
+        if let Err(e) = cache.save(cache_path) {
+            warn!("Failed to write index cache {:?}: {}", cache_path, e);
+        }
+        Ok(scan)
+    }
+
+    /// Index a directory, reusing songs from `cache` whose files are
+    /// unchanged if `reuse` is set; returns the songs and a cache of the
+    /// files found
+    fn scan_with_cache(
+        path: &Path,
+        cache: &IndexCache,
+        reuse: bool,
+        progress: &ScanProgress,
+    ) -> Result<(CachedScan, IndexCache)> {
         if !path.exists() {
             warn!("Songs directory does not exist: {:?}", path);
-            return Ok(HashMap::new());
+            return Ok((CachedScan::default(), IndexCache::new(path)));
         }
 
         // Phase 1: Collect all txt file paths (fast, single-threaded)
         let txt_files = Self::collect_txt_files(path)?;
         info!("Found {} txt files to index", txt_files.len());
+        progress.found.store(txt_files.len(), Ordering::Relaxed);
 
-        // Phase 2: Parse all songs in parallel
-        let songs: HashMap<String, Song> = txt_files
+        // Phase 2: Parse new and changed songs in parallel
+        let indexed: Vec<_> = txt_files
             .par_iter()
-            .filter_map(|file_path| match Self::index_song(file_path) {
-                Ok(song) => {
-                    info!(
-                        "Indexed: {} - {}",
-                        song.metadata.artist, song.metadata.title
-                    );
-                    Some((song.id.clone(), song))
+            .filter_map(|file_path| {
+                let stamp = match FileStamp::of(file_path) {
+                    Ok(stamp) => stamp,
+                    Err(e) => {
+                        warn!("Failed to read {:?}: {}", file_path, e);
+                        progress.failed.fetch_add(1, Ordering::Relaxed);
+                        return None;
+                    }
+                };
+
+                if let Some(mut song) = cache.get(file_path, stamp).filter(|_| reuse) {
+                    match Self::resolve_files(file_path, &song) {
+                        Ok(files) => {
+                            song.files = files;
+                            progress.indexed.fetch_add(1, Ordering::Relaxed);
+                            return Some((file_path, stamp, song, true));
+                        }
+                        Err(e) => warn!("Failed to resolve files of {:?}: {}", file_path, e),
+                    }
                 }
-                Err(e) => {
-                    warn!("Failed to parse {:?}: {}", file_path, e);
-                    None
+
+                match Self::index_song(file_path) {
+                    Ok(song) => {
+                        info!(
+                            "Indexed: {} - {}",
+                            song.metadata.artist, song.metadata.title
+                        );
+                        progress.indexed.fetch_add(1, Ordering::Relaxed);
+                        Some((file_path, stamp, song, false))
+                    }
+                    Err(e) => {
+                        warn!("Failed to parse {:?}: {}", file_path, e);
+                        progress.failed.fetch_add(1, Ordering::Relaxed);
+                        None
+                    }
                 }
             })
             .collect();
 
-        info!("Indexed {} songs from {:?}", songs.len(), path);
-        Ok(songs)
+        // Sorted so copies of a song are numbered the same way on every scan
+        let mut indexed = indexed;
+        indexed.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut scan = CachedScan::default();
+        let mut new_cache = IndexCache::new(path);
+        let mut reused = 0;
+        for (file_path, stamp, mut song, cached) in indexed {
+            reused += cached as usize;
+            song.id = Self::unique_id(&song.id, |id| scan.songs.contains_key(id));
+            if let Some(old) = cache.previous_id(file_path).filter(|old| *old != song.id) {
+                scan.renamed.push((old.to_string(), song.id.clone()));
+            }
+            new_cache.insert(file_path.clone(), stamp, &song);
+            scan.songs.insert(song.id.clone(), song);
+        }
+        let removed = cache.len() - txt_files.iter().filter(|f| cache.contains(f)).count();
+
+        info!(
+            "Indexed {} songs from {:?} ({} cached, {} parsed, {} removed)",
+            scan.songs.len(),
+            path,
+            reused,
+            scan.songs.len() - reused,
+            removed
+        );
+        Ok((scan, new_cache))
     }
 
     /// Recursively collect all .txt file paths
@@ -52,20 +162,73 @@ impl Indexer {
     }
 
     fn collect_txt_files_recursive(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        let (song_files, subfolders) = Self::list_folder(path)?;
+        files.extend(song_files);
+        for folder in subfolders {
+            Self::collect_txt_files_recursive(&folder, files)?;
+        }
+        Ok(())
+    }
+
+    /// Index the songs directly inside `path`, ignoring its subfolders
+    pub fn scan_folder(path: &Path) -> Result<Vec<Song>> {
+        let (mut song_files, _) = Self::list_folder(path)?;
+        song_files.sort();
+
+        let mut songs: Vec<Song> = Vec::new();
+        for file_path in song_files {
+            match Self::index_song(&file_path) {
+                Ok(mut song) => {
+                    song.id = Self::unique_id(&song.id, |id| songs.iter().any(|s| s.id == id));
+                    songs.push(song);
+                }
+                Err(e) => warn!("Failed to parse {:?}: {}", file_path, e),
+            }
+        }
+        Ok(songs)
+    }
+
+    /// ID for a song with the given content ID that is not `taken`
+    ///
+    /// Copies of a song are numbered: the first keeps the content ID and
+    /// later ones get their number mixed in. Callers go through copies in
+    /// path order, so the numbering survives moving the whole library.
+    pub fn unique_id(content_id: &str, taken: impl Fn(&str) -> bool) -> String {
+        if !taken(content_id) {
+            return content_id.to_string();
+        }
+        (2..)
+            .map(|n: u64| Parser::hash_id(format!("{}\n{}", content_id, n).as_bytes()))
+            .find(|id| !taken(id))
+            .unwrap()
+    }
+
+    /// Song files and subfolders of a folder
+    fn list_folder(path: &Path) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
         let entries = std::fs::read_dir(path)?;
+        let mut files = Vec::new();
+        let mut subfolders = Vec::new();
+        let mut xml_files = Vec::new();
 
         for entry in entries {
             let entry = entry?;
             let file_path = entry.path();
 
             if file_path.is_dir() {
-                Self::collect_txt_files_recursive(&file_path, files)?;
+                subfolders.push(file_path);
             } else if Self::is_ultrastar_file(&file_path) {
                 files.push(file_path);
+            } else if Self::is_xml_file(&file_path) {
+                xml_files.push(file_path);
             }
         }
 
-        Ok(())
+        // A TXT next to an XML file is usually a conversion of it and wins
+        if files.is_empty() {
+            files = xml_files;
+        }
+
+        Ok((files, subfolders))
     }
 
     pub fn is_ultrastar_file(path: &Path) -> bool {
@@ -74,26 +237,28 @@ impl Indexer {
             .unwrap_or(false)
     }
 
-    /// Read file content, handling encoding issues
-    pub fn read_song_content(txt_path: &Path) -> Result<String> {
-        let content = std::fs::read_to_string(txt_path)?;
-
-        // Try to detect encoding issues and re-read if necessary
-        let content = if content.contains('\u{FFFD}') {
-            // Contains replacement characters, might be wrong encoding
-            // Try reading as Latin-1
-            let bytes = std::fs::read(txt_path)?;
-            bytes.iter().map(|&b| b as char).collect()
-        } else {
-            content
-        };
+    /// SingStar MelodyXML or Performous `notes.xml`
+    pub fn is_xml_file(path: &Path) -> bool {
+        path.extension()
+            .map(|ext| ext.eq_ignore_ascii_case("xml"))
+            .unwrap_or(false)
+    }
 
-        Ok(content)
+    /// Read file content, detecting its encoding (BOMs, #ENCODING, CP1252 fallback)
+    pub fn read_song_content(txt_path: &Path) -> Result<String> {
+        let bytes = std::fs::read(txt_path)?;
+        Ok(encoding::decode(&bytes).text)
     }
 
     pub fn index_song(txt_path: &Path) -> Result<Song> {
         let content = Self::read_song_content(txt_path)?;
-        let mut song = Parser::parse(&content, txt_path)?;
+        let mut song = if Self::is_xml_file(txt_path) {
+            let mut song = XmlImporter::import(&content, txt_path)?;
+            Self::find_xml_media(txt_path, &mut song.metadata);
+            song
+        } else {
+            Parser::parse(&content, txt_path)?
+        };
 
         // Resolve file paths
         song.files = Self::resolve_files(txt_path, &song)?;
@@ -135,17 +300,70 @@ impl Indexer {
             .map(|f| dir.join(f))
             .filter(|p| p.exists());
 
+        let vocals_path = song
+            .metadata
+            .vocals_file
+            .as_ref()
+            .map(|f| dir.join(f))
+            .filter(|p| p.exists());
+
+        let instrumental_path = song
+            .metadata
+            .instrumental_file
+            .as_ref()
+            .map(|f| dir.join(f))
+            .filter(|p| p.exists());
+
         Ok(SongFiles {
             txt_path: txt_path.to_path_buf(),
             audio_path,
             video_path,
             cover_path,
             background_path,
+            vocals_path,
+            instrumental_path,
         })
     }
 
+    /// MelodyXML has no file tags, so look for the names SingStar
+    /// extractors use (`music.ogg`, `video.mp4`, `vocals.ogg`, ...)
+    fn find_xml_media(xml_path: &Path, metadata: &mut SongMetadata) {
+        const AUDIO: &[&str] = &["ogg", "mp3", "m4a", "opus", "flac", "wav"];
+        const VIDEO: &[&str] = &["mp4", "webm", "mkv", "m4v", "mpg", "m2v", "avi"];
+        const IMAGE: &[&str] = &["jpg", "jpeg", "png"];
+
+        let Some(dir) = xml_path.parent() else {
+            return;
+        };
+        let find = |stems: &[&str], extensions: &[&str]| {
+            stems.iter().find_map(|stem| {
+                extensions
+                    .iter()
+                    .map(|ext| format!("{}.{}", stem, ext))
+                    .find(|name| dir.join(name).exists())
+            })
+        };
+
+        metadata.audio_file = metadata
+            .audio_file
+            .take()
+            .or_else(|| find(&["music", "song", "audio"], AUDIO));
+        metadata.video_file = metadata
+            .video_file
+            .take()
+            .or_else(|| find(&["video"], VIDEO));
+        metadata.vocals_file = metadata
+            .vocals_file
+            .take()
+            .or_else(|| find(&["vocals"], AUDIO));
+        metadata.background_file = metadata
+            .background_file
+            .take()
+            .or_else(|| find(&["background"], IMAGE));
+    }
+
     /// Try to find a cover image in the directory if not specified
-    fn find_cover_image(dir: &Path) -> Option<std::path::PathBuf> {
+    pub fn find_cover_image(dir: &Path) -> Option<std::path::PathBuf> {
         let patterns = ["cover.jpg", "cover.png", "[CO].jpg", "[CO].png"];
 
         for pattern in &patterns {
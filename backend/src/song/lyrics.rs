@@ -0,0 +1,222 @@
+use crate::song::timing::Timing;
+use crate::song::types::{LineBreak, Note, Song};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Song lyrics grouped into lines and words, per player track
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Lyrics {
+    pub tracks: Vec<LyricsTrack>,
+}
+
+/// The lines sung by one player
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct LyricsTrack {
+    /// 1-indexed player number
+    pub player: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub singer: Option<String>,
+    pub lines: Vec<Line>,
+}
+
+/// The notes between two line breaks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Line {
+    /// Display text of the whole line
+    pub text: String,
+    /// Start of the first note in milliseconds
+    pub start: f64,
+    /// End of the last note in milliseconds
+    pub end: f64,
+    pub notes: Vec<Note>,
+    pub words: Vec<Word>,
+}
+
+/// Consecutive syllables forming one word
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Word {
+    /// Display text with `~` continuations removed
+    pub text: String,
+    /// Start in milliseconds
+    pub start: f64,
+    /// End in milliseconds
+    pub end: f64,
+    /// Index of the word's first note in `Line::notes`
+    pub first_note: usize,
+    pub note_count: usize,
+}
+
+impl Lyrics {
+    /// Group a song's notes into lines and words for every player
+    pub fn from_song(song: &Song) -> Self {
+        let tracks = song
+            .tracks
+            .iter()
+            .enumerate()
+            .map(|(i, track)| LyricsTrack {
+                player: i + 1,
+                singer: track.singer.clone(),
+                lines: Self::lines(song, &track.notes, &track.line_breaks),
+            })
+            .collect();
+
+        Self { tracks }
+    }
+
+    fn lines(song: &Song, notes: &[Note], line_breaks: &[LineBreak]) -> Vec<Line> {
+        split_lines(notes, line_breaks)
+            .into_iter()
+            .map(|notes| Line::new(song, notes))
+            .collect()
+    }
+}
+
+impl Line {
+    /// Build a line from a non-empty run of notes
+    fn new(song: &Song, notes: &[Note]) -> Self {
+        let span = song.span_timing(notes).unwrap_or(Timing {
+            start_ms: 0.0,
+            end_ms: 0.0,
+        });
+
+        let mut words: Vec<Word> = Vec::new();
+        for (i, note) in notes.iter().enumerate() {
+            let starts_word = match i {
+                0 => true,
+                _ => {
+                    note.text.starts_with(char::is_whitespace)
+                        || notes[i - 1].text.ends_with(char::is_whitespace)
+                }
+            };
+            let text = syllable_text(&note.text);
+            let timing = song.note_timing(note);
+
+            match words.last_mut() {
+                Some(word) if !starts_word => {
+                    word.text.push_str(text);
+                    word.start = word.start.min(timing.start_ms);
+                    word.end = word.end.max(timing.end_ms);
+                    word.note_count += 1;
+                }
+                _ => words.push(Word {
+                    text: text.to_string(),
+                    start: timing.start_ms,
+                    end: timing.end_ms,
+                    first_note: i,
+                    note_count: 1,
+                }),
+            }
+        }
+
+        let text = words
+            .iter()
+            .map(|word| word.text.as_str())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        Self {
+            text,
+            start: span.start_ms,
+            end: span.end_ms,
+            notes: notes.to_vec(),
+            words,
+        }
+    }
+}
+
+/// Syllable text as displayed: surrounding whitespace and `~` holds removed
+fn syllable_text(text: &str) -> &str {
+    text.trim().trim_matches('~')
+}
+
+/// Split a track's notes at its line breaks; lines without notes are skipped
+pub fn split_lines<'a>(notes: &'a [Note], line_breaks: &[LineBreak]) -> Vec<&'a [Note]> {
+    let mut lines = Vec::new();
+    let mut line_breaks = line_breaks.iter().peekable();
+    let mut line_start = 0;
+
+    for (i, note) in notes.iter().enumerate() {
+        // Any break before this note closes the current line
+        let mut new_line = false;
+        while line_breaks
+            .next_if(|lb| lb.start_beat <= note.start_beat)
+            .is_some()
+        {
+            new_line = true;
+        }
+        if new_line && i > line_start {
+            lines.push(&notes[line_start..i]);
+            line_start = i;
+        }
+    }
+
+    if line_start < notes.len() {
+        lines.push(&notes[line_start..]);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::song::fixtures;
+    use crate::song::parser::Parser;
+    use std::path::PathBuf;
+
+    fn parse(content: &str) -> Song {
+        Parser::parse(content, &PathBuf::from("test.txt")).unwrap()
+    }
+
+    #[test]
+    fn test_lines_and_words() {
+        let lyrics = Lyrics::from_song(&parse(fixtures::SIMPLE));
+
+        assert_eq!(lyrics.tracks.len(), 1);
+        let lines = &lyrics.tracks[0].lines;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].text, "Hello world");
+        assert_eq!(lines[0].start, 1000.0);
+        assert_eq!(lines[0].end, 1550.0);
+        assert_eq!(lines[0].notes.len(), 2);
+        assert_eq!(lines[0].words.len(), 2);
+        assert_eq!(lines[0].words[1].text, "world");
+        assert_eq!(lines[0].words[1].first_note, 1);
+        assert_eq!(lines[1].text, "Test");
+    }
+
+    #[test]
+    fn test_syllables_and_continuations() {
+        let song = parse(
+            "#TITLE:T\n#ARTIST:A\n#BPM:300\n: 0 2 1 Some\n: 2 2 1 thing\n: 4 2 3 ~\n: 6 2 1  new~\n: 8 2 1 er\n: 10 2 1  end\nE\n",
+        );
+        let line = &Lyrics::from_song(&song).tracks[0].lines[0];
+
+        let words: Vec<_> = line.words.iter().map(|w| w.text.as_str()).collect();
+        assert_eq!(words, ["Something", "newer", "end"]);
+        assert_eq!(line.words[0].note_count, 3);
+        assert_eq!(line.words[0].end, song.beat_to_ms(6.0));
+        assert_eq!(line.text, "Something newer end");
+    }
+
+    #[test]
+    fn test_duet_tracks() {
+        let lyrics = Lyrics::from_song(&parse(fixtures::DUET));
+
+        assert_eq!(lyrics.tracks.len(), 2);
+        assert_eq!(lyrics.tracks[1].player, 2);
+        assert_eq!(lyrics.tracks[1].singer.as_deref(), Some("Singer Two"));
+        assert_eq!(lyrics.tracks[1].lines[1].text, "too");
+    }
+
+    #[test]
+    fn test_split_lines_skips_empty_lines() {
+        let song = parse(
+            "#TITLE:T\n#ARTIST:A\n#BPM:300\n- 0\n: 1 2 1 One\n- 4\n- 5\n: 6 2 1 Two\n- 10\nE\n",
+        );
+        let lines = split_lines(&song.tracks[0].notes, &song.tracks[0].line_breaks);
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1][0].text, "Two");
+    }
+}
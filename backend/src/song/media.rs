@@ -0,0 +1,181 @@
+use std::fs::File;
+use std::path::Path;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use thiserror::Error;
+
+/// Containers we can read; other files are skipped rather than reported
+const PROBED_EXTENSIONS: &[&str] = &[
+    "mp3", "wav", "flac", "ogg", "m4a", "mp4", "mov", "mkv", "webm",
+];
+
+/// Packets decoded to make sure the audio stream is readable
+const DECODED_PACKETS: usize = 8;
+
+#[derive(Debug, Error)]
+pub enum MediaError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    /// The container or codec cannot be checked here; the file may still play
+    #[error("Unsupported format: {0}")]
+    Unsupported(String),
+
+    #[error("{0}")]
+    Corrupt(String),
+}
+
+/// What probing a media file found
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaInfo {
+    /// Length of the audio track; `None` for files without audio
+    pub duration_ms: Option<f64>,
+}
+
+/// Reads audio and video containers to find broken files and their length
+pub struct MediaProbe;
+
+impl MediaProbe {
+    /// Open a file, decode the start of its first audio track and work out
+    /// how long it is
+    pub fn probe(path: &Path) -> Result<MediaInfo, MediaError> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_default();
+        if !PROBED_EXTENSIONS.contains(&extension.as_str()) {
+            return Err(MediaError::Unsupported(extension));
+        }
+
+        let source = MediaSourceStream::new(Box::new(File::open(path)?), Default::default());
+        let mut hint = Hint::new();
+        hint.with_extension(&extension);
+        // The extension is one we can read, so failing here means a broken file
+        let probed = symphonia::default::get_probe()
+            .format(
+                &hint,
+                source,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(|e| MediaError::Corrupt(e.to_string()))?;
+        let mut format = probed.format;
+
+        let Some(track) = format
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        else {
+            return Ok(MediaInfo { duration_ms: None });
+        };
+        let track_id = track.id;
+        let params = track.codec_params.clone();
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&params, &DecoderOptions::default())
+            .map_err(classify)?;
+
+        // Without a frame count in the header, count the packets instead
+        let mut frames = 0;
+        let mut decoded = 0;
+        loop {
+            let packet = match format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(e))
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    break
+                }
+                Err(e) => return Err(classify(e)),
+            };
+            if packet.track_id() != track_id {
+                continue;
+            }
+            frames += packet.dur();
+            if decoded < DECODED_PACKETS {
+                decoder.decode(&packet).map_err(classify)?;
+                decoded += 1;
+            } else if params.n_frames.is_some() {
+                break;
+            }
+        }
+        if decoded == 0 {
+            return Err(MediaError::Corrupt("No audio data".to_string()));
+        }
+
+        let frames = params.n_frames.unwrap_or(frames);
+        let duration_ms = match (params.time_base, params.sample_rate) {
+            (Some(time_base), _) => {
+                let time = time_base.calc_time(frames);
+                Some((time.seconds as f64 + time.frac) * 1000.0)
+            }
+            (None, Some(rate)) => Some(frames as f64 * 1000.0 / rate as f64),
+            (None, None) => None,
+        };
+        Ok(MediaInfo { duration_ms })
+    }
+}
+
+fn classify(error: SymphoniaError) -> MediaError {
+    match error {
+        SymphoniaError::Unsupported(feature) => MediaError::Unsupported(feature.to_string()),
+        SymphoniaError::IoError(e) if e.kind() != std::io::ErrorKind::UnexpectedEof => {
+            MediaError::Io(e)
+        }
+        e => MediaError::Corrupt(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A silent 16-bit mono WAV file
+    pub(crate) fn wav(duration_ms: u32) -> Vec<u8> {
+        let rate: u32 = 8000;
+        let data_len = rate * duration_ms / 1000 * 2;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+        bytes.extend_from_slice(&rate.to_le_bytes());
+        bytes.extend_from_slice(&(rate * 2).to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        bytes.resize(bytes.len() + data_len as usize, 0);
+        bytes
+    }
+
+    #[test]
+    fn test_probe() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("song.wav");
+        std::fs::write(&path, wav(1500)).unwrap();
+        let info = MediaProbe::probe(&path).unwrap();
+        assert_eq!(info.duration_ms.map(f64::round), Some(1500.0));
+
+        let path = dir.path().join("song.mp3");
+        std::fs::write(&path, b"not really an mp3").unwrap();
+        assert!(matches!(
+            MediaProbe::probe(&path),
+            Err(MediaError::Corrupt(_))
+        ));
+
+        let path = dir.path().join("song.opus");
+        std::fs::write(&path, b"").unwrap();
+        assert!(matches!(
+            MediaProbe::probe(&path),
+            Err(MediaError::Unsupported(_))
+        ));
+    }
+}
@@ -1,8 +1,39 @@
+#[cfg(test)]
+mod fixtures;
+pub mod aliases;
+pub mod cache;
+pub mod duplicates;
+pub mod encoding;
+pub mod export;
+pub mod fixer;
+pub mod import;
 pub mod indexer;
+pub mod lyrics;
+pub mod media;
 pub mod parser;
+pub mod report;
+pub mod rules;
+pub mod timing;
+pub mod tokenizer;
+pub mod transform;
 pub mod types;
 pub mod validator;
+pub mod writer;
 
-pub use indexer::Indexer;
+pub use aliases::AliasTable;
+pub use cache::{FileStamp, IndexCache};
+pub use duplicates::{DuplicateCandidate, DuplicateFinder, DuplicateGroup, DuplicateReason};
+pub use fixer::{Fix, FixResult, Fixer};
+pub use indexer::{CachedScan, Indexer, ScanProgress};
+pub use lyrics::{Line, Lyrics, LyricsTrack, Word};
+pub use media::{MediaError, MediaInfo, MediaProbe};
+pub use parser::{ParseError, ParseErrorKind, ParseMode, ParseOutput, Parser, TrackLines};
+pub use report::{ReportFormat, Reporter};
+pub use rules::{RuleConfig, RuleConfigError, Severity};
+pub use timing::{SongTimings, Timing, TrackTimings};
+pub use transform::{Transform, TransformError};
 pub use types::*;
-pub use validator::{ValidationError, ValidationErrorKind, ValidationResult, Validator};
+pub use validator::{
+    ValidationError, ValidationErrorKind, ValidationOptions, ValidationResult, Validator,
+};
+pub use writer::Writer;
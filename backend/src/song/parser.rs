@@ -1,228 +1,535 @@
-use crate::error::{AppError, Result};
-use crate::song::types::{LineBreak, Note, NoteType, Song, SongFiles, SongMetadata};
+use crate::song::tokenizer::{SourceLine, Token, Tokenizer};
+use crate::song::types::{FormatVersion, Song, SongFiles, SongMetadata, SourceFormat, Track};
 use std::path::Path;
 
+/// Maximum length of the offending line included in a parse error
+const SNIPPET_MAX_CHARS: usize = 80;
+
+/// A parse failure with the location of the offending input
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// 1-indexed line number, `None` for errors about the file as a whole
+    pub line: Option<usize>,
+    /// 1-indexed character column within the line
+    pub column: Option<usize>,
+    /// The offending line (trimmed and shortened)
+    pub snippet: Option<String>,
+}
+
+impl ParseError {
+    pub(crate) fn new(kind: ParseErrorKind) -> Self {
+        Self {
+            kind,
+            line: None,
+            column: None,
+            snippet: None,
+        }
+    }
+
+    /// Point the error at the start of `part`, a subslice of `line`
+    pub(crate) fn at(kind: ParseErrorKind, line: &str, part: &str) -> Self {
+        let offset = (part.as_ptr() as usize).saturating_sub(line.as_ptr() as usize);
+        let offset = offset.min(line.len());
+        Self {
+            column: Some(line[..offset].chars().count() + 1),
+            ..Self::new(kind)
+        }
+    }
+
+    /// Attach the line number and snippet once the error leaves line parsing;
+    /// `indent` is the number of characters trimmed from the start of the line
+    pub(crate) fn on_line(mut self, line_num: usize, indent: usize, line: &str) -> Self {
+        self.line = Some(line_num);
+        self.column = Some(self.column.unwrap_or(1) + indent);
+        self.snippet = Some(line.chars().take(SNIPPET_MAX_CHARS).collect());
+        self
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                write!(f, "Line {}, column {}: {}", line, column, self.kind)?
+            }
+            (Some(line), None) => write!(f, "Line {}: {}", line, self.kind)?,
+            _ => write!(f, "{}", self.kind)?,
+        }
+        if let Some(snippet) = &self.snippet {
+            write!(f, " (`{}`)", snippet)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    MissingTag(&'static str),
+    InvalidHeaderValue { tag: String, value: String },
+    UnsupportedVersion(String),
+    RelativeNotSupported(String),
+    UnknownNoteType(char),
+    MissingNoteFields,
+    InvalidStartBeat(String),
+    InvalidLength(String),
+    InvalidPitch(String),
+    MissingLineBreakBeat,
+    InvalidLineBreakBeat(String),
+    MissingBeatShift,
+    BeatOutOfRange(String),
+
+    // Recoverable problems, reported as warnings in lenient mode
+    EmptyTag(&'static str),
+    IgnoredHeaderValue { tag: String, value: String },
+    UnknownLine,
+    MissingEndMarker,
+}
+
+impl ParseErrorKind {
+    /// Stable machine-readable identifier, used in API error bodies
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MissingTag(_) => "missing_tag",
+            Self::InvalidHeaderValue { .. } => "invalid_header_value",
+            Self::UnsupportedVersion(_) => "unsupported_version",
+            Self::RelativeNotSupported(_) => "relative_not_supported",
+            Self::UnknownNoteType(_) => "unknown_note_type",
+            Self::MissingNoteFields => "missing_note_fields",
+            Self::InvalidStartBeat(_) => "invalid_start_beat",
+            Self::InvalidLength(_) => "invalid_length",
+            Self::InvalidPitch(_) => "invalid_pitch",
+            Self::MissingLineBreakBeat => "missing_line_break_beat",
+            Self::InvalidLineBreakBeat(_) => "invalid_line_break_beat",
+            Self::MissingBeatShift => "missing_beat_shift",
+            Self::BeatOutOfRange(_) => "beat_out_of_range",
+            Self::EmptyTag(_) => "empty_tag",
+            Self::IgnoredHeaderValue { .. } => "ignored_header_value",
+            Self::UnknownLine => "unknown_line",
+            Self::MissingEndMarker => "missing_end_marker",
+        }
+    }
+}
+
+impl std::fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingTag(tag) => write!(f, "Missing required {} tag", tag),
+            Self::InvalidHeaderValue { tag, value } => {
+                write!(f, "Invalid {} value: {}", tag, value)
+            }
+            Self::UnsupportedVersion(v) => write!(f, "Unsupported format version: {}", v),
+            Self::RelativeNotSupported(v) => {
+                write!(f, "#RELATIVE is not supported in format version {}", v)
+            }
+            Self::UnknownNoteType(c) => write!(f, "Unknown note type: {}", c),
+            Self::MissingNoteFields => {
+                write!(f, "Invalid note line (expected start, length and pitch)")
+            }
+            Self::InvalidStartBeat(v) => write!(f, "Invalid start beat: {}", v),
+            Self::InvalidLength(v) => write!(f, "Invalid length: {}", v),
+            Self::InvalidPitch(v) => write!(f, "Invalid pitch: {}", v),
+            Self::MissingLineBreakBeat => write!(f, "Line break needs a start beat"),
+            Self::InvalidLineBreakBeat(v) => write!(f, "Invalid line break beat: {}", v),
+            Self::MissingBeatShift => write!(f, "Relative line break needs a beat shift"),
+            Self::BeatOutOfRange(v) => {
+                write!(f, "Beat out of range after relative shifts: {}", v)
+            }
+            Self::EmptyTag(tag) => write!(f, "{} tag is empty", tag),
+            Self::IgnoredHeaderValue { tag, value } => {
+                write!(f, "Ignoring invalid {} value: {}", tag, value)
+            }
+            Self::UnknownLine => write!(f, "Unknown line type"),
+            Self::MissingEndMarker => write!(f, "Missing 'E' end marker"),
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, ParseError>;
+
+/// How the parser reacts to malformed input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Fail on the first error; used when loading songs into the library
+    Strict,
+    /// Skip malformed lines and collect every problem; used by the validator
+    Lenient,
+}
+
+/// Result of a lenient parse: a best-effort song plus everything that went wrong
+///
+/// `errors` is empty exactly when a strict parse of the same input succeeds.
+#[derive(Debug, Clone)]
+pub struct ParseOutput {
+    pub song: Song,
+    pub errors: Vec<ParseError>,
+    pub warnings: Vec<ParseError>,
+    /// Where each track's notes and line breaks were read, parallel to `song.tracks`
+    pub source_lines: Vec<TrackLines>,
+}
+
+/// 1-indexed source line numbers of a track's notes and line breaks
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackLines {
+    pub notes: Vec<usize>,
+    pub line_breaks: Vec<usize>,
+}
+
+/// Collects diagnostics, or bails out on the first error in strict mode
+struct Diagnostics {
+    mode: ParseMode,
+    errors: Vec<ParseError>,
+    warnings: Vec<ParseError>,
+}
+
+impl Diagnostics {
+    fn error(&mut self, error: ParseError) -> Result<()> {
+        match self.mode {
+            ParseMode::Strict => Err(error),
+            ParseMode::Lenient => {
+                self.errors.push(error);
+                Ok(())
+            }
+        }
+    }
+
+    fn warning(&mut self, warning: ParseError) {
+        self.warnings.push(warning);
+    }
+}
+
 /// Parser for UltraStar TXT files
 pub struct Parser;
 
 impl Parser {
-    /// Parse an UltraStar TXT file from a string
+    /// Parse an UltraStar TXT file from a string, failing on the first error
     pub fn parse(content: &str, txt_path: &Path) -> Result<Song> {
+        Self::parse_with_mode(content, txt_path, ParseMode::Strict).map(|output| output.song)
+    }
+
+    /// Parse an UltraStar TXT file, collecting all errors and warnings
+    pub fn parse_lenient(content: &str, txt_path: &Path) -> ParseOutput {
+        Self::parse_with_mode(content, txt_path, ParseMode::Lenient)
+            .expect("lenient parsing never fails")
+    }
+
+    /// Parse an UltraStar TXT file in the given mode
+    ///
+    /// In strict mode the first error is returned; in lenient mode this
+    /// always succeeds and placeholder values stand in for missing tags.
+    pub fn parse_with_mode(content: &str, txt_path: &Path, mode: ParseMode) -> Result<ParseOutput> {
+        let mut diagnostics = Diagnostics {
+            mode,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+        };
         let mut metadata = MetadataBuilder::default();
-        let mut notes_p1: Vec<Note> = Vec::new();
-        let mut notes_p2: Vec<Note> = Vec::new();
-        let mut line_breaks_p1: Vec<LineBreak> = Vec::new();
-        let mut line_breaks_p2: Vec<LineBreak> = Vec::new();
-        let mut current_player = 1;
-        let mut is_duet = false;
-
-        for line in content.lines() {
-            let line = line.trim();
-            if line.is_empty() {
-                continue;
-            }
+        let mut tracks: Vec<Track> = vec![Track::default()];
+        let mut source_lines = vec![TrackLines::default()];
+        // Indices of the tracks the following notes belong to
+        let mut current_tracks = vec![0];
+        let mut has_end_marker = false;
+        // Beat origin per track, only advanced for #RELATIVE:yes songs
+        let mut beat_origin = vec![0i32];
 
-            if line.starts_with('#') {
-                Self::parse_header_line(line, &mut metadata)?;
-                if line.starts_with("#P1")
-                    || line.starts_with("#P2")
-                    || line.starts_with("#DUETSINGERP1")
-                    || line.starts_with("#DUETSINGERP2")
-                {
-                    is_duet = true;
+        for (line, token) in Tokenizer::new(content) {
+            let token = match token {
+                Ok(token) => token,
+                Err(e) => {
+                    diagnostics.error(e)?;
+                    continue;
                 }
-            } else if line.starts_with('P') {
-                // Player switch (P1 or P2)
-                is_duet = true;
-                if line.starts_with("P1") || line == "P 1" {
-                    current_player = 1;
-                } else if line.starts_with("P2") || line == "P 2" {
-                    current_player = 2;
+            };
+
+            match token {
+                Token::Header { tag, name, value } => {
+                    Self::parse_header(&line, tag, name, value, &mut metadata, &mut diagnostics)?;
                 }
-            } else if line.starts_with(':')
-                || line.starts_with('*')
-                || line.starts_with('F')
-                || line.starts_with('R')
-                || line.starts_with('G')
-            {
-                let note = Self::parse_note_line(line)?;
-                if current_player == 2 {
-                    notes_p2.push(note);
-                } else {
-                    notes_p1.push(note);
+                Token::Player(player) => {
+                    let Some(player_tracks) = Self::player_tracks(player, &metadata) else {
+                        continue;
+                    };
+                    current_tracks = player_tracks;
+                    let needed = current_tracks.iter().max().map_or(0, |i| i + 1);
+                    if tracks.len() < needed {
+                        tracks.resize_with(needed, Track::default);
+                        source_lines.resize_with(needed, TrackLines::default);
+                        beat_origin.resize(needed, 0);
+                    }
                 }
-            } else if line.starts_with('-') {
-                let line_break = Self::parse_line_break(line)?;
-                if current_player == 2 {
-                    line_breaks_p2.push(line_break);
-                } else {
-                    line_breaks_p1.push(line_break);
+                Token::Note(note) => {
+                    for &track in &current_tracks {
+                        let mut note = note.clone();
+                        let Some(start_beat) = note.start_beat.checked_add(beat_origin[track])
+                        else {
+                            diagnostics.error(Self::beat_out_of_range(&line, 0))?;
+                            break;
+                        };
+                        note.start_beat = start_beat;
+                        tracks[track].notes.push(note);
+                        source_lines[track].notes.push(line.number);
+                    }
+                }
+                Token::LineBreak(line_break) => {
+                    let shift = if metadata.relative {
+                        // Relative format: "- <break> <shift>", both relative to the
+                        // current origin; the shift moves the origin for the next line
+                        let Some(shift) = line_break.end_beat else {
+                            diagnostics.error(
+                                line.locate(ParseError::new(ParseErrorKind::MissingBeatShift)),
+                            )?;
+                            continue;
+                        };
+                        Some(shift)
+                    } else {
+                        None
+                    };
+                    for &track in &current_tracks {
+                        let mut line_break = line_break.clone();
+                        if let Some(shift) = shift {
+                            let start_beat = line_break.start_beat.checked_add(beat_origin[track]);
+                            let origin = beat_origin[track].checked_add(shift);
+                            let (Some(start_beat), Some(origin)) = (start_beat, origin) else {
+                                let field = if start_beat.is_none() { 0 } else { 1 };
+                                diagnostics.error(Self::beat_out_of_range(&line, field))?;
+                                break;
+                            };
+                            line_break.start_beat = start_beat;
+                            line_break.end_beat = None;
+                            beat_origin[track] = origin;
+                        }
+                        tracks[track].line_breaks.push(line_break);
+                        source_lines[track].line_breaks.push(line.number);
+                    }
+                }
+                Token::End => has_end_marker = true,
+                Token::Unknown => {
+                    diagnostics.warning(line.locate(ParseError::new(ParseErrorKind::UnknownLine)))
                 }
-            } else if line == "E" {
-                // End of file marker
-                break;
             }
         }
 
-        let song_metadata = metadata.build()?;
+        if !has_end_marker {
+            diagnostics.warning(ParseError::new(ParseErrorKind::MissingEndMarker));
+        }
 
-        // Generate ID from path
-        let id = Self::generate_id(txt_path);
+        let song_metadata = metadata.build(&mut diagnostics)?;
+        for (i, track) in tracks.iter_mut().enumerate() {
+            track.singer = song_metadata.singer(i + 1).map(str::to_string);
+        }
 
-        Ok(Song {
-            id,
+        let mut song = Song {
+            id: String::new(),
             metadata: song_metadata,
-            notes: notes_p1,
-            notes_p2: if is_duet && !notes_p2.is_empty() {
-                Some(notes_p2)
-            } else {
-                None
-            },
-            line_breaks: line_breaks_p1,
-            line_breaks_p2: if is_duet && !line_breaks_p2.is_empty() {
-                Some(line_breaks_p2)
-            } else {
-                None
-            },
+            tracks,
+            source_format: SourceFormat::UltraStar,
             files: SongFiles {
                 txt_path: txt_path.to_path_buf(),
                 ..Default::default()
             },
+        };
+        song.id = song.content_id();
+
+        Ok(ParseOutput {
+            song,
+            errors: diagnostics.errors,
+            warnings: diagnostics.warnings,
+            source_lines,
         })
     }
 
-    fn parse_header_line(line: &str, metadata: &mut MetadataBuilder) -> Result<()> {
-        // Format: #TAG:value
-        let line = &line[1..]; // Remove leading #
-        let (tag, value) = line.split_once(':').unwrap_or((line, ""));
-        let tag = tag.trim().to_uppercase();
-        let value = value.trim();
+    fn parse_header(
+        line: &SourceLine,
+        tag: String,
+        name: &str,
+        value: &str,
+        metadata: &mut MetadataBuilder,
+        diagnostics: &mut Diagnostics,
+    ) -> Result<()> {
+        let text = line.text;
+        let invalid = |tag: &str| {
+            line.locate(ParseError::at(
+                ParseErrorKind::InvalidHeaderValue {
+                    tag: tag.to_string(),
+                    value: value.to_string(),
+                },
+                text,
+                value,
+            ))
+        };
+        let ignored = |tag: &str| {
+            line.locate(ParseError::at(
+                ParseErrorKind::IgnoredHeaderValue {
+                    tag: tag.to_string(),
+                    value: value.to_string(),
+                },
+                text,
+                value,
+            ))
+        };
+
+        // #RELATIVE only affects how beats are read, the song itself is absolute
+        if tag == "RELATIVE" {
+            metadata.relative = value.eq_ignore_ascii_case("yes");
+            return Ok(());
+        }
+        // #ENCODING was already applied when the file was decoded
+        if tag == "ENCODING" {
+            return Ok(());
+        }
+        let repeated = metadata.tag_order.contains(&tag);
+        if !repeated {
+            metadata.tag_order.push(tag.clone());
+        }
 
         match tag.as_str() {
-            "TITLE" => metadata.title = Some(value.to_string()),
-            "ARTIST" => metadata.artist = Some(value.to_string()),
-            "MP3" | "AUDIO" => metadata.audio_file = Some(value.to_string()),
-            "BPM" => {
-                // BPM might use comma as decimal separator
-                let bpm_str = value.replace(',', ".");
-                metadata.bpm =
-                    Some(bpm_str.parse().map_err(|_| {
-                        AppError::ParseError(format!("Invalid BPM value: {}", value))
-                    })?);
+            "VERSION" => match FormatVersion::parse(value) {
+                Some(version) if version.major > 1 => {
+                    diagnostics.error(line.locate(ParseError::at(
+                        ParseErrorKind::UnsupportedVersion(value.to_string()),
+                        text,
+                        value,
+                    )))?
+                }
+                Some(version) => metadata.version = Some(version),
+                None => diagnostics.error(invalid("VERSION"))?,
+            },
+            "TITLE" | "ARTIST" => {
+                let (field, tag) = if tag == "TITLE" {
+                    (&mut metadata.title, "TITLE")
+                } else {
+                    (&mut metadata.artist, "ARTIST")
+                };
+                if value.is_empty() {
+                    diagnostics.warning(line.locate(ParseError::at(
+                        ParseErrorKind::EmptyTag(tag),
+                        text,
+                        value,
+                    )));
+                }
+                *field = Some(value.to_string());
             }
-            "GAP" => {
-                let gap_str = value.replace(',', ".");
-                metadata.gap =
-                    Some(gap_str.parse().map_err(|_| {
-                        AppError::ParseError(format!("Invalid GAP value: {}", value))
-                    })?);
+            "MP3" => metadata.mp3_file = Some(value.to_string()),
+            "AUDIO" => metadata.audio_file = Some(value.to_string()),
+            "VOCALS" => metadata.vocals_file = Some(value.to_string()),
+            "INSTRUMENTAL" => metadata.instrumental_file = Some(value.to_string()),
+            // Decimal tags might use comma as decimal separator
+            "BPM" | "GAP" | "START" | "END" | "PREVIEWSTART" => {
+                let Some(number) = Self::parse_decimal(value) else {
+                    diagnostics.error(invalid(&tag))?;
+                    return Ok(());
+                };
+                match tag.as_str() {
+                    // Beat timings divide by the BPM
+                    "BPM" if !(number > 0.0 && number.is_finite()) => {
+                        diagnostics.error(invalid("BPM"))?
+                    }
+                    "BPM" => metadata.bpm = Some(number),
+                    "GAP" => metadata.gap = Some(number),
+                    "START" => metadata.start = Some(number),
+                    "END" => metadata.end = Some(number),
+                    _ => metadata.preview_start = Some(number),
+                }
             }
             "VIDEO" => metadata.video_file = Some(value.to_string()),
             "VIDEOGAP" => {
-                let vgap_str = value.replace(',', ".");
-                metadata.video_gap = vgap_str.parse().ok();
+                metadata.video_gap = Self::parse_decimal(value);
+                if metadata.video_gap.is_none() {
+                    diagnostics.warning(ignored("VIDEOGAP"));
+                }
             }
             "COVER" => metadata.cover_file = Some(value.to_string()),
             "BACKGROUND" => metadata.background_file = Some(value.to_string()),
             "GENRE" => metadata.genre = Some(value.to_string()),
-            "YEAR" => metadata.year = value.parse().ok(),
+            "YEAR" => {
+                metadata.year = value.parse().ok();
+                if metadata.year.is_none() && !value.is_empty() {
+                    diagnostics.warning(ignored("YEAR"));
+                }
+            }
             "LANGUAGE" => metadata.language = Some(value.to_string()),
             "EDITION" => metadata.edition = Some(value.to_string()),
             "CREATOR" => metadata.creator = Some(value.to_string()),
             "DUETSINGERP1" | "P1" => metadata.duet_singer_p1 = Some(value.to_string()),
             "DUETSINGERP2" | "P2" => metadata.duet_singer_p2 = Some(value.to_string()),
-            _ => {} // Ignore unknown tags
+            "MEDLEYSTARTBEAT" | "MEDLEYENDBEAT" => {
+                let beat = value.parse().ok();
+                if beat.is_none() {
+                    diagnostics.warning(ignored(&tag));
+                }
+                if tag == "MEDLEYSTARTBEAT" {
+                    metadata.medley_start_beat = beat;
+                } else {
+                    metadata.medley_end_beat = beat;
+                }
+            }
+            _ => {
+                // Unknown tags are kept as-is, including repeats, which are
+                // listed again so the writer can put them back in place
+                if repeated {
+                    metadata.tag_order.push(tag.clone());
+                }
+                metadata
+                    .extra_tags
+                    .push((name.to_string(), value.to_string()));
+            }
         }
 
         Ok(())
     }
 
-    fn parse_note_line(line: &str) -> Result<Note> {
-        // Format: NoteType StartBeat Length Pitch Text
-        // Example: : 0 5 7 Some~ ly~
-        let note_type = match line.chars().next() {
-            Some(':') => NoteType::Normal,
-            Some('*') => NoteType::Golden,
-            Some('F') => NoteType::Freestyle,
-            Some('R') => NoteType::Rap,
-            Some('G') => NoteType::GoldenRap,
-            _ => {
-                return Err(AppError::ParseError(format!(
-                    "Unknown note type in: {}",
-                    line
-                )))
-            }
-        };
-
-        let rest = line[1..].trim();
-        let parts: Vec<&str> = rest.splitn(4, ' ').collect();
-
-        if parts.len() < 4 {
-            return Err(AppError::ParseError(format!(
-                "Invalid note line (expected 4+ parts): {}",
-                line
-            )));
-        }
-
-        let start_beat: i32 = parts[0]
-            .parse()
-            .map_err(|_| AppError::ParseError(format!("Invalid start beat: {}", parts[0])))?;
-        let length: i32 = parts[1]
-            .parse()
-            .map_err(|_| AppError::ParseError(format!("Invalid length: {}", parts[1])))?;
-        let pitch: i32 = parts[2]
-            .parse()
-            .map_err(|_| AppError::ParseError(format!("Invalid pitch: {}", parts[2])))?;
-        let text = parts[3].to_string();
-
-        Ok(Note {
-            note_type,
-            start_beat,
-            length,
-            pitch,
-            text,
-        })
+    /// Parse a decimal header value, accepting a comma as decimal separator
+    fn parse_decimal(value: &str) -> Option<f64> {
+        value.replace(',', ".").parse().ok()
     }
 
-    fn parse_line_break(line: &str) -> Result<LineBreak> {
-        // Format: - StartBeat [EndBeat]
-        let rest = line[1..].trim();
-        let parts: Vec<&str> = rest.split_whitespace().collect();
+    /// Track indices a `P<n>` marker selects
+    ///
+    /// `P3` means "both singers" in duets unless the header declares a
+    /// third singer, in which case it is a track of its own.
+    /// Point at the `field`th number of a relative note or line break whose
+    /// absolute beat does not fit
+    fn beat_out_of_range(line: &SourceLine, field: usize) -> ParseError {
+        let mut rest = line.text.chars();
+        rest.next();
+        let part = rest
+            .as_str()
+            .split_whitespace()
+            .nth(field)
+            .unwrap_or(line.text);
+        line.locate(ParseError::at(
+            ParseErrorKind::BeatOutOfRange(part.to_string()),
+            line.text,
+            part,
+        ))
+    }
 
-        if parts.is_empty() {
-            return Err(AppError::ParseError(format!(
-                "Invalid line break: {}",
-                line
-            )));
+    fn player_tracks(player: usize, metadata: &MetadataBuilder) -> Option<Vec<usize>> {
+        match player {
+            0 => None,
+            3 if !metadata.declares_singer(3) => Some(vec![0, 1]),
+            n => Some(vec![n - 1]),
         }
+    }
 
-        let start_beat: i32 = parts[0]
-            .parse()
-            .map_err(|_| AppError::ParseError(format!("Invalid line break beat: {}", parts[0])))?;
-
-        let end_beat: Option<i32> = if parts.len() > 1 {
-            parts[1].parse().ok()
-        } else {
-            None
-        };
-
-        Ok(LineBreak {
-            start_beat,
-            end_beat,
-        })
+    /// ID derived from the file path, which songs had before IDs were based
+    /// on their content; kept so those IDs still resolve
+    pub fn path_id(path: &Path) -> String {
+        Self::hash_id(path.to_string_lossy().as_bytes())
     }
 
-    fn generate_id(path: &Path) -> String {
+    pub(crate) fn hash_id(data: &[u8]) -> String {
         // Use FNV-1a hash for a deterministic ID that's stable across restarts
         // FNV-1a is a simple, fast, non-cryptographic hash with good distribution
         const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
         const FNV_PRIME: u64 = 0x100000001b3;
 
-        let path_str = path.to_string_lossy();
         let mut hash = FNV_OFFSET_BASIS;
 
-        for byte in path_str.bytes() {
-            hash ^= byte as u64;
+        for byte in data {
+            hash ^= *byte as u64;
             hash = hash.wrapping_mul(FNV_PRIME);
         }
 
@@ -232,6 +539,7 @@ impl Parser {
 
 #[derive(Default)]
 struct MetadataBuilder {
+    version: Option<FormatVersion>,
     title: Option<String>,
     artist: Option<String>,
     bpm: Option<f64>,
@@ -244,24 +552,74 @@ struct MetadataBuilder {
     creator: Option<String>,
     duet_singer_p1: Option<String>,
     duet_singer_p2: Option<String>,
+    mp3_file: Option<String>,
     audio_file: Option<String>,
+    vocals_file: Option<String>,
+    instrumental_file: Option<String>,
     video_file: Option<String>,
     cover_file: Option<String>,
     background_file: Option<String>,
+    start: Option<f64>,
+    end: Option<f64>,
+    preview_start: Option<f64>,
+    medley_start_beat: Option<i32>,
+    medley_end_beat: Option<i32>,
+    relative: bool,
+    tag_order: Vec<String>,
+    extra_tags: Vec<(String, String)>,
 }
 
 impl MetadataBuilder {
-    fn build(self) -> Result<SongMetadata> {
+    /// Whether a singer name header was seen for a player beyond the second
+    fn declares_singer(&self, player: usize) -> bool {
+        [format!("P{}", player), format!("DUETSINGERP{}", player)]
+            .iter()
+            .any(|tag| {
+                self.extra_tags
+                    .iter()
+                    .any(|(name, _)| name.eq_ignore_ascii_case(tag))
+            })
+    }
+
+    fn build(self, diagnostics: &mut Diagnostics) -> Result<SongMetadata> {
+        // Relative timing was dropped from the format in 1.0.0
+        if let Some(version) = self.version.filter(|_| self.relative) {
+            if version >= FormatVersion::V1_0_0 {
+                diagnostics.error(ParseError::new(ParseErrorKind::RelativeNotSupported(
+                    version.to_string(),
+                )))?;
+            }
+        }
+
+        // Missing required tags are errors; lenient mode carries on with placeholders
+        let mut required = |value: Option<String>, tag: &'static str| match value {
+            Some(value) => Ok(value),
+            None => diagnostics
+                .error(ParseError::new(ParseErrorKind::MissingTag(tag)))
+                .map(|_| String::new()),
+        };
+        let title = required(self.title, "TITLE")?;
+        let artist = required(self.artist, "ARTIST")?;
+        let bpm = match self.bpm {
+            Some(bpm) => bpm,
+            None => {
+                diagnostics.error(ParseError::new(ParseErrorKind::MissingTag("BPM")))?;
+                0.0
+            }
+        };
+
+        // Since 1.1.0 #AUDIO is the canonical tag and #MP3 only a fallback for
+        // older players. Legacy files only know #MP3, so it wins there.
+        let audio_file = match self.version {
+            Some(version) if version >= FormatVersion::V1_1_0 => self.audio_file.or(self.mp3_file),
+            _ => self.mp3_file.or(self.audio_file),
+        };
+
         Ok(SongMetadata {
-            title: self
-                .title
-                .ok_or_else(|| AppError::ParseError("Missing required TITLE tag".to_string()))?,
-            artist: self
-                .artist
-                .ok_or_else(|| AppError::ParseError("Missing required ARTIST tag".to_string()))?,
-            bpm: self
-                .bpm
-                .ok_or_else(|| AppError::ParseError("Missing required BPM tag".to_string()))?,
+            version: self.version.map(|v| v.to_string()),
+            title,
+            artist,
+            bpm,
             gap: self.gap.unwrap_or(0.0),
             video_gap: self.video_gap,
             genre: self.genre,
@@ -271,10 +629,19 @@ impl MetadataBuilder {
             creator: self.creator,
             duet_singer_p1: self.duet_singer_p1,
             duet_singer_p2: self.duet_singer_p2,
-            audio_file: self.audio_file,
+            audio_file,
             video_file: self.video_file,
             cover_file: self.cover_file,
             background_file: self.background_file,
+            vocals_file: self.vocals_file,
+            instrumental_file: self.instrumental_file,
+            start: self.start,
+            end: self.end,
+            preview_start: self.preview_start,
+            medley_start_beat: self.medley_start_beat,
+            medley_end_beat: self.medley_end_beat,
+            tag_order: self.tag_order,
+            extra_tags: self.extra_tags,
         })
     }
 }
@@ -282,6 +649,8 @@ impl MetadataBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::song::fixtures;
+    use crate::song::types::{NoteType, SongSummary};
     use std::path::PathBuf;
 
     #[test]
@@ -304,10 +673,10 @@ E
         assert_eq!(song.metadata.artist, "Test Artist");
         assert_eq!(song.metadata.bpm, 300.0);
         assert_eq!(song.metadata.gap, 1000.0);
-        assert_eq!(song.notes.len(), 3);
-        assert_eq!(song.line_breaks.len(), 1);
+        assert_eq!(song.tracks[0].notes.len(), 3);
+        assert_eq!(song.tracks[0].line_breaks.len(), 1);
 
-        let first_note = &song.notes[0];
+        let first_note = &song.tracks[0].notes[0];
         assert_eq!(first_note.note_type, NoteType::Normal);
         assert_eq!(first_note.start_beat, 0);
         assert_eq!(first_note.length, 5);
@@ -328,9 +697,9 @@ E
 "#;
         let song = Parser::parse(content, &PathBuf::from("test.txt")).unwrap();
 
-        assert_eq!(song.notes[0].note_type, NoteType::Normal);
-        assert_eq!(song.notes[1].note_type, NoteType::Golden);
-        assert_eq!(song.notes[2].note_type, NoteType::Freestyle);
+        assert_eq!(song.tracks[0].notes[0].note_type, NoteType::Normal);
+        assert_eq!(song.tracks[0].notes[1].note_type, NoteType::Golden);
+        assert_eq!(song.tracks[0].notes[2].note_type, NoteType::Freestyle);
     }
 
     #[test]
@@ -351,15 +720,39 @@ E
 "#;
         let song = Parser::parse(content, &PathBuf::from("test.txt")).unwrap();
 
-        assert!(song.notes_p2.is_some());
-        assert_eq!(song.notes.len(), 1);
-        assert_eq!(song.notes_p2.as_ref().unwrap().len(), 1);
-        assert_eq!(song.notes[0].text, "Player one");
-        assert_eq!(song.notes_p2.as_ref().unwrap()[0].text, "Player two");
+        assert_eq!(song.tracks.len(), 2);
+        assert_eq!(song.tracks[0].notes.len(), 1);
+        assert_eq!(song.tracks[1].notes.len(), 1);
+        assert_eq!(song.tracks[0].notes[0].text, "Player one");
+        assert_eq!(song.tracks[1].notes[0].text, "Player two");
+        assert_eq!(song.tracks[1].singer.as_deref(), Some("Singer Two"));
         assert_eq!(song.metadata.duet_singer_p1, Some("Singer One".to_string()));
         assert_eq!(song.metadata.duet_singer_p2, Some("Singer Two".to_string()));
     }
 
+    #[test]
+    fn test_content_id() {
+        let id =
+            |content: &str, path: &str| Parser::parse(content, &PathBuf::from(path)).unwrap().id;
+        let original = id(fixtures::SIMPLE, "/songs/Test/song.txt");
+
+        // Moving the file or fixing lyrics and sync keeps the ID
+        assert_eq!(id(fixtures::SIMPLE, "/mnt/karaoke/Other/x.txt"), original);
+        let edited = fixtures::SIMPLE
+            .replace("Hello", "Hallo")
+            .replace("#GAP:1000", "#GAP:1200")
+            .replace("Test Artist", "The Test Artist");
+        assert_eq!(id(&edited, "song.txt"), original);
+
+        // Different notes or another song do not
+        let transposed = fixtures::SIMPLE.replace(": 20 4 7 Test", ": 20 4 9 Test");
+        assert_ne!(id(&transposed, "song.txt"), original);
+        let renamed = fixtures::SIMPLE.replace("#TITLE:Test Song", "#TITLE:Other Song");
+        assert_ne!(id(&renamed, "song.txt"), original);
+
+        assert_ne!(Parser::path_id(Path::new("song.txt")), original);
+    }
+
     #[test]
     fn test_parse_bpm_with_comma() {
         let content = r#"
@@ -376,6 +769,23 @@ E
         assert_eq!(song.metadata.gap, 1234.56);
     }
 
+    #[test]
+    fn test_non_positive_bpm_is_invalid() {
+        for bpm in ["0", "-120", "inf"] {
+            let content = fixtures::SIMPLE.replace("#BPM:300", &format!("#BPM:{}", bpm));
+            let err = Parser::parse(&content, &PathBuf::from("test.txt")).unwrap_err();
+
+            assert_eq!(
+                err.kind,
+                ParseErrorKind::InvalidHeaderValue {
+                    tag: "BPM".to_string(),
+                    value: bpm.to_string(),
+                }
+            );
+            assert_eq!(err.line, Some(4));
+        }
+    }
+
     #[test]
     fn test_parse_negative_pitch() {
         let content = r#"
@@ -388,8 +798,8 @@ E
 "#;
         let song = Parser::parse(content, &PathBuf::from("test.txt")).unwrap();
 
-        assert_eq!(song.notes[0].pitch, -3);
-        assert_eq!(song.notes[1].pitch, 12);
+        assert_eq!(song.tracks[0].notes[0].pitch, -3);
+        assert_eq!(song.tracks[0].notes[1].pitch, 12);
     }
 
     #[test]
@@ -405,9 +815,9 @@ E
 "#;
         let song = Parser::parse(content, &PathBuf::from("test.txt")).unwrap();
 
-        assert_eq!(song.line_breaks.len(), 1);
-        assert_eq!(song.line_breaks[0].start_beat, 10);
-        assert_eq!(song.line_breaks[0].end_beat, Some(15));
+        assert_eq!(song.tracks[0].line_breaks.len(), 1);
+        assert_eq!(song.tracks[0].line_breaks[0].start_beat, 10);
+        assert_eq!(song.tracks[0].line_breaks[0].end_beat, Some(15));
     }
 
     #[test]
@@ -434,7 +844,308 @@ E
 "#;
         let song = Parser::parse(content, &PathBuf::from("test.txt")).unwrap();
 
-        assert_eq!(song.notes[0].note_type, NoteType::Rap);
-        assert_eq!(song.notes[1].note_type, NoteType::GoldenRap);
+        assert_eq!(song.tracks[0].notes[0].note_type, NoteType::Rap);
+        assert_eq!(song.tracks[0].notes[1].note_type, NoteType::GoldenRap);
+    }
+
+    #[test]
+    fn test_parse_format_1_1_tags() {
+        let content = r#"
+#VERSION:1.1.0
+#TITLE:Version Test
+#ARTIST:Test
+#MP3:legacy.mp3
+#AUDIO:song.ogg
+#VOCALS:vocals.ogg
+#INSTRUMENTAL:instrumental.ogg
+#BPM:400
+#START:12.5
+#END:183000
+#PREVIEWSTART:45,5
+#MEDLEYSTARTBEAT:120
+#MEDLEYENDBEAT:480
+: 0 5 7 Test
+E
+"#;
+        let song = Parser::parse(content, &PathBuf::from("test.txt")).unwrap();
+
+        assert_eq!(song.metadata.version, Some("1.1.0".to_string()));
+        assert_eq!(song.metadata.audio_file, Some("song.ogg".to_string()));
+        assert_eq!(song.metadata.vocals_file, Some("vocals.ogg".to_string()));
+        assert_eq!(
+            song.metadata.instrumental_file,
+            Some("instrumental.ogg".to_string())
+        );
+        assert_eq!(song.metadata.start, Some(12.5));
+        assert_eq!(song.metadata.end, Some(183000.0));
+        assert_eq!(song.metadata.preview_start, Some(45.5));
+        assert_eq!(song.metadata.medley_start_beat, Some(120));
+        assert_eq!(song.metadata.medley_end_beat, Some(480));
+    }
+
+    #[test]
+    fn test_legacy_mp3_wins_over_audio() {
+        let content = r#"
+#TITLE:Legacy
+#ARTIST:Test
+#AUDIO:other.ogg
+#MP3:song.mp3
+#BPM:400
+: 0 5 7 Test
+E
+"#;
+        let song = Parser::parse(content, &PathBuf::from("test.txt")).unwrap();
+
+        assert_eq!(song.metadata.version, None);
+        assert_eq!(song.metadata.audio_file, Some("song.mp3".to_string()));
+    }
+
+    #[test]
+    fn test_unsupported_major_version() {
+        let content = r#"
+#VERSION:2.0.0
+#TITLE:Future
+#ARTIST:Test
+#BPM:400
+: 0 5 7 Test
+E
+"#;
+        let result = Parser::parse(content, &PathBuf::from("test.txt"));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_relative_timing() {
+        let content = r#"
+#TITLE:Relative Test
+#ARTIST:Test
+#BPM:400
+#RELATIVE:yes
+: 0 5 7 One
+: 6 3 5 Two
+- 12 20
+: 0 4 7 Three
+: 5 4 7 Four
+- 10 15
+: 2 4 7 Five
+E
+"#;
+        let song = Parser::parse(content, &PathBuf::from("test.txt")).unwrap();
+
+        let beats: Vec<i32> = song.tracks[0].notes.iter().map(|n| n.start_beat).collect();
+        assert_eq!(beats, vec![0, 6, 20, 25, 37]);
+        assert_eq!(song.tracks[0].line_breaks.len(), 2);
+        assert_eq!(song.tracks[0].line_breaks[0].start_beat, 12);
+        assert_eq!(song.tracks[0].line_breaks[0].end_beat, None);
+        assert_eq!(song.tracks[0].line_breaks[1].start_beat, 30);
+    }
+
+    #[test]
+    fn test_parse_relative_duet() {
+        let content = r#"
+#TITLE:Relative Duet
+#ARTIST:Test
+#BPM:400
+#RELATIVE:yes
+P1
+: 0 5 7 One
+- 8 10
+: 0 5 7 Two
+P2
+: 4 5 5 Three
+- 12 16
+: 2 5 5 Four
+E
+"#;
+        let song = Parser::parse(content, &PathBuf::from("test.txt")).unwrap();
+
+        let p1: Vec<i32> = song.tracks[0].notes.iter().map(|n| n.start_beat).collect();
+        let p2: Vec<i32> = song.tracks[1].notes.iter().map(|n| n.start_beat).collect();
+        assert_eq!(p1, vec![0, 10]);
+        assert_eq!(p2, vec![4, 18]);
+        assert_eq!(song.tracks[0].line_breaks[0].start_beat, 8);
+        assert_eq!(song.tracks[1].line_breaks[0].start_beat, 12);
+    }
+
+    #[test]
+    fn test_relative_line_break_requires_shift() {
+        let content = r#"
+#TITLE:Relative Test
+#ARTIST:Test
+#BPM:400
+#RELATIVE:yes
+: 0 5 7 One
+- 12
+: 0 4 7 Two
+E
+"#;
+        let result = Parser::parse(content, &PathBuf::from("test.txt"));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_relative_beat_out_of_range() {
+        let content = "#TITLE:Relative\n#ARTIST:Test\n#BPM:400\n#RELATIVE:yes\n: 0 5 7 One\n- 8 2147483647\n: 1 4 7 Two\nE\n";
+        let err = Parser::parse(content, &PathBuf::from("test.txt")).unwrap_err();
+
+        assert_eq!(err.kind, ParseErrorKind::BeatOutOfRange("1".to_string()));
+        assert_eq!(err.line, Some(7));
+        assert_eq!(err.column, Some(3));
+
+        let content = "#TITLE:Relative\n#ARTIST:Test\n#BPM:400\n#RELATIVE:yes\n: 0 5 7 One\n- 8 2147483647\n: 0 4 7 Two\n- 0  1\nE\n";
+        let err = Parser::parse(content, &PathBuf::from("test.txt")).unwrap_err();
+
+        assert_eq!(err.kind, ParseErrorKind::BeatOutOfRange("1".to_string()));
+        assert_eq!(err.line, Some(8));
+        assert_eq!(err.column, Some(6));
+    }
+
+    #[test]
+    fn test_relative_rejected_in_versioned_files() {
+        let content = r#"
+#VERSION:1.0.0
+#TITLE:Relative Test
+#ARTIST:Test
+#BPM:400
+#RELATIVE:yes
+: 0 5 7 One
+E
+"#;
+        let result = Parser::parse(content, &PathBuf::from("test.txt"));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_extra_tags() {
+        let content = r#"
+#TITLE:Custom Tags
+#ARTIST:Test
+#TAGS:rock, live
+#BPM:400
+#author:Someone
+#RENDITION:Acoustic
+#COMMENT:First
+#COMMENT:Second
+: 0 5 7 Test
+E
+"#;
+        let song = Parser::parse(content, &PathBuf::from("test.txt")).unwrap();
+
+        let tags: Vec<(&str, &str)> = song
+            .metadata
+            .extra_tags
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        assert_eq!(
+            tags,
+            vec![
+                ("TAGS", "rock, live"),
+                ("author", "Someone"),
+                ("RENDITION", "Acoustic"),
+                ("COMMENT", "First"),
+                ("COMMENT", "Second"),
+            ]
+        );
+        assert_eq!(song.metadata.extra_tag("AUTHOR"), Some("Someone"));
+        assert_eq!(song.metadata.extra_tag("comment"), Some("Second"));
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        let content =
+            "#TITLE:Location\n#ARTIST:Test\n#BPM:400\n: 0 5 7 Fine\n  : 8 x 7 Broken\nE\n";
+        let err = Parser::parse(content, &PathBuf::from("test.txt")).unwrap_err();
+
+        assert_eq!(err.kind, ParseErrorKind::InvalidLength("x".to_string()));
+        assert_eq!(err.line, Some(5));
+        assert_eq!(err.column, Some(7));
+        assert_eq!(err.snippet.as_deref(), Some(": 8 x 7 Broken"));
+    }
+
+    #[test]
+    fn test_header_error_location() {
+        let content = "#TITLE:Location\n#ARTIST:Test\n#BPM: fast\n: 0 5 7 Fine\nE\n";
+        let err = Parser::parse(content, &PathBuf::from("test.txt")).unwrap_err();
+
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::InvalidHeaderValue {
+                tag: "BPM".to_string(),
+                value: "fast".to_string()
+            }
+        );
+        assert_eq!(err.line, Some(3));
+        assert_eq!(err.column, Some(7));
+    }
+
+    #[test]
+    fn test_missing_tag_has_no_location() {
+        let content = "#TITLE:Location\n#ARTIST:Test\n: 0 5 7 Fine\nE\n";
+        let err = Parser::parse(content, &PathBuf::from("test.txt")).unwrap_err();
+
+        assert_eq!(err.kind, ParseErrorKind::MissingTag("BPM"));
+        assert_eq!(err.line, None);
+        assert_eq!(err.to_string(), "Missing required BPM tag");
+    }
+
+    #[test]
+    fn test_p3_means_both_singers_in_duets() {
+        let song = Parser::parse(fixtures::DUET_BOTH, &PathBuf::from("test.txt")).unwrap();
+
+        assert_eq!(song.tracks.len(), 2);
+        let texts = |track: usize| -> Vec<&str> {
+            song.tracks[track]
+                .notes
+                .iter()
+                .map(|n| n.text.as_str())
+                .collect()
+        };
+        assert_eq!(texts(0), vec!["One", "Together"]);
+        assert_eq!(texts(1), vec!["Two", "Together"]);
+        assert_eq!(song.tracks[1].line_breaks[0].start_beat, 14);
+    }
+
+    #[test]
+    fn test_p3_is_own_track_when_singer_declared() {
+        let song = Parser::parse(fixtures::THREE_TRACKS, &PathBuf::from("test.txt")).unwrap();
+
+        assert_eq!(song.tracks.len(), 3);
+        assert_eq!(song.tracks[2].singer.as_deref(), Some("Tenor"));
+        assert_eq!(song.tracks[2].notes[0].text, "Low");
+        assert_eq!(song.tracks[0].notes.len(), 1);
+        assert_eq!(SongSummary::from(&song).track_count, 3);
+    }
+
+    #[test]
+    fn test_huge_player_number_is_unknown_line() {
+        let content = fixtures::SIMPLE.replace(": 0 5 7 Hello", "P 4000000000\n: 0 5 7 Hello");
+        let output = Parser::parse_lenient(&content, &PathBuf::from("test.txt"));
+
+        assert_eq!(output.song.tracks.len(), 1);
+        assert_eq!(output.warnings[0].kind, ParseErrorKind::UnknownLine);
+        assert_eq!(output.warnings[0].line, Some(6));
+    }
+
+    #[test]
+    fn test_song_json_keeps_two_player_fields() {
+        let song = Parser::parse(fixtures::DUET, &PathBuf::from("test.txt")).unwrap();
+        let json = serde_json::to_value(&song).unwrap();
+
+        assert_eq!(json["tracks"].as_array().unwrap().len(), 2);
+        assert_eq!(json["tracks"][1]["notes"][0]["text"], "Player two");
+        assert!(json.get("files").is_none());
+        assert_eq!(json["notes"][0]["text"], "Player one");
+        assert_eq!(json["notes_p2"][0]["text"], "Player two");
+        assert_eq!(json["line_breaks_p2"][0]["start_beat"], 10);
+
+        let restored: Song = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(restored.tracks, song.tracks);
+
+        // Payloads without `tracks` still deserialize
+        let mut legacy = json.clone();
+        legacy.as_object_mut().unwrap().remove("tracks");
+        let restored: Song = serde_json::from_value(legacy).unwrap();
+        assert_eq!(restored.tracks, song.tracks);
     }
 }
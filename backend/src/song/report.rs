@@ -0,0 +1,308 @@
+use crate::song::validator::{ValidationError, ValidationResult};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Machine-readable formats for validation results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// The serialized [`ValidationResult`]s
+    Json,
+    /// SARIF 2.1.0, for code scanning and review tools
+    Sarif,
+    /// JUnit XML with one test case per file
+    Junit,
+    /// A summary table for pull request comments
+    Markdown,
+}
+
+/// Renders validation results for CI and code review tooling
+pub struct Reporter;
+
+impl Reporter {
+    /// Render results; paths are shown relative to `root`
+    pub fn render(results: &[ValidationResult], root: &Path, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Json => serde_json::to_string_pretty(results).unwrap(),
+            ReportFormat::Sarif => Self::sarif(results, root),
+            ReportFormat::Junit => Self::junit(results, root),
+            ReportFormat::Markdown => Self::markdown(results, root),
+        }
+    }
+
+    fn sarif(results: &[ValidationResult], root: &Path) -> String {
+        let mut rules = BTreeMap::new();
+        let mut sarif_results = Vec::new();
+        for result in results {
+            let uri = uri_encode(&relative_path(&result.path, root));
+            for (level, finding) in findings(result) {
+                rules.insert(finding.kind.rule_id(), finding.kind.code());
+
+                let mut location = json!({ "artifactLocation": { "uri": uri } });
+                if let Some(line) = finding.line {
+                    location["region"] = json!({ "startLine": line });
+                }
+                sarif_results.push(json!({
+                    "ruleId": finding.kind.rule_id(),
+                    "level": level,
+                    "message": { "text": message(finding) },
+                    "locations": [{ "physicalLocation": location }],
+                }));
+            }
+        }
+
+        let rules: Vec<_> = rules
+            .into_iter()
+            .map(|(id, name)| json!({ "id": id, "name": name }))
+            .collect();
+        let sarif = json!({
+            "$schema": SARIF_SCHEMA,
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "frank-validate",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "rules": rules,
+                    }
+                },
+                "results": sarif_results,
+            }],
+        });
+        serde_json::to_string_pretty(&sarif).unwrap()
+    }
+
+    fn junit(results: &[ValidationResult], root: &Path) -> String {
+        let failures = results.iter().filter(|r| !r.is_valid()).count();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let counts = format!("tests=\"{}\" failures=\"{}\"", results.len(), failures);
+        let _ = writeln!(xml, "<testsuites name=\"frank-validate\" {}>", counts);
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"frank-validate\" {} errors=\"0\">",
+            counts
+        );
+
+        for result in results {
+            let name = xml_escape(&relative_path(&result.path, root));
+            let _ = write!(
+                xml,
+                "    <testcase classname=\"frank-validate\" name=\"{}\"",
+                name
+            );
+            if result.errors.is_empty() && result.warnings.is_empty() {
+                xml.push_str("/>\n");
+                continue;
+            }
+            xml.push_str(">\n");
+
+            if let Some(first) = result.errors.first() {
+                let _ = write!(
+                    xml,
+                    "      <failure type=\"{}\" message=\"{}\">",
+                    first.kind.rule_id(),
+                    xml_escape(&format!("{} errors", result.errors.len()))
+                );
+                for error in &result.errors {
+                    let _ = writeln!(xml, "{}", xml_escape(&describe(error)));
+                }
+                xml.push_str("</failure>\n");
+            }
+            if !result.warnings.is_empty() {
+                xml.push_str("      <system-out>");
+                for warning in &result.warnings {
+                    let _ = writeln!(xml, "warning: {}", xml_escape(&describe(warning)));
+                }
+                xml.push_str("</system-out>\n");
+            }
+            xml.push_str("    </testcase>\n");
+        }
+
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+
+    fn markdown(results: &[ValidationResult], root: &Path) -> String {
+        let invalid = results.iter().filter(|r| !r.is_valid()).count();
+        let warnings: usize = results.iter().map(|r| r.warnings.len()).sum();
+
+        let mut md = String::from("## frank-validate\n\n");
+        let _ = writeln!(
+            md,
+            "**{}** files checked: **{}** valid, **{}** with errors, **{}** warnings\n",
+            results.len(),
+            results.len() - invalid,
+            invalid,
+            warnings
+        );
+
+        if results
+            .iter()
+            .all(|r| r.errors.is_empty() && r.warnings.is_empty())
+        {
+            md.push_str("No problems found.\n");
+            return md;
+        }
+
+        md.push_str("| File | Line | Rule | Severity | Message |\n");
+        md.push_str("| --- | ---: | --- | --- | --- |\n");
+        for result in results {
+            let path = table_escape(&relative_path(&result.path, root));
+            for (level, finding) in findings(result) {
+                let line = finding.line.map(|l| l.to_string()).unwrap_or_default();
+                let _ = writeln!(
+                    md,
+                    "| {} | {} | {} | {} | {} |",
+                    path,
+                    line,
+                    finding.kind.rule_id(),
+                    level,
+                    table_escape(&message(finding))
+                );
+            }
+        }
+        md
+    }
+}
+
+/// Errors, then warnings, with their SARIF level
+fn findings(result: &ValidationResult) -> impl Iterator<Item = (&'static str, &ValidationError)> {
+    let errors = result.errors.iter().map(|e| ("error", e));
+    errors.chain(result.warnings.iter().map(|w| ("warning", w)))
+}
+
+fn message(finding: &ValidationError) -> String {
+    match &finding.context {
+        Some(context) => format!("{} ({})", finding.kind, context),
+        None => finding.kind.to_string(),
+    }
+}
+
+fn describe(finding: &ValidationError) -> String {
+    match finding.line {
+        Some(line) => format!(
+            "line {}: [{}] {}",
+            line,
+            finding.kind.rule_id(),
+            message(finding)
+        ),
+        None => format!("[{}] {}", finding.kind.rule_id(), message(finding)),
+    }
+}
+
+/// Path relative to `root` with forward slashes
+fn relative_path(path: &Path, root: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Percent-encode everything but unreserved characters and `/`
+fn uri_encode(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => {
+                let _ = write!(encoded, "%{:02X}", byte);
+            }
+        }
+    }
+    encoded
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn table_escape(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::song::validator::ValidationErrorKind;
+    use std::path::PathBuf;
+
+    fn results() -> Vec<ValidationResult> {
+        vec![
+            ValidationResult {
+                path: PathBuf::from("/songs/A - B/song.txt"),
+                errors: vec![ValidationError {
+                    kind: ValidationErrorKind::InvalidNoteFormat("Invalid pitch: x".to_string()),
+                    line: Some(5),
+                    context: Some(": 0 5 x a".to_string()),
+                }],
+                warnings: vec![ValidationError {
+                    kind: ValidationErrorKind::ContainsBom,
+                    line: None,
+                    context: None,
+                }],
+            },
+            ValidationResult {
+                path: PathBuf::from("/songs/C - D/song.txt"),
+                errors: vec![],
+                warnings: vec![],
+            },
+        ]
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let json = Reporter::render(&results(), Path::new("/songs"), ReportFormat::Json);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(
+            value[0]["errors"][0],
+            json!({
+                "rule": "FV012",
+                "kind": "invalid_note_format",
+                "message": "Invalid note format: Invalid pitch: x",
+                "line": 5,
+                "context": ": 0 5 x a",
+            })
+        );
+        assert_eq!(value[1]["path"], "/songs/C - D/song.txt");
+    }
+
+    #[test]
+    fn test_sarif() {
+        let sarif = Reporter::render(&results(), Path::new("/songs"), ReportFormat::Sarif);
+        let value: serde_json::Value = serde_json::from_str(&sarif).unwrap();
+        let run = &value["runs"][0];
+
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "FV002");
+        assert_eq!(run["results"][0]["ruleId"], "FV012");
+        assert_eq!(run["results"][0]["level"], "error");
+        let location = &run["results"][0]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "A%20-%20B/song.txt");
+        assert_eq!(location["region"]["startLine"], 5);
+        assert_eq!(run["results"][1]["level"], "warning");
+    }
+
+    #[test]
+    fn test_junit_and_markdown() {
+        let junit = Reporter::render(&results(), Path::new("/songs"), ReportFormat::Junit);
+        assert!(junit.contains("<testsuites name=\"frank-validate\" tests=\"2\" failures=\"1\">"));
+        assert!(junit.contains("<failure type=\"FV012\" message=\"1 errors\">line 5: [FV012]"));
+        assert!(junit.contains("<testcase classname=\"frank-validate\" name=\"C - D/song.txt\"/>"));
+
+        let markdown = Reporter::render(&results(), Path::new("/songs"), ReportFormat::Markdown);
+        assert!(markdown
+            .contains("**2** files checked: **1** valid, **1** with errors, **1** warnings"));
+        assert!(markdown.contains("| A - B/song.txt | 5 | FV012 | error |"));
+        assert!(markdown.contains("| A - B/song.txt |  | FV002 | warning |"));
+    }
+}
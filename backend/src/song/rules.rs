@@ -0,0 +1,241 @@
+use crate::song::validator::{ValidationErrorKind, ValidationResult};
+use globset::{Glob, GlobMatcher};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Config file looked up in the root of a song library
+pub const CONFIG_FILE_NAME: &str = "frank-validate.toml";
+
+#[derive(Debug, Error)]
+pub enum RuleConfigError {
+    #[error("Could not read {0:?}: {1}")]
+    Io(PathBuf, std::io::Error),
+
+    #[error("Invalid config: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    #[error("Invalid path glob: {0}")]
+    Glob(#[from] globset::Error),
+
+    #[error("Unknown rule: {0}")]
+    UnknownRule(String),
+
+    #[error("Rule {0} reports songs that fail to load and cannot be configured")]
+    FixedRule(String),
+}
+
+/// How findings of a rule are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Off,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+    #[serde(default)]
+    rules: HashMap<String, Severity>,
+    #[serde(default)]
+    paths: Vec<RawPathRules>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPathRules {
+    glob: String,
+    rules: HashMap<String, Severity>,
+}
+
+/// Rule severities from a `frank-validate.toml`
+///
+/// ```toml
+/// [rules]
+/// FV029 = "off"
+/// FV025 = "error"
+///
+/// [[paths]]
+/// glob = "Curated/**"
+/// rules = { FV026 = "error" }
+/// ```
+///
+/// Globs match paths relative to the library root; later `[[paths]]`
+/// sections win over earlier ones and over `[rules]`.
+#[derive(Debug, Default)]
+pub struct RuleConfig {
+    root: PathBuf,
+    rules: HashMap<String, Severity>,
+    paths: Vec<(GlobMatcher, HashMap<String, Severity>)>,
+}
+
+impl RuleConfig {
+    /// Load `frank-validate.toml` from the library root, if there is one
+    pub fn discover(root: &Path) -> Result<Option<Self>, RuleConfigError> {
+        let path = root.join(CONFIG_FILE_NAME);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Self::load(&path, root).map(Some)
+    }
+
+    pub fn load(path: &Path, root: &Path) -> Result<Self, RuleConfigError> {
+        let content =
+            std::fs::read_to_string(path).map_err(|e| RuleConfigError::Io(path.into(), e))?;
+        Self::parse(&content, root)
+    }
+
+    pub fn parse(content: &str, root: &Path) -> Result<Self, RuleConfigError> {
+        let raw: RawConfig = toml::from_str(content)?;
+        check_rules(&raw.rules)?;
+
+        let mut paths = Vec::new();
+        for section in raw.paths {
+            check_rules(&section.rules)?;
+            let matcher = Glob::new(&section.glob)?.compile_matcher();
+            paths.push((matcher, section.rules));
+        }
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            rules: raw.rules,
+            paths,
+        })
+    }
+
+    /// Configured severity of a rule for a file, if any
+    pub fn severity(&self, rule_id: &str, path: &Path) -> Option<Severity> {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        self.paths
+            .iter()
+            .rev()
+            .filter(|(glob, _)| glob.is_match(relative))
+            .find_map(|(_, rules)| rules.get(rule_id))
+            .or_else(|| self.rules.get(rule_id))
+            .copied()
+    }
+
+    /// Move findings between errors and warnings, or drop them, as configured
+    pub fn apply(&self, result: &mut ValidationResult) {
+        let errors = std::mem::take(&mut result.errors);
+        let warnings = std::mem::take(&mut result.warnings);
+        let findings = errors
+            .into_iter()
+            .map(|e| (Severity::Error, e))
+            .chain(warnings.into_iter().map(|w| (Severity::Warning, w)));
+
+        for (default, finding) in findings {
+            let rule_id = finding.kind.rule_id();
+            let severity = if ValidationErrorKind::PARSE_ERROR_RULE_IDS.contains(&rule_id) {
+                default
+            } else {
+                self.severity(rule_id, &result.path).unwrap_or(default)
+            };
+            match severity {
+                Severity::Error => result.errors.push(finding),
+                Severity::Warning => result.warnings.push(finding),
+                Severity::Off => {}
+            }
+        }
+    }
+}
+
+fn check_rules(rules: &HashMap<String, Severity>) -> Result<(), RuleConfigError> {
+    for id in rules.keys() {
+        if !ValidationErrorKind::RULE_IDS.contains(&id.as_str()) {
+            return Err(RuleConfigError::UnknownRule(id.clone()));
+        }
+        if ValidationErrorKind::PARSE_ERROR_RULE_IDS.contains(&id.as_str()) {
+            return Err(RuleConfigError::FixedRule(id.clone()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::song::validator::ValidationError;
+
+    fn finding(kind: ValidationErrorKind) -> ValidationError {
+        ValidationError {
+            kind,
+            line: None,
+            context: None,
+        }
+    }
+
+    fn rules(findings: &[ValidationError]) -> Vec<&'static str> {
+        findings.iter().map(|f| f.kind.rule_id()).collect()
+    }
+
+    #[test]
+    fn test_apply_severities() {
+        let config = RuleConfig::parse(
+            r#"
+[rules]
+FV002 = "error"
+FV028 = "off"
+FV021 = "warning"
+
+[[paths]]
+glob = "Curated/**"
+rules = { FV028 = "error" }
+"#,
+            Path::new("/songs"),
+        )
+        .unwrap();
+
+        let result = |path: &str| ValidationResult {
+            path: PathBuf::from(path),
+            errors: vec![finding(ValidationErrorKind::NoNotes)],
+            warnings: vec![
+                finding(ValidationErrorKind::ContainsBom),
+                finding(ValidationErrorKind::EmptyLine),
+            ],
+        };
+
+        let mut other = result("/songs/Other/song.txt");
+        config.apply(&mut other);
+        assert_eq!(rules(&other.errors), vec!["FV002"]);
+        assert_eq!(rules(&other.warnings), vec!["FV021"]);
+
+        let mut curated = result("/songs/Curated/A - B/song.txt");
+        config.apply(&mut curated);
+        assert_eq!(rules(&curated.errors), vec!["FV002", "FV028"]);
+        assert_eq!(rules(&curated.warnings), vec!["FV021"]);
+    }
+
+    #[test]
+    fn test_rejects_unknown_rules() {
+        let result = RuleConfig::parse("[rules]\nFV999 = \"off\"\n", Path::new("."));
+        assert!(matches!(result, Err(RuleConfigError::UnknownRule(id)) if id == "FV999"));
+
+        let result = RuleConfig::parse("[rules]\nFV001 = \"fatal\"\n", Path::new("."));
+        assert!(matches!(result, Err(RuleConfigError::Toml(_))));
+    }
+
+    #[test]
+    fn test_parse_error_rules_are_fixed() {
+        let result = RuleConfig::parse(
+            "[[paths]]\nglob = \"**\"\nrules = { FV012 = \"off\" }\n",
+            Path::new("."),
+        );
+        assert!(matches!(result, Err(RuleConfigError::FixedRule(id)) if id == "FV012"));
+
+        let config = RuleConfig {
+            rules: HashMap::from([("FV005".to_string(), Severity::Off)]),
+            ..Default::default()
+        };
+        let mut result = ValidationResult {
+            path: PathBuf::from("song.txt"),
+            errors: vec![finding(ValidationErrorKind::MissingBpm)],
+            warnings: vec![],
+        };
+        config.apply(&mut result);
+        assert_eq!(rules(&result.errors), vec!["FV005"]);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::song::lyrics::split_lines;
+use crate::song::types::{LineBreak, Note, Song};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A time span in milliseconds from the start of the audio
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Timing {
+    pub start_ms: f64,
+    pub end_ms: f64,
+}
+
+/// Precomputed millisecond timings for a song, parallel to its note data
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SongTimings {
+    pub ms_per_beat: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_note_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_note_ms: Option<f64>,
+    pub singing_duration_ms: f64,
+    /// One entry per track in `Song::tracks`
+    pub tracks: Vec<TrackTimings>,
+}
+
+/// Timings of one singer track
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TrackTimings {
+    /// One entry per note in `Track::notes`
+    pub notes: Vec<Timing>,
+    /// One entry per non-empty line
+    pub lines: Vec<Timing>,
+}
+
+impl Song {
+    /// Milliseconds per beat; UltraStar beats are quarter notes of the BPM
+    pub fn ms_per_beat(&self) -> f64 {
+        60000.0 / (self.metadata.bpm * 4.0)
+    }
+
+    /// Convert a beat to milliseconds from the start of the audio
+    pub fn beat_to_ms(&self, beat: f64) -> f64 {
+        self.metadata.gap + beat * self.ms_per_beat()
+    }
+
+    /// Convert milliseconds from the start of the audio to a (fractional) beat
+    pub fn ms_to_beat(&self, ms: f64) -> f64 {
+        (ms - self.metadata.gap) / self.ms_per_beat()
+    }
+
+    /// Start and end time of a note
+    pub fn note_timing(&self, note: &Note) -> Timing {
+        Timing {
+            start_ms: self.beat_to_ms(note.start_beat as f64),
+            end_ms: self.beat_to_ms((note.start_beat + note.length) as f64),
+        }
+    }
+
+    /// Span from the earliest note start to the latest note end
+    pub fn span_timing(&self, notes: &[Note]) -> Option<Timing> {
+        notes
+            .iter()
+            .map(|note| self.note_timing(note))
+            .reduce(|span, timing| Timing {
+                start_ms: span.start_ms.min(timing.start_ms),
+                end_ms: span.end_ms.max(timing.end_ms),
+            })
+    }
+
+    /// Start and end time of each line, where lines are the notes between
+    /// line breaks; lines without notes are skipped
+    pub fn line_timings(&self, notes: &[Note], line_breaks: &[LineBreak]) -> Vec<Timing> {
+        split_lines(notes, line_breaks)
+            .into_iter()
+            .filter_map(|line| self.span_timing(line))
+            .collect()
+    }
+
+    /// Start time of the earliest note of any player
+    pub fn first_note_ms(&self) -> Option<f64> {
+        self.all_notes()
+            .map(|note| note.start_beat)
+            .min()
+            .map(|beat| self.beat_to_ms(beat as f64))
+    }
+
+    /// End time of the latest note of any player
+    pub fn last_note_ms(&self) -> Option<f64> {
+        self.all_notes()
+            .map(|note| note.start_beat + note.length)
+            .max()
+            .map(|beat| self.beat_to_ms(beat as f64))
+    }
+
+    /// Time from the first note's start to the last note's end
+    pub fn singing_duration_ms(&self) -> f64 {
+        match (self.first_note_ms(), self.last_note_ms()) {
+            (Some(first), Some(last)) => last - first,
+            _ => 0.0,
+        }
+    }
+
+    /// Precompute all timings, e.g. for API clients
+    pub fn timings(&self) -> SongTimings {
+        SongTimings {
+            ms_per_beat: self.ms_per_beat(),
+            first_note_ms: self.first_note_ms(),
+            last_note_ms: self.last_note_ms(),
+            singing_duration_ms: self.singing_duration_ms(),
+            tracks: self
+                .tracks
+                .iter()
+                .map(|track| TrackTimings {
+                    notes: track.notes.iter().map(|n| self.note_timing(n)).collect(),
+                    lines: self.line_timings(&track.notes, &track.line_breaks),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::song::fixtures;
+    use crate::song::parser::Parser;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_beat_ms_conversion() {
+        // BPM 300 => 50ms per beat, GAP 1000
+        let song = Parser::parse(fixtures::SIMPLE, &PathBuf::from("test.txt")).unwrap();
+
+        assert_eq!(song.ms_per_beat(), 50.0);
+        assert_eq!(song.beat_to_ms(0.0), 1000.0);
+        assert_eq!(song.beat_to_ms(20.0), 2000.0);
+        assert_eq!(song.ms_to_beat(2000.0), 20.0);
+        assert_eq!(song.ms_to_beat(song.beat_to_ms(7.5)), 7.5);
+    }
+
+    #[test]
+    fn test_song_timings() {
+        let song = Parser::parse(fixtures::SIMPLE, &PathBuf::from("test.txt")).unwrap();
+        let timings = song.timings();
+
+        assert_eq!(timings.first_note_ms, Some(1000.0));
+        // Last note: 20 + 4 beats
+        assert_eq!(timings.last_note_ms, Some(2200.0));
+        assert_eq!(timings.singing_duration_ms, 1200.0);
+        assert_eq!(timings.tracks.len(), 1);
+        let track = &timings.tracks[0];
+        assert_eq!(track.notes.len(), song.tracks[0].notes.len());
+        assert_eq!(track.notes[1].start_ms, 1400.0);
+        assert_eq!(track.notes[1].end_ms, 1550.0);
+        assert_eq!(track.lines.len(), 2);
+        assert_eq!(track.lines[0].start_ms, 1000.0);
+        assert_eq!(track.lines[0].end_ms, 1550.0);
+        assert_eq!(track.lines[1].start_ms, 2000.0);
+    }
+
+    #[test]
+    fn test_duet_timings() {
+        let song = Parser::parse(fixtures::DUET, &PathBuf::from("test.txt")).unwrap();
+        let timings = song.timings();
+
+        assert_eq!(timings.tracks.len(), 2);
+        assert_eq!(timings.tracks[1].notes.len(), 2);
+        assert_eq!(timings.tracks[1].lines.len(), 2);
+    }
+}
@@ -0,0 +1,334 @@
+use crate::song::parser::{ParseError, ParseErrorKind};
+use crate::song::types::{LineBreak, Note, NoteType};
+
+/// Highest player number a `P` line may switch to; files never have more
+/// than a handful of singers, and each player gets a track allocated
+pub const MAX_PLAYERS: usize = 9;
+
+/// A single classified line of an UltraStar TXT file
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token<'a> {
+    /// `#TAG:value`, with the tag upper-cased and the value trimmed
+    Header {
+        tag: String,
+        /// The tag as spelled in the file
+        name: &'a str,
+        value: &'a str,
+    },
+    /// Player switch (`P1`, `P 2`, ...)
+    Player(usize),
+    Note(Note),
+    LineBreak(LineBreak),
+    /// `E` end marker
+    End,
+    /// Anything else; players ignore these lines
+    Unknown,
+}
+
+/// A non-empty input line together with its position
+#[derive(Debug, Clone, Copy)]
+pub struct SourceLine<'a> {
+    /// 1-indexed line number
+    pub number: usize,
+    /// Number of characters trimmed from the start of the line
+    pub indent: usize,
+    /// The line with surrounding whitespace removed
+    pub text: &'a str,
+}
+
+impl SourceLine<'_> {
+    /// Attach this line's location to an error produced while reading it
+    pub fn locate(&self, error: ParseError) -> ParseError {
+        error.on_line(self.number, self.indent, self.text)
+    }
+}
+
+/// Splits file content into tokens, one per non-empty line, up to the `E` marker
+///
+/// The tokenizer never stops on malformed lines: each line yields its own
+/// result so callers can decide whether to abort or keep going.
+pub struct Tokenizer<'a> {
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+    finished: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(content: &'a str) -> Self {
+        Self {
+            lines: content.lines().enumerate(),
+            finished: false,
+        }
+    }
+
+    /// Classify a line; `untrimmed` is the same line with trailing whitespace
+    /// kept, since it is part of a note's syllable text
+    fn tokenize_line(line: &'a str, untrimmed: &'a str) -> Result<Token<'a>, ParseError> {
+        if let Some(header) = line.strip_prefix('#') {
+            // Format: #TAG:value
+            let (tag, value) = header.split_once(':').unwrap_or((header, ""));
+            Ok(Token::Header {
+                tag: tag.trim().to_uppercase(),
+                name: tag.trim(),
+                value: value.trim(),
+            })
+        } else if let Some(player) = line.strip_prefix('P') {
+            // Player switch (P1, P2, "P 1", ...)
+            Ok(player
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&player| player <= MAX_PLAYERS)
+                .map(Token::Player)
+                .unwrap_or(Token::Unknown))
+        } else if line.starts_with(':')
+            || line.starts_with('*')
+            || line.starts_with('F')
+            || line.starts_with('R')
+            || line.starts_with('G')
+        {
+            Self::parse_note_line(untrimmed).map(Token::Note)
+        } else if line.starts_with('-') {
+            Self::parse_line_break(line).map(Token::LineBreak)
+        } else if line == "E" {
+            Ok(Token::End)
+        } else {
+            Ok(Token::Unknown)
+        }
+    }
+
+    fn parse_note_line(line: &str) -> Result<Note, ParseError> {
+        // Format: NoteType StartBeat Length Pitch Text
+        // Example: : 0 5 7 Some~ ly~
+        //
+        // The numeric fields may be separated by any run of whitespace. A
+        // single separator follows the pitch; everything after it is the
+        // syllable text, kept verbatim (leading space, trailing `~`, tabs).
+        let symbol = line.chars().next().unwrap_or_default();
+        let note_type = NoteType::from_symbol(symbol)
+            .ok_or_else(|| ParseError::at(ParseErrorKind::UnknownNoteType(symbol), line, line))?;
+
+        let rest = &line[symbol.len_utf8()..];
+        let missing_fields =
+            || ParseError::at(ParseErrorKind::MissingNoteFields, line, rest.trim_start());
+
+        let (start, rest) = Self::next_field(rest).ok_or_else(missing_fields)?;
+        let (length, rest) = Self::next_field(rest).ok_or_else(missing_fields)?;
+        let (pitch, rest) = Self::next_field(rest).ok_or_else(missing_fields)?;
+
+        let start_beat: i32 = start.parse().map_err(|_| {
+            ParseError::at(
+                ParseErrorKind::InvalidStartBeat(start.to_string()),
+                line,
+                start,
+            )
+        })?;
+        let length: i32 = length.parse().map_err(|_| {
+            ParseError::at(
+                ParseErrorKind::InvalidLength(length.to_string()),
+                line,
+                length,
+            )
+        })?;
+        let pitch: i32 = pitch.parse().map_err(|_| {
+            ParseError::at(ParseErrorKind::InvalidPitch(pitch.to_string()), line, pitch)
+        })?;
+
+        // Drop the one separator after the pitch; a note may have no text
+        let mut text = rest.chars();
+        text.next();
+        let text = text.as_str().to_string();
+
+        Ok(Note {
+            note_type,
+            start_beat,
+            length,
+            pitch,
+            text,
+        })
+    }
+
+    /// Split off the next whitespace-delimited field, skipping leading whitespace
+    fn next_field(s: &str) -> Option<(&str, &str)> {
+        let s = s.trim_start();
+        if s.is_empty() {
+            return None;
+        }
+        let end = s.find(char::is_whitespace).unwrap_or(s.len());
+        Some(s.split_at(end))
+    }
+
+    fn parse_line_break(line: &str) -> Result<LineBreak, ParseError> {
+        // Format: - StartBeat [EndBeat]
+        let rest = line[1..].trim();
+        let parts: Vec<&str> = rest.split_whitespace().collect();
+
+        if parts.is_empty() {
+            return Err(ParseError::at(
+                ParseErrorKind::MissingLineBreakBeat,
+                line,
+                rest,
+            ));
+        }
+
+        let start_beat: i32 = parts[0].parse().map_err(|_| {
+            ParseError::at(
+                ParseErrorKind::InvalidLineBreakBeat(parts[0].to_string()),
+                line,
+                parts[0],
+            )
+        })?;
+
+        let end_beat: Option<i32> = if parts.len() > 1 {
+            Some(parts[1].parse().map_err(|_| {
+                ParseError::at(
+                    ParseErrorKind::InvalidLineBreakBeat(parts[1].to_string()),
+                    line,
+                    parts[1],
+                )
+            })?)
+        } else {
+            None
+        };
+
+        Ok(LineBreak {
+            start_beat,
+            end_beat,
+        })
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = (SourceLine<'a>, Result<Token<'a>, ParseError>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        for (index, raw_line) in self.lines.by_ref() {
+            let text = raw_line.trim();
+            if text.is_empty() {
+                continue;
+            }
+
+            let line = SourceLine {
+                number: index + 1,
+                indent: raw_line.chars().take_while(|c| c.is_whitespace()).count(),
+                text,
+            };
+            let token =
+                Self::tokenize_line(text, raw_line.trim_start()).map_err(|e| line.locate(e));
+            if token == Ok(Token::End) {
+                self.finished = true;
+            }
+            return Some((line, token));
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(content: &str) -> Vec<Result<Token<'_>, ParseError>> {
+        Tokenizer::new(content).map(|(_, token)| token).collect()
+    }
+
+    #[test]
+    fn test_tokenize_lines() {
+        let tokens = tokens("#title: Song \nP 2\n: 0 5 7 Hi\n- 10 12\nwhat\nE\n: 1 1 1 ignored\n");
+
+        assert_eq!(
+            tokens,
+            vec![
+                Ok(Token::Header {
+                    tag: "TITLE".to_string(),
+                    name: "title",
+                    value: "Song"
+                }),
+                Ok(Token::Player(2)),
+                Ok(Token::Note(Note {
+                    note_type: NoteType::Normal,
+                    start_beat: 0,
+                    length: 5,
+                    pitch: 7,
+                    text: "Hi".to_string(),
+                })),
+                Ok(Token::LineBreak(LineBreak {
+                    start_beat: 10,
+                    end_beat: Some(12),
+                })),
+                Ok(Token::Unknown),
+                Ok(Token::End),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_player_numbers_are_capped() {
+        assert_eq!(tokens("P9\n"), vec![Ok(Token::Player(MAX_PLAYERS))]);
+        assert_eq!(tokens("P10\n"), vec![Ok(Token::Unknown)]);
+        assert_eq!(tokens("P 99999999999\n"), vec![Ok(Token::Unknown)]);
+    }
+
+    #[test]
+    fn test_tokenizer_continues_after_errors() {
+        let lines: Vec<_> = Tokenizer::new(": x 5 7 Bad\n  - y\n: 0 5 7 Good\n").collect();
+
+        assert_eq!(lines.len(), 3);
+        let err = lines[1].1.as_ref().unwrap_err();
+        assert_eq!(err.line, Some(2));
+        assert_eq!(err.column, Some(5));
+        assert!(lines[2].1.is_ok());
+    }
+
+    /// Note lines seen in the wild, with the syllable text players expect
+    const NOTE_TEXT_CASES: &[(&str, (i32, i32, i32), &str)] = &[
+        (": 0 5 7 Hello", (0, 5, 7), "Hello"),
+        (": 8 3 5  world", (8, 3, 5), " world"),
+        (": 8 3 5 word ", (8, 3, 5), "word "),
+        (": 4 2 3 ~", (4, 2, 3), "~"),
+        (": 4 2 3 la~", (4, 2, 3), "la~"),
+        (":\t0\t5\t7\tTabbed", (0, 5, 7), "Tabbed"),
+        (":\t0\t5\t7\t\tTab word", (0, 5, 7), "\tTab word"),
+        (":  12   4  -3 Spaced", (12, 4, -3), "Spaced"),
+        (":0 5 7 Tight", (0, 5, 7), "Tight"),
+        (": 0 5 7 two  spaces", (0, 5, 7), "two  spaces"),
+        (": 0 5 7 tab\tinside", (0, 5, 7), "tab\tinside"),
+        (": 0 5 7 ", (0, 5, 7), ""),
+        (": 0 5 7", (0, 5, 7), ""),
+        ("* 10 2 12 Ünïcödé", (10, 2, 12), "Ünïcödé"),
+        ("  : 0 5 7 Indented", (0, 5, 7), "Indented"),
+        (": 0 5 7 Windows\r", (0, 5, 7), "Windows"),
+    ];
+
+    #[test]
+    fn test_note_text_is_preserved() {
+        for (line, (start, length, pitch), text) in NOTE_TEXT_CASES {
+            let content = format!("{}\n", line);
+            let tokens = tokens(&content);
+            let Some(Ok(Token::Note(note))) = tokens.first() else {
+                panic!("{:?} did not parse as a note: {:?}", line, tokens);
+            };
+
+            assert_eq!(
+                (note.start_beat, note.length, note.pitch),
+                (*start, *length, *pitch),
+                "fields of {:?}",
+                line
+            );
+            assert_eq!(note.text, *text, "text of {:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_note_with_missing_fields() {
+        let tokens = tokens(": 0 5\n:\n");
+
+        for token in tokens {
+            assert_eq!(token.unwrap_err().kind, ParseErrorKind::MissingNoteFields);
+        }
+    }
+}
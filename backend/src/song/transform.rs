@@ -0,0 +1,461 @@
+use crate::song::types::{Note, Song, Track};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum TransformError {
+    #[error("Player {0} does not exist (the song has {1} tracks)")]
+    PlayerNotFound(usize, usize),
+
+    #[error("Note {1} of player {0} does not exist")]
+    NoteNotFound(usize, usize),
+
+    #[error("Invalid BPM factor: {0}")]
+    InvalidFactor(f64),
+
+    #[error("Cannot shift the GAP by {0}ms")]
+    InvalidGapShift(f64),
+
+    #[error("Cannot split a note of {length} beats at beat {at}")]
+    InvalidSplit { at: i32, length: i32 },
+
+    #[error("Cannot merge {0} notes")]
+    InvalidMerge(usize),
+
+    #[error("Cannot merge notes across a line break")]
+    MergeAcrossLine,
+
+    #[error("A note would start before beat 0 (at beat {0})")]
+    NegativeBeat(i32),
+
+    #[error("A beat or pitch would be out of range")]
+    OutOfRange,
+}
+
+/// An edit to a song's timing or notes
+///
+/// Players are 1-indexed like in the lyrics API; operations without a
+/// player apply to every track.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Transform {
+    /// Move `#GAP` by some milliseconds, moving all notes in time
+    ShiftGap { ms: f64 },
+    /// Move notes and line breaks by a number of beats
+    ShiftBeats {
+        beats: i32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        player: Option<usize>,
+    },
+    /// Move pitches by a number of semitones (12 for an octave)
+    Transpose {
+        semitones: i32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        player: Option<usize>,
+    },
+    /// Multiply the BPM and rescale all beats so the timing stays the same
+    RescaleBpm { factor: f64 },
+    /// Split a note in two, `at` beats after its start; the second half holds the syllable
+    SplitNote {
+        player: usize,
+        index: usize,
+        at: i32,
+    },
+    /// Merge `count` consecutive notes into the first one's pitch and type
+    MergeNotes {
+        player: usize,
+        index: usize,
+        count: usize,
+    },
+}
+
+impl Transform {
+    /// Apply operations in order; the song is left untouched if any fails
+    pub fn apply_all(song: &mut Song, transforms: &[Transform]) -> Result<(), TransformError> {
+        let mut result = song.clone();
+        for transform in transforms {
+            transform.apply_to(&mut result)?;
+        }
+        *song = result;
+        Ok(())
+    }
+
+    /// Apply one operation; the song is left untouched if it fails
+    pub fn apply(&self, song: &mut Song) -> Result<(), TransformError> {
+        Self::apply_all(song, std::slice::from_ref(self))
+    }
+
+    fn apply_to(&self, song: &mut Song) -> Result<(), TransformError> {
+        match *self {
+            Transform::ShiftGap { ms } => {
+                let gap = song.metadata.gap + ms;
+                if !gap.is_finite() {
+                    return Err(TransformError::InvalidGapShift(ms));
+                }
+                song.metadata.gap = gap;
+            }
+            Transform::ShiftBeats { beats, player } => {
+                let shift = |beat: i32| add(beat, beats);
+                for track in tracks_mut(song, player)? {
+                    for note in &mut track.notes {
+                        note.start_beat = shift(note.start_beat)?;
+                        if note.start_beat < 0 {
+                            return Err(TransformError::NegativeBeat(note.start_beat));
+                        }
+                    }
+                    for line_break in &mut track.line_breaks {
+                        line_break.start_beat = shift(line_break.start_beat)?;
+                        line_break.end_beat = line_break.end_beat.map(shift).transpose()?;
+                    }
+                }
+                if player.is_none() {
+                    let metadata = &mut song.metadata;
+                    metadata.medley_start_beat =
+                        metadata.medley_start_beat.map(shift).transpose()?;
+                    metadata.medley_end_beat = metadata.medley_end_beat.map(shift).transpose()?;
+                }
+            }
+            Transform::Transpose { semitones, player } => {
+                for track in tracks_mut(song, player)? {
+                    for note in &mut track.notes {
+                        note.pitch = add(note.pitch, semitones)?;
+                    }
+                }
+            }
+            Transform::RescaleBpm { factor } => {
+                let bpm = song.metadata.bpm * factor;
+                if !(factor > 0.0 && bpm > 0.0 && bpm.is_finite()) {
+                    return Err(TransformError::InvalidFactor(factor));
+                }
+                let scale = |beat: i32| to_beat(beat as f64 * factor);
+
+                song.metadata.bpm = bpm;
+                for track in &mut song.tracks {
+                    let mut previous = None;
+                    for note in &mut track.notes {
+                        let start = scale(note.start_beat)?;
+                        // Notes must not be squashed onto the same beat
+                        if let Some((original, scaled)) = previous {
+                            if note.start_beat != original && start == scaled {
+                                return Err(TransformError::InvalidFactor(factor));
+                            }
+                        }
+                        previous = Some((note.start_beat, start));
+
+                        let end = scale(add(note.start_beat, note.length)?)?;
+                        note.start_beat = start;
+                        note.length = end
+                            .checked_sub(start)
+                            .ok_or(TransformError::OutOfRange)?
+                            .max(1);
+                    }
+                    for line_break in &mut track.line_breaks {
+                        line_break.start_beat = scale(line_break.start_beat)?;
+                        line_break.end_beat = line_break.end_beat.map(scale).transpose()?;
+                    }
+                }
+                let metadata = &mut song.metadata;
+                metadata.medley_start_beat = metadata.medley_start_beat.map(scale).transpose()?;
+                metadata.medley_end_beat = metadata.medley_end_beat.map(scale).transpose()?;
+            }
+            Transform::SplitNote { player, index, at } => {
+                let track = track_mut(song, player)?;
+                let note = track
+                    .notes
+                    .get_mut(index)
+                    .ok_or(TransformError::NoteNotFound(player, index))?;
+                if at <= 0 || at >= note.length {
+                    return Err(TransformError::InvalidSplit {
+                        at,
+                        length: note.length,
+                    });
+                }
+
+                let second = Note {
+                    start_beat: add(note.start_beat, at)?,
+                    length: note.length - at,
+                    text: "~".to_string(),
+                    ..note.clone()
+                };
+                note.length = at;
+                track.notes.insert(index + 1, second);
+            }
+            Transform::MergeNotes {
+                player,
+                index,
+                count,
+            } => {
+                if count < 2 {
+                    return Err(TransformError::InvalidMerge(count));
+                }
+                let track = track_mut(song, player)?;
+                let last = index
+                    .checked_add(count - 1)
+                    .ok_or(TransformError::InvalidMerge(count))?;
+                if last >= track.notes.len() {
+                    return Err(TransformError::NoteNotFound(player, last));
+                }
+                let (first_beat, last_beat) =
+                    (track.notes[index].start_beat, track.notes[last].start_beat);
+                if track
+                    .line_breaks
+                    .iter()
+                    .any(|lb| lb.start_beat > first_beat && lb.start_beat <= last_beat)
+                {
+                    return Err(TransformError::MergeAcrossLine);
+                }
+
+                let merged: Vec<Note> = track.notes.drain(index + 1..=last).collect();
+                let note = &mut track.notes[index];
+                for other in merged {
+                    note.length = add(other.start_beat, other.length)? - note.start_beat;
+                    // "~" only marks a held syllable
+                    note.text.push_str(other.text.trim_start_matches('~'));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `a + b` for beats and pitches, which come straight from requests
+fn add(a: i32, b: i32) -> Result<i32, TransformError> {
+    a.checked_add(b).ok_or(TransformError::OutOfRange)
+}
+
+/// A rescaled beat, rounded, or an error if it does not fit a beat
+fn to_beat(beat: f64) -> Result<i32, TransformError> {
+    let beat = beat.round();
+    if beat >= i32::MIN as f64 && beat <= i32::MAX as f64 {
+        Ok(beat as i32)
+    } else {
+        Err(TransformError::OutOfRange)
+    }
+}
+
+/// The track of a 1-indexed player
+fn track_mut(song: &mut Song, player: usize) -> Result<&mut Track, TransformError> {
+    let count = song.tracks.len();
+    player
+        .checked_sub(1)
+        .and_then(|i| song.tracks.get_mut(i))
+        .ok_or(TransformError::PlayerNotFound(player, count))
+}
+
+/// One player's track, or all tracks
+fn tracks_mut(song: &mut Song, player: Option<usize>) -> Result<&mut [Track], TransformError> {
+    match player {
+        Some(player) => track_mut(song, player).map(std::slice::from_mut),
+        None => Ok(&mut song.tracks),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::song::fixtures;
+    use crate::song::parser::Parser;
+    use std::path::Path;
+
+    fn parse(content: &str) -> Song {
+        Parser::parse(content, Path::new("test.txt")).unwrap()
+    }
+
+    fn starts(song: &Song, track: usize) -> Vec<i32> {
+        song.tracks[track]
+            .notes
+            .iter()
+            .map(|n| n.start_beat)
+            .collect()
+    }
+
+    #[test]
+    fn test_shift_and_transpose() {
+        let mut song = parse(fixtures::DUET);
+        let transforms = [
+            Transform::ShiftBeats {
+                beats: 4,
+                player: Some(2),
+            },
+            Transform::Transpose {
+                semitones: -12,
+                player: None,
+            },
+            Transform::ShiftGap { ms: 250.0 },
+        ];
+        Transform::apply_all(&mut song, &transforms).unwrap();
+
+        assert_eq!(starts(&song, 0), vec![0, 12]);
+        assert_eq!(starts(&song, 1), vec![4, 16]);
+        assert_eq!(song.tracks[1].line_breaks[0].start_beat, 14);
+        assert_eq!(song.tracks[0].notes[0].pitch, -5);
+        assert_eq!(song.metadata.gap, 250.0);
+    }
+
+    #[test]
+    fn test_rescale_bpm_keeps_timing() {
+        let original = parse(fixtures::SIMPLE);
+        let mut song = original.clone();
+        Transform::RescaleBpm { factor: 2.0 }
+            .apply(&mut song)
+            .unwrap();
+
+        assert_eq!(song.metadata.bpm, 600.0);
+        assert_eq!(starts(&song, 0), vec![0, 16, 40]);
+        assert_eq!(song.timings().tracks, original.timings().tracks);
+    }
+
+    #[test]
+    fn test_rescale_bpm_limits() {
+        let original = parse(fixtures::SIMPLE);
+        let mut song = original.clone();
+
+        for (factor, error) in [
+            (1e306, TransformError::InvalidFactor(1e306)),
+            (1e9, TransformError::OutOfRange),
+            (1e-9, TransformError::InvalidFactor(1e-9)),
+            (0.0, TransformError::InvalidFactor(0.0)),
+        ] {
+            assert_eq!(
+                Transform::RescaleBpm { factor }.apply(&mut song),
+                Err(error)
+            );
+            assert_eq!(song, original);
+        }
+    }
+
+    #[test]
+    fn test_shift_gap_must_stay_finite() {
+        let mut song = parse(fixtures::SIMPLE);
+        let shift = Transform::ShiftGap { ms: 1e308 };
+
+        shift.apply(&mut song).unwrap();
+        assert_eq!(
+            shift.apply(&mut song),
+            Err(TransformError::InvalidGapShift(1e308))
+        );
+        assert!(matches!(
+            Transform::ShiftGap { ms: f64::NAN }.apply(&mut song),
+            Err(TransformError::InvalidGapShift(ms)) if ms.is_nan()
+        ));
+    }
+
+    #[test]
+    fn test_split_and_merge() {
+        let original = parse(fixtures::SIMPLE);
+        let mut song = original.clone();
+
+        Transform::SplitNote {
+            player: 1,
+            index: 0,
+            at: 2,
+        }
+        .apply(&mut song)
+        .unwrap();
+        let notes = &song.tracks[0].notes;
+        assert_eq!(
+            (notes[0].length, notes[1].start_beat, notes[1].length),
+            (2, 2, 3)
+        );
+        assert_eq!(notes[1].text, "~");
+
+        Transform::MergeNotes {
+            player: 1,
+            index: 0,
+            count: 2,
+        }
+        .apply(&mut song)
+        .unwrap();
+        assert_eq!(song, original);
+    }
+
+    #[test]
+    fn test_failed_transforms_leave_song_untouched() {
+        let original = parse(fixtures::SIMPLE);
+        let mut song = original.clone();
+
+        let result = Transform::apply_all(
+            &mut song,
+            &[
+                Transform::Transpose {
+                    semitones: 1,
+                    player: None,
+                },
+                Transform::MergeNotes {
+                    player: 1,
+                    index: 1,
+                    count: 2,
+                },
+            ],
+        );
+        assert_eq!(result, Err(TransformError::MergeAcrossLine));
+        assert_eq!(song, original);
+
+        assert_eq!(
+            Transform::ShiftBeats {
+                beats: -1,
+                player: None
+            }
+            .apply(&mut song),
+            Err(TransformError::NegativeBeat(-1))
+        );
+        assert_eq!(
+            Transform::Transpose {
+                semitones: 1,
+                player: Some(2)
+            }
+            .apply(&mut song),
+            Err(TransformError::PlayerNotFound(2, 1))
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_operations() {
+        let original = parse(fixtures::SIMPLE);
+        let mut song = original.clone();
+
+        let operations = [
+            Transform::ShiftBeats {
+                beats: i32::MAX,
+                player: None,
+            },
+            Transform::Transpose {
+                semitones: i32::MAX,
+                player: Some(1),
+            },
+        ];
+        for operation in operations {
+            assert_eq!(operation.apply(&mut song), Err(TransformError::OutOfRange));
+            assert_eq!(song, original);
+        }
+
+        let merge: Transform = serde_json::from_str(
+            r#"{"op":"merge_notes","player":1,"index":2,"count":18446744073709551615}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            merge.apply(&mut song),
+            Err(TransformError::InvalidMerge(usize::MAX))
+        );
+    }
+
+    #[test]
+    fn test_deserialize_operations() {
+        let transforms: Vec<Transform> = serde_json::from_str(
+            r#"[{"op": "shift_beats", "beats": -2}, {"op": "rescale_bpm", "factor": 0.5}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            transforms,
+            vec![
+                Transform::ShiftBeats {
+                    beats: -2,
+                    player: None
+                },
+                Transform::RescaleBpm { factor: 0.5 },
+            ]
+        );
+    }
+}
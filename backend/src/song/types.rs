@@ -1,23 +1,175 @@
+use crate::song::duplicates::normalize;
+use crate::song::parser::Parser;
 use serde::{Deserialize, Serialize};
+use std::fmt::Write;
 use std::path::PathBuf;
 use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SongJson", into = "SongJson")]
 pub struct Song {
+    /// See [`Song::content_id`]; copies of a song are numbered, see
+    /// [`Indexer::unique_id`](crate::song::Indexer::unique_id)
     pub id: String,
     pub metadata: SongMetadata,
-    pub notes: Vec<Note>,
+    /// Singer tracks in player order; solo songs have exactly one
+    pub tracks: Vec<Track>,
+    /// Format of the file the song was read from
+    pub source_format: SourceFormat,
+    pub files: SongFiles,
+}
+
+impl Song {
+    /// Whether more than one singer part is defined
+    pub fn is_multi_track(&self) -> bool {
+        self.tracks.len() > 1
+    }
+
+    /// Notes of all tracks
+    pub fn all_notes(&self) -> impl Iterator<Item = &Note> {
+        self.tracks.iter().flat_map(|track| &track.notes)
+    }
+
+    /// ID derived from what is sung rather than where the file is, so it
+    /// survives moving or renaming the song's folder
+    ///
+    /// Hashes the normalized artist and title and the type, timing and pitch
+    /// of every note. Lyrics, GAP and BPM are left out, so fixing typos or
+    /// sync keeps the ID.
+    pub fn content_id(&self) -> String {
+        let mut content = format!(
+            "{}\n{}\n",
+            normalize(&self.metadata.artist),
+            normalize(&self.metadata.title)
+        );
+        for track in &self.tracks {
+            content.push('P');
+            for note in &track.notes {
+                let _ = write!(
+                    content,
+                    "{}{},{},{};",
+                    note.note_type.symbol(),
+                    note.start_beat,
+                    note.length,
+                    note.pitch
+                );
+            }
+        }
+        Parser::hash_id(content.as_bytes())
+    }
+}
+
+/// The notes and line breaks sung by one player
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Track {
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub notes_p2: Option<Vec<Note>>,
+    pub singer: Option<String>,
+    pub notes: Vec<Note>,
     pub line_breaks: Vec<LineBreak>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub line_breaks_p2: Option<Vec<LineBreak>>,
-    #[serde(skip)]
-    pub files: SongFiles,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+/// JSON shape of a [`Song`]
+///
+/// Besides `tracks`, the first two tracks are mirrored into the fields
+/// clients used before songs could have more than two players.
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(as = Song)]
+struct SongJson {
+    id: String,
+    metadata: SongMetadata,
+    #[serde(default)]
+    tracks: Vec<Track>,
+    /// Notes of the first track (superseded by `tracks`)
+    #[serde(default)]
+    notes: Vec<Note>,
+    /// Notes of the second track (superseded by `tracks`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notes_p2: Option<Vec<Note>>,
+    /// Line breaks of the first track (superseded by `tracks`)
+    #[serde(default)]
+    line_breaks: Vec<LineBreak>,
+    /// Line breaks of the second track (superseded by `tracks`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    line_breaks_p2: Option<Vec<LineBreak>>,
+    #[serde(default)]
+    source_format: SourceFormat,
+}
+
+impl From<Song> for SongJson {
+    fn from(song: Song) -> Self {
+        let first = song.tracks.first().cloned().unwrap_or_default();
+        let second = song.tracks.get(1);
+
+        Self {
+            notes: first.notes,
+            notes_p2: second.map(|track| track.notes.clone()),
+            line_breaks: first.line_breaks,
+            line_breaks_p2: second.map(|track| track.line_breaks.clone()),
+            id: song.id,
+            metadata: song.metadata,
+            tracks: song.tracks,
+            source_format: song.source_format,
+        }
+    }
+}
+
+impl From<SongJson> for Song {
+    fn from(json: SongJson) -> Self {
+        let tracks = if json.tracks.is_empty() {
+            // Older payloads only carry the two-player fields
+            let mut tracks = vec![Track {
+                singer: json.metadata.duet_singer_p1.clone(),
+                notes: json.notes,
+                line_breaks: json.line_breaks,
+            }];
+            if json.notes_p2.is_some() || json.line_breaks_p2.is_some() {
+                tracks.push(Track {
+                    singer: json.metadata.duet_singer_p2.clone(),
+                    notes: json.notes_p2.unwrap_or_default(),
+                    line_breaks: json.line_breaks_p2.unwrap_or_default(),
+                });
+            }
+            tracks
+        } else {
+            json.tracks
+        };
+
+        Self {
+            id: json.id,
+            metadata: json.metadata,
+            tracks,
+            source_format: json.source_format,
+            files: SongFiles::default(),
+        }
+    }
+}
+
+impl utoipa::PartialSchema for Song {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        <SongJson as utoipa::PartialSchema>::schema()
+    }
+}
+
+impl ToSchema for Song {
+    fn name() -> std::borrow::Cow<'static, str> {
+        SongJson::name()
+    }
+
+    fn schemas(
+        schemas: &mut Vec<(
+            String,
+            utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>,
+        )>,
+    ) {
+        SongJson::schemas(schemas)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct SongMetadata {
+    /// Format version from the #VERSION tag (absent for legacy files)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
     pub title: String,
     pub artist: String,
     pub bpm: f64,
@@ -47,16 +199,138 @@ pub struct SongMetadata {
     pub cover_file: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub background_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vocals_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instrumental_file: Option<String>,
+    /// Playback start offset in seconds (#START)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<f64>,
+    /// Playback end in milliseconds (#END)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<f64>,
+    /// Preview start in seconds (#PREVIEWSTART)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview_start: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub medley_start_beat: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub medley_end_beat: Option<i32>,
+    /// Header tags in the order they appeared in the file (used by the writer)
+    #[serde(skip)]
+    pub tag_order: Vec<String>,
+    /// Header tags without a dedicated field (e.g. #AUTHOR, #TAGS) as
+    /// `[tag, value]` pairs in file order, spelled as in the file; a tag
+    /// that repeats has one pair per occurrence
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schema(example = json!([["AUTHOR", "Someone"], ["COMMENT", "First"], ["COMMENT", "Second"]]))]
+    pub extra_tags: Vec<(String, String)>,
 }
 
-#[derive(Debug, Clone, Default)]
+impl SongMetadata {
+    /// Parsed #VERSION, if present and well-formed
+    pub fn format_version(&self) -> Option<FormatVersion> {
+        self.version.as_deref().and_then(FormatVersion::parse)
+    }
+
+    /// Value of a header tag without a dedicated field, ignoring case; the
+    /// last one wins if the tag repeats
+    pub fn extra_tag(&self, tag: &str) -> Option<&str> {
+        self.extra_tags
+            .iter()
+            .rev()
+            .find(|(name, _)| name.eq_ignore_ascii_case(tag))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Singer name declared for a 1-indexed player (#P1, #DUETSINGERP1, ...)
+    ///
+    /// Players beyond the second have no dedicated field and are looked up
+    /// in `extra_tags`.
+    pub fn singer(&self, player: usize) -> Option<&str> {
+        match player {
+            1 => self.duet_singer_p1.as_deref(),
+            2 => self.duet_singer_p2.as_deref(),
+            _ => [format!("P{}", player), format!("DUETSINGERP{}", player)]
+                .iter()
+                .find_map(|tag| self.extra_tag(tag)),
+        }
+    }
+}
+
+/// UltraStar file format version as declared by the #VERSION tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FormatVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl FormatVersion {
+    /// First versioned release of the format; drops #RELATIVE and #ENCODING
+    pub const V1_0_0: FormatVersion = FormatVersion::new(1, 0, 0);
+    /// First version that defines #AUDIO, #VOCALS and #INSTRUMENTAL
+    pub const V1_1_0: FormatVersion = FormatVersion::new(1, 1, 0);
+
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parse "1.1.0"-style versions; missing minor/patch components default to 0
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.trim().split('.');
+        let major = parts.next()?.trim().parse().ok()?;
+        let minor = match parts.next() {
+            Some(p) => p.trim().parse().ok()?,
+            None => 0,
+        };
+        let patch = match parts.next() {
+            Some(p) => p.trim().parse().ok()?,
+            None => 0,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self::new(major, minor, patch))
+    }
+}
+
+impl std::fmt::Display for FormatVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// File formats songs are read from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceFormat {
+    /// UltraStar TXT
+    #[default]
+    UltraStar,
+    /// SingStar MelodyXML
+    SingStar,
+    /// Performous `notes.xml` (MelodyXML as written by its SingStar extractor)
+    Performous,
+    /// Standard MIDI or karaoke file
+    Midi,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct SongFiles {
+    /// Song file the song was read from (TXT or XML)
     #[allow(dead_code)]
     pub txt_path: PathBuf,
     pub audio_path: Option<PathBuf>,
     pub video_path: Option<PathBuf>,
     pub cover_path: Option<PathBuf>,
     pub background_path: Option<PathBuf>,
+    pub vocals_path: Option<PathBuf>,
+    pub instrumental_path: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
@@ -69,7 +343,32 @@ pub enum NoteType {
     GoldenRap,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+impl NoteType {
+    /// Note type for the leading character of a note line
+    pub fn from_symbol(symbol: char) -> Option<Self> {
+        match symbol {
+            ':' => Some(NoteType::Normal),
+            '*' => Some(NoteType::Golden),
+            'F' => Some(NoteType::Freestyle),
+            'R' => Some(NoteType::Rap),
+            'G' => Some(NoteType::GoldenRap),
+            _ => None,
+        }
+    }
+
+    /// Leading character used for this note type in TXT files
+    pub fn symbol(self) -> char {
+        match self {
+            NoteType::Normal => ':',
+            NoteType::Golden => '*',
+            NoteType::Freestyle => 'F',
+            NoteType::Rap => 'R',
+            NoteType::GoldenRap => 'G',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct Note {
     pub note_type: NoteType,
     pub start_beat: i32,
@@ -78,7 +377,7 @@ pub struct Note {
     pub text: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct LineBreak {
     pub start_beat: i32,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -86,7 +385,7 @@ pub struct LineBreak {
 }
 
 /// Summary of a song for listing (without full note data)
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct SongSummary {
     pub id: String,
     pub title: String,
@@ -98,6 +397,9 @@ pub struct SongSummary {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub language: Option<String>,
     pub has_video: bool,
+    /// Number of singer tracks
+    pub track_count: usize,
+    /// Same as `track_count > 1`, kept for older clients
     pub is_duet: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cover_url: Option<String>,
@@ -113,7 +415,8 @@ impl From<&Song> for SongSummary {
             year: song.metadata.year,
             language: song.metadata.language.clone(),
             has_video: song.files.video_path.is_some(),
-            is_duet: song.notes_p2.is_some(),
+            track_count: song.tracks.len(),
+            is_duet: song.is_multi_track(),
             cover_url: song
                 .files
                 .cover_path
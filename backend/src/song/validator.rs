@@ -1,8 +1,18 @@
+use crate::song::encoding;
+use crate::song::media::{MediaError, MediaProbe};
+use crate::song::parser::{ParseError, ParseErrorKind, Parser, TrackLines};
+use crate::song::tokenizer::{Token, Tokenizer};
+use crate::song::types::{Note, NoteType, Song};
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
 use std::path::Path;
 
 /// Represents a validation error with context about where it occurred
-#[derive(Debug, Clone)]
+///
+/// Serializes as `{rule, kind, message, line, context}`.
+#[derive(Debug, Clone, Serialize)]
 pub struct ValidationError {
+    #[serde(flatten)]
     pub kind: ValidationErrorKind,
     pub line: Option<usize>,
     pub context: Option<String>,
@@ -17,7 +27,60 @@ impl std::fmt::Display for ValidationError {
     }
 }
 
-#[derive(Debug, Clone)]
+impl From<&ParseError> for ValidationError {
+    fn from(error: &ParseError) -> Self {
+        let kind = match &error.kind {
+            ParseErrorKind::MissingTag("TITLE") => ValidationErrorKind::MissingTitle,
+            ParseErrorKind::MissingTag("ARTIST") => ValidationErrorKind::MissingArtist,
+            ParseErrorKind::EmptyTag(tag) => ValidationErrorKind::EmptyTag(tag.to_string()),
+            ParseErrorKind::MissingTag("BPM") => ValidationErrorKind::MissingBpm,
+            ParseErrorKind::InvalidHeaderValue { tag, value } if tag == "BPM" => {
+                ValidationErrorKind::InvalidBpm(value.clone())
+            }
+            ParseErrorKind::InvalidHeaderValue { tag, value } if tag == "GAP" => {
+                ValidationErrorKind::InvalidGap(value.clone())
+            }
+            ParseErrorKind::IgnoredHeaderValue { tag, value } if tag == "YEAR" => {
+                ValidationErrorKind::InvalidYear(value.clone())
+            }
+            ParseErrorKind::MissingTag(_)
+            | ParseErrorKind::InvalidHeaderValue { .. }
+            | ParseErrorKind::IgnoredHeaderValue { .. }
+            | ParseErrorKind::UnsupportedVersion(_)
+            | ParseErrorKind::RelativeNotSupported(_) => {
+                ValidationErrorKind::InvalidHeader(error.kind.to_string())
+            }
+            ParseErrorKind::UnknownNoteType(symbol) => {
+                ValidationErrorKind::InvalidNoteType(symbol.to_string())
+            }
+            ParseErrorKind::MissingNoteFields
+            | ParseErrorKind::InvalidStartBeat(_)
+            | ParseErrorKind::InvalidLength(_)
+            | ParseErrorKind::InvalidPitch(_) => {
+                ValidationErrorKind::InvalidNoteFormat(error.kind.to_string())
+            }
+            ParseErrorKind::UnknownLine => ValidationErrorKind::InvalidNoteFormat(format!(
+                "Unknown line type: {}",
+                error.snippet.as_deref().unwrap_or_default()
+            )),
+            ParseErrorKind::MissingLineBreakBeat
+            | ParseErrorKind::InvalidLineBreakBeat(_)
+            | ParseErrorKind::MissingBeatShift
+            | ParseErrorKind::BeatOutOfRange(_) => {
+                ValidationErrorKind::InvalidLineBreak(error.kind.to_string())
+            }
+            ParseErrorKind::MissingEndMarker => ValidationErrorKind::NoEndMarker,
+        };
+
+        ValidationError {
+            kind,
+            line: error.line,
+            context: error.snippet.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum ValidationErrorKind {
     // Encoding issues
     InvalidUtf8,
@@ -28,11 +91,13 @@ pub enum ValidationErrorKind {
     MissingArtist,
     MissingBpm,
     MissingAudio,
+    EmptyTag(String),
 
     // Invalid field values
     InvalidBpm(String),
     InvalidGap(String),
     InvalidYear(String),
+    InvalidHeader(String),
 
     // Note parsing errors
     InvalidNoteType(String),
@@ -54,20 +119,183 @@ pub enum ValidationErrorKind {
     NoNotes,
     NoEndMarker,
     EmptyFile,
+
+    // Note semantics
+    NonPositiveLength(i32),
+    UnsortedNotes {
+        start: i32,
+        previous_start: i32,
+    },
+    OverlappingNotes {
+        start: i32,
+        previous_end: i32,
+    },
+    LineBreakInsideNote {
+        beat: i32,
+        note_start: i32,
+        note_end: i32,
+    },
+    EmptyLine,
+    PitchJump {
+        from: i32,
+        to: i32,
+    },
+    PitchRange {
+        low: i32,
+        high: i32,
+    },
+    ImplausibleBpm(f64),
+
+    // Duet structure
+    EmptyTrack(usize),
+    UnbalancedTracks {
+        player: usize,
+        notes: usize,
+        max: usize,
+    },
+
+    // Media content
+    UndecodableMedia(String),
+    UnplayableVideo(String),
+    NotesExceedAudio {
+        last_note_ms: f64,
+        audio_ms: f64,
+    },
+    GapBeyondAudio {
+        gap_ms: f64,
+        audio_ms: f64,
+    },
+}
+
+impl ValidationErrorKind {
+    /// Every rule ID, in order
+    pub const RULE_IDS: &'static [&'static str] = &[
+        "FV001", "FV002", "FV003", "FV004", "FV005", "FV006", "FV007", "FV008", "FV009", "FV010",
+        "FV011", "FV012", "FV013", "FV014", "FV015", "FV016", "FV017", "FV018", "FV019", "FV020",
+        "FV021", "FV022", "FV023", "FV024", "FV025", "FV026", "FV027", "FV028", "FV029", "FV030",
+        "FV031", "FV032", "FV033", "FV034", "FV035", "FV036", "FV037", "FV038",
+    ];
+
+    /// Rules reporting what also stops the server from loading a song; they
+    /// can be neither disabled nor downgraded, so a file passing validation
+    /// always loads
+    pub const PARSE_ERROR_RULE_IDS: &'static [&'static str] = &[
+        "FV003", "FV004", "FV005", "FV007", "FV008", "FV010", "FV011", "FV012", "FV013", "FV023",
+    ];
+
+    /// Stable rule ID used in reports, `frank-validate.toml` and suppressions
+    pub fn rule_id(&self) -> &'static str {
+        match self {
+            Self::InvalidUtf8 => "FV001",
+            Self::ContainsBom => "FV002",
+            Self::MissingTitle => "FV003",
+            Self::MissingArtist => "FV004",
+            Self::MissingBpm => "FV005",
+            Self::MissingAudio => "FV006",
+            Self::InvalidBpm(_) => "FV007",
+            Self::InvalidGap(_) => "FV008",
+            Self::InvalidYear(_) => "FV009",
+            Self::InvalidHeader(_) => "FV010",
+            Self::InvalidNoteType(_) => "FV011",
+            Self::InvalidNoteFormat(_) => "FV012",
+            Self::InvalidLineBreak(_) => "FV013",
+            Self::AudioFileNotFound(_) => "FV014",
+            Self::VideoFileNotFound(_) => "FV015",
+            Self::CoverFileNotFound(_) => "FV016",
+            Self::BackgroundFileNotFound(_) => "FV017",
+            Self::UnsupportedAudioFormat(_) => "FV018",
+            Self::UnsupportedVideoFormat(_) => "FV019",
+            Self::UnsupportedImageFormat(_) => "FV020",
+            Self::NoNotes => "FV021",
+            Self::NoEndMarker => "FV022",
+            Self::EmptyFile => "FV023",
+            Self::NonPositiveLength(_) => "FV024",
+            Self::UnsortedNotes { .. } => "FV025",
+            Self::OverlappingNotes { .. } => "FV026",
+            Self::LineBreakInsideNote { .. } => "FV027",
+            Self::EmptyLine => "FV028",
+            Self::PitchJump { .. } => "FV029",
+            Self::PitchRange { .. } => "FV030",
+            Self::ImplausibleBpm(_) => "FV031",
+            Self::EmptyTrack(_) => "FV032",
+            Self::UnbalancedTracks { .. } => "FV033",
+            Self::UndecodableMedia(_) => "FV034",
+            Self::UnplayableVideo(_) => "FV035",
+            Self::NotesExceedAudio { .. } => "FV036",
+            Self::GapBeyondAudio { .. } => "FV037",
+            Self::EmptyTag(_) => "FV038",
+        }
+    }
+
+    /// Stable machine-readable name of the rule
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidUtf8 => "invalid_utf8",
+            Self::ContainsBom => "contains_bom",
+            Self::MissingTitle => "missing_title",
+            Self::MissingArtist => "missing_artist",
+            Self::MissingBpm => "missing_bpm",
+            Self::MissingAudio => "missing_audio",
+            Self::InvalidBpm(_) => "invalid_bpm",
+            Self::InvalidGap(_) => "invalid_gap",
+            Self::InvalidYear(_) => "invalid_year",
+            Self::InvalidHeader(_) => "invalid_header",
+            Self::InvalidNoteType(_) => "invalid_note_type",
+            Self::InvalidNoteFormat(_) => "invalid_note_format",
+            Self::InvalidLineBreak(_) => "invalid_line_break",
+            Self::AudioFileNotFound(_) => "audio_file_not_found",
+            Self::VideoFileNotFound(_) => "video_file_not_found",
+            Self::CoverFileNotFound(_) => "cover_file_not_found",
+            Self::BackgroundFileNotFound(_) => "background_file_not_found",
+            Self::UnsupportedAudioFormat(_) => "unsupported_audio_format",
+            Self::UnsupportedVideoFormat(_) => "unsupported_video_format",
+            Self::UnsupportedImageFormat(_) => "unsupported_image_format",
+            Self::NoNotes => "no_notes",
+            Self::NoEndMarker => "no_end_marker",
+            Self::EmptyFile => "empty_file",
+            Self::NonPositiveLength(_) => "non_positive_length",
+            Self::UnsortedNotes { .. } => "unsorted_notes",
+            Self::OverlappingNotes { .. } => "overlapping_notes",
+            Self::LineBreakInsideNote { .. } => "line_break_inside_note",
+            Self::EmptyLine => "empty_line",
+            Self::PitchJump { .. } => "pitch_jump",
+            Self::PitchRange { .. } => "pitch_range",
+            Self::ImplausibleBpm(_) => "implausible_bpm",
+            Self::EmptyTrack(_) => "empty_track",
+            Self::UnbalancedTracks { .. } => "unbalanced_tracks",
+            Self::UndecodableMedia(_) => "undecodable_media",
+            Self::UnplayableVideo(_) => "unplayable_video",
+            Self::NotesExceedAudio { .. } => "notes_exceed_audio",
+            Self::GapBeyondAudio { .. } => "gap_beyond_audio",
+            Self::EmptyTag(_) => "empty_tag",
+        }
+    }
+}
+
+impl Serialize for ValidationErrorKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry("rule", self.rule_id())?;
+        map.serialize_entry("kind", self.code())?;
+        map.serialize_entry("message", &self.to_string())?;
+        map.end()
+    }
 }
 
 impl std::fmt::Display for ValidationErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::InvalidUtf8 => write!(f, "File is not valid UTF-8"),
-            Self::ContainsBom => write!(f, "File contains UTF-8 BOM (should be UTF-8 without BOM)"),
+            Self::ContainsBom => write!(f, "File contains a BOM (should be UTF-8 without BOM)"),
             Self::MissingTitle => write!(f, "Missing required #TITLE tag"),
             Self::MissingArtist => write!(f, "Missing required #ARTIST tag"),
             Self::MissingBpm => write!(f, "Missing required #BPM tag"),
             Self::MissingAudio => write!(f, "Missing required #AUDIO or #MP3 tag"),
+            Self::EmptyTag(tag) => write!(f, "Required #{} tag is empty", tag),
             Self::InvalidBpm(v) => write!(f, "Invalid BPM value: {}", v),
             Self::InvalidGap(v) => write!(f, "Invalid GAP value: {}", v),
             Self::InvalidYear(v) => write!(f, "Invalid YEAR value: {}", v),
+            Self::InvalidHeader(v) => write!(f, "Invalid header: {}", v),
             Self::InvalidNoteType(v) => write!(f, "Invalid note type: {}", v),
             Self::InvalidNoteFormat(v) => write!(f, "Invalid note format: {}", v),
             Self::InvalidLineBreak(v) => write!(f, "Invalid line break format: {}", v),
@@ -81,12 +309,77 @@ impl std::fmt::Display for ValidationErrorKind {
             Self::NoNotes => write!(f, "Song contains no notes"),
             Self::NoEndMarker => write!(f, "Missing 'E' end marker"),
             Self::EmptyFile => write!(f, "File is empty"),
+            Self::NonPositiveLength(v) => write!(f, "Note length must be positive, got {}", v),
+            Self::UnsortedNotes {
+                start,
+                previous_start,
+            } => write!(
+                f,
+                "Note at beat {} comes after a note at beat {}",
+                start, previous_start
+            ),
+            Self::OverlappingNotes {
+                start,
+                previous_end,
+            } => write!(
+                f,
+                "Note at beat {} starts before the previous note ends (beat {})",
+                start, previous_end
+            ),
+            Self::LineBreakInsideNote {
+                beat,
+                note_start,
+                note_end,
+            } => write!(
+                f,
+                "Line break at beat {} falls inside the note from beat {} to {}",
+                beat, note_start, note_end
+            ),
+            Self::EmptyLine => write!(f, "Line contains no notes"),
+            Self::PitchJump { from, to } => write!(
+                f,
+                "Pitch jumps {} semitones (from {} to {})",
+                (to - from).abs(),
+                from,
+                to
+            ),
+            Self::PitchRange { low, high } => write!(
+                f,
+                "Pitch range of {} semitones ({} to {}) is implausible for one singer",
+                high - low,
+                low,
+                high
+            ),
+            Self::ImplausibleBpm(v) => write!(f, "Implausible BPM value: {}", v),
+            Self::EmptyTrack(player) => write!(f, "Duet track P{} has no notes", player),
+            Self::UnbalancedTracks { player, notes, max } => write!(
+                f,
+                "Duet track P{} has only {} notes while another has {}",
+                player, notes, max
+            ),
+            Self::UndecodableMedia(file) => write!(f, "Media file cannot be decoded: {}", file),
+            Self::UnplayableVideo(file) => {
+                write!(f, "Video format cannot be played in browsers: {}", file)
+            }
+            Self::NotesExceedAudio {
+                last_note_ms,
+                audio_ms,
+            } => write!(
+                f,
+                "Last note ends at {:.0}ms, after the audio ends at {:.0}ms",
+                last_note_ms, audio_ms
+            ),
+            Self::GapBeyondAudio { gap_ms, audio_ms } => write!(
+                f,
+                "GAP of {:.0}ms lies beyond the audio length of {:.0}ms",
+                gap_ms, audio_ms
+            ),
         }
     }
 }
 
 /// Result of validating a song file
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ValidationResult {
     pub path: std::path::PathBuf,
     pub errors: Vec<ValidationError>,
@@ -111,12 +404,45 @@ const VIDEO_EXTENSIONS: &[&str] = &["mp4", "avi", "mkv", "webm", "mov"];
 /// Supported image formats
 const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp"];
 
+/// Plausible #BPM values; UltraStar BPM counts quarter beats, so most songs
+/// use 100 to 600
+const MIN_BPM: f64 = 30.0;
+const MAX_BPM: f64 = 1500.0;
+
+/// Semitones between neighbouring notes of a line beyond which a jump is likely a typo
+const MAX_PITCH_JUMP: u32 = 24;
+
+/// Semitones between a track's lowest and highest note beyond which no one can sing it
+const MAX_PITCH_RANGE: u32 = 36;
+
+/// A duet track with less than this share of the busiest track's notes is unbalanced
+const MIN_TRACK_SHARE: f64 = 0.2;
+
+/// `#COMMENT` prefix that disables the listed rules for one file, e.g.
+/// `#COMMENT:frank-validate-disable FV025 FV029`
+pub const SUPPRESSION_PREFIX: &str = "frank-validate-disable";
+
+/// Video containers browsers cannot play
+const UNPLAYABLE_VIDEO_EXTENSIONS: &[&str] = &["avi"];
+
+/// Options for [`Validator::validate_with`]
+#[derive(Debug, Clone, Default)]
+pub struct ValidationOptions {
+    /// Read audio and video files to check that they decode and cover the
+    /// notes; much slower than the other checks
+    pub media: bool,
+}
+
 /// Validates an UltraStar TXT file comprehensively
 pub struct Validator;
 
 impl Validator {
     /// Validate a song file and return all errors and warnings
     pub fn validate(txt_path: &Path) -> ValidationResult {
+        Self::validate_with(txt_path, &ValidationOptions::default())
+    }
+
+    pub fn validate_with(txt_path: &Path, options: &ValidationOptions) -> ValidationResult {
         let mut errors = Vec::new();
         let mut warnings = Vec::new();
 
@@ -137,31 +463,24 @@ impl Validator {
             }
         };
 
-        // Check for BOM
-        if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        // Decode the same way the indexer does
+        let decoded = encoding::decode(&bytes);
+        if decoded.had_bom {
             warnings.push(ValidationError {
                 kind: ValidationErrorKind::ContainsBom,
                 line: None,
-                context: None,
+                context: (!decoded.is_utf8()).then(|| decoded.encoding.name().to_string()),
             });
         }
-
-        // Try to decode as UTF-8
-        let content = match String::from_utf8(bytes.clone()) {
-            Ok(s) => s,
-            Err(_) => {
-                errors.push(ValidationError {
-                    kind: ValidationErrorKind::InvalidUtf8,
-                    line: None,
-                    context: Some("File is not valid UTF-8 encoding".to_string()),
-                });
-                return ValidationResult {
-                    path: txt_path.to_path_buf(),
-                    errors,
-                    warnings,
-                };
-            }
-        };
+        if !decoded.is_utf8() || decoded.had_errors {
+            // Still loads, but the format requires UTF-8 since 1.0.0
+            warnings.push(ValidationError {
+                kind: ValidationErrorKind::InvalidUtf8,
+                line: None,
+                context: Some(format!("Decoded as {}", decoded.encoding.name())),
+            });
+        }
+        let content = decoded.text;
 
         // Check for empty file
         let trimmed = content.trim();
@@ -181,148 +500,23 @@ impl Validator {
         // Get parent directory for file checks
         let dir = txt_path.parent();
 
-        // Parse and validate content
-        let mut has_title = false;
-        let mut has_artist = false;
-        let mut has_bpm = false;
-        let mut audio_file: Option<String> = None;
-        let mut video_file: Option<String> = None;
-        let mut cover_file: Option<String> = None;
-        let mut background_file: Option<String> = None;
-        let mut has_notes = false;
-        let mut has_end_marker = false;
-
-        for (line_num, line) in content.lines().enumerate() {
-            let line_num = line_num + 1; // 1-indexed
-            let line = line.trim();
-
-            if line.is_empty() {
-                continue;
-            }
-
-            if let Some(line_content) = line.strip_prefix('#') {
-                // Header line
-                let (tag, value) = line_content.split_once(':').unwrap_or((line_content, ""));
-                let tag = tag.trim().to_uppercase();
-                let value = value.trim();
-
-                match tag.as_str() {
-                    "TITLE" => {
-                        has_title = true;
-                        if value.is_empty() {
-                            errors.push(ValidationError {
-                                kind: ValidationErrorKind::MissingTitle,
-                                line: Some(line_num),
-                                context: Some("TITLE tag is empty".to_string()),
-                            });
-                        }
-                    }
-                    "ARTIST" => {
-                        has_artist = true;
-                        if value.is_empty() {
-                            errors.push(ValidationError {
-                                kind: ValidationErrorKind::MissingArtist,
-                                line: Some(line_num),
-                                context: Some("ARTIST tag is empty".to_string()),
-                            });
-                        }
-                    }
-                    "BPM" => {
-                        has_bpm = true;
-                        let bpm_str = value.replace(',', ".");
-                        if bpm_str.parse::<f64>().is_err() {
-                            errors.push(ValidationError {
-                                kind: ValidationErrorKind::InvalidBpm(value.to_string()),
-                                line: Some(line_num),
-                                context: None,
-                            });
-                        }
-                    }
-                    "GAP" => {
-                        let gap_str = value.replace(',', ".");
-                        if gap_str.parse::<f64>().is_err() && !value.is_empty() {
-                            errors.push(ValidationError {
-                                kind: ValidationErrorKind::InvalidGap(value.to_string()),
-                                line: Some(line_num),
-                                context: None,
-                            });
-                        }
-                    }
-                    "YEAR" => {
-                        if !value.is_empty() && value.parse::<u16>().is_err() {
-                            warnings.push(ValidationError {
-                                kind: ValidationErrorKind::InvalidYear(value.to_string()),
-                                line: Some(line_num),
-                                context: None,
-                            });
-                        }
-                    }
-                    "MP3" | "AUDIO" => {
-                        audio_file = Some(value.to_string());
-                    }
-                    "VIDEO" => {
-                        video_file = Some(value.to_string());
-                    }
-                    "COVER" => {
-                        cover_file = Some(value.to_string());
-                    }
-                    "BACKGROUND" => {
-                        background_file = Some(value.to_string());
-                    }
-                    _ => {} // Unknown tags are OK
-                }
-            } else if line.starts_with(':')
-                || line.starts_with('*')
-                || line.starts_with('F')
-                || line.starts_with('R')
-                || line.starts_with('G')
-            {
-                // Note line
-                has_notes = true;
-                Self::validate_note_line(line, line_num, &mut errors);
-            } else if line.starts_with('-') {
-                // Line break
-                Self::validate_line_break(line, line_num, &mut errors);
-            } else if line == "E" {
-                has_end_marker = true;
-            } else if line.starts_with('P') {
-                // Player marker (P1, P2, P 1, P 2) - valid
-            } else if !line.is_empty() {
-                // Unknown line type
-                warnings.push(ValidationError {
-                    kind: ValidationErrorKind::InvalidNoteFormat(format!(
-                        "Unknown line type: {}",
-                        &line[..line.len().min(50)]
-                    )),
-                    line: Some(line_num),
-                    context: None,
-                });
+        // Parse leniently with the same parser the server uses, so every error
+        // reported here is exactly what would stop the song from loading
+        let output = Parser::parse_lenient(&content, txt_path);
+        errors.extend(output.errors.iter().map(ValidationError::from));
+        for warning in &output.warnings {
+            let diagnostic = ValidationError::from(warning);
+            match warning.kind {
+                // Players load these, but a song without title or artist is unusable
+                ParseErrorKind::EmptyTag(_) => errors.push(diagnostic),
+                _ => warnings.push(diagnostic),
             }
         }
 
-        // Check for missing mandatory fields
-        if !has_title {
-            errors.push(ValidationError {
-                kind: ValidationErrorKind::MissingTitle,
-                line: None,
-                context: None,
-            });
-        }
-        if !has_artist {
-            errors.push(ValidationError {
-                kind: ValidationErrorKind::MissingArtist,
-                line: None,
-                context: None,
-            });
-        }
-        if !has_bpm {
-            errors.push(ValidationError {
-                kind: ValidationErrorKind::MissingBpm,
-                line: None,
-                context: None,
-            });
-        }
-        if audio_file.is_none() {
+        let song = output.song;
+        let metadata = &song.metadata;
+
+        if metadata.audio_file.is_none() {
             errors.push(ValidationError {
                 kind: ValidationErrorKind::MissingAudio,
                 line: None,
@@ -330,38 +524,52 @@ impl Validator {
             });
         }
 
-        // Check for notes and end marker
-        if !has_notes {
+        // Check for notes
+        if song.all_notes().next().is_none() {
             errors.push(ValidationError {
                 kind: ValidationErrorKind::NoNotes,
                 line: None,
                 context: None,
             });
         }
-        if !has_end_marker {
-            warnings.push(ValidationError {
-                kind: ValidationErrorKind::NoEndMarker,
-                line: None,
-                context: None,
-            });
-        }
+
+        Self::check_song(
+            &song,
+            &output.source_lines,
+            &content,
+            &mut errors,
+            &mut warnings,
+        );
 
         // Validate file references
         if let Some(dir) = dir {
-            if let Some(ref audio) = audio_file {
+            if let Some(ref audio) = metadata.audio_file {
                 Self::validate_audio_file(dir, audio, &mut errors);
             }
-            if let Some(ref video) = video_file {
+            for stem in [&metadata.vocals_file, &metadata.instrumental_file]
+                .into_iter()
+                .flatten()
+            {
+                Self::validate_audio_file(dir, stem, &mut errors);
+            }
+            if let Some(ref video) = metadata.video_file {
                 Self::validate_video_file(dir, video, &mut errors);
             }
-            if let Some(ref cover) = cover_file {
+            if let Some(ref cover) = metadata.cover_file {
                 Self::validate_image_file(dir, cover, "cover", &mut errors);
             }
-            if let Some(ref background) = background_file {
+            if let Some(ref background) = metadata.background_file {
                 Self::validate_image_file(dir, background, "background", &mut errors);
             }
+            if options.media {
+                Self::check_media(dir, &song, &mut errors, &mut warnings);
+            }
         }
 
+        let suppressed = Self::suppressed_rules(&content);
+        errors.retain(|e| !suppressed.contains(&e.kind.rule_id()));
+        warnings.retain(|w| !suppressed.contains(&w.kind.rule_id()));
+
         ValidationResult {
             path: txt_path.to_path_buf(),
             errors,
@@ -369,93 +577,257 @@ impl Validator {
         }
     }
 
-    fn validate_note_line(line: &str, line_num: usize, errors: &mut Vec<ValidationError>) {
-        let rest = line[1..].trim();
-        let parts: Vec<&str> = rest.splitn(4, ' ').collect();
+    /// Rule IDs disabled by `#COMMENT:frank-validate-disable ...` lines
+    fn suppressed_rules(content: &str) -> Vec<&str> {
+        Tokenizer::new(content)
+            .filter_map(|(_, token)| match token {
+                Ok(Token::Header { tag, value, .. }) if tag == "COMMENT" => {
+                    value.strip_prefix(SUPPRESSION_PREFIX)
+                }
+                _ => None,
+            })
+            .flat_map(|ids| ids.split([' ', ',']).filter(|id| !id.is_empty()))
+            .filter(|id| !ValidationErrorKind::PARSE_ERROR_RULE_IDS.contains(id))
+            .collect()
+    }
 
-        if parts.len() < 4 {
-            errors.push(ValidationError {
-                kind: ValidationErrorKind::InvalidNoteFormat(format!(
-                    "Note needs 4 parts (start, length, pitch, text), got {}",
-                    parts.len()
-                )),
-                line: Some(line_num),
-                context: Some(line.to_string()),
-            });
-            return;
+    /// Checks on the song as a whole that syntax alone cannot catch
+    fn check_song(
+        song: &Song,
+        source_lines: &[TrackLines],
+        content: &str,
+        errors: &mut Vec<ValidationError>,
+        warnings: &mut Vec<ValidationError>,
+    ) {
+        let mut bpm_line = None;
+        let mut player_lines = Vec::new();
+        for (line, token) in Tokenizer::new(content) {
+            match token {
+                Ok(Token::Header { tag, .. }) if tag == "BPM" => bpm_line = Some(line.number),
+                Ok(Token::Player(player)) => player_lines.push((line.number, player)),
+                _ => {}
+            }
         }
+        let issue = |kind, line| ValidationError {
+            kind,
+            line,
+            context: None,
+        };
 
-        // Validate start beat
-        if parts[0].parse::<i32>().is_err() {
-            errors.push(ValidationError {
-                kind: ValidationErrorKind::InvalidNoteFormat(format!(
-                    "Invalid start beat: {}",
-                    parts[0]
-                )),
-                line: Some(line_num),
-                context: None,
-            });
+        let bpm_reported = errors.iter().any(|e| {
+            matches!(
+                e.kind,
+                ValidationErrorKind::MissingBpm | ValidationErrorKind::InvalidBpm(_)
+            )
+        });
+        let bpm = song.metadata.bpm;
+        if !bpm_reported && !(MIN_BPM..=MAX_BPM).contains(&bpm) {
+            warnings.push(issue(ValidationErrorKind::ImplausibleBpm(bpm), bpm_line));
         }
 
-        // Validate length
-        if parts[1].parse::<i32>().is_err() {
-            errors.push(ValidationError {
-                kind: ValidationErrorKind::InvalidNoteFormat(format!(
-                    "Invalid note length: {}",
-                    parts[1]
-                )),
-                line: Some(line_num),
-                context: None,
-            });
+        for (track, lines) in song.tracks.iter().zip(source_lines) {
+            let notes: Vec<_> = track.notes.iter().zip(&lines.notes).collect();
+
+            for (note, &line) in &notes {
+                if note.length <= 0 {
+                    errors.push(issue(
+                        ValidationErrorKind::NonPositiveLength(note.length),
+                        Some(line),
+                    ));
+                }
+            }
+
+            // Notes of different P sections may interleave (e.g. P3 in duets),
+            // so only neighbours from the same section must be in order
+            for pair in notes.windows(2) {
+                let ((previous, previous_line), (note, line)) = (pair[0], pair[1]);
+                let same_section = !player_lines
+                    .iter()
+                    .any(|(player_line, _)| previous_line < player_line && player_line < line);
+                if same_section && note.start_beat < previous.start_beat {
+                    warnings.push(issue(
+                        ValidationErrorKind::UnsortedNotes {
+                            start: note.start_beat,
+                            previous_start: previous.start_beat,
+                        },
+                        Some(*line),
+                    ));
+                }
+            }
+
+            let mut sorted = notes.clone();
+            sorted.sort_by_key(|(note, _)| note.start_beat);
+            for pair in sorted.windows(2) {
+                let ((previous, _), (note, line)) = (pair[0], pair[1]);
+                let previous_end = previous.start_beat.saturating_add(previous.length);
+                if note.start_beat < previous_end {
+                    warnings.push(issue(
+                        ValidationErrorKind::OverlappingNotes {
+                            start: note.start_beat,
+                            previous_end,
+                        },
+                        Some(*line),
+                    ));
+                }
+            }
+
+            for (line_break, &line) in track.line_breaks.iter().zip(&lines.line_breaks) {
+                let beat = line_break.start_beat;
+                if let Some((note, _)) = sorted.iter().find(|(note, _)| {
+                    note.start_beat < beat && beat < note.start_beat.saturating_add(note.length)
+                }) {
+                    warnings.push(issue(
+                        ValidationErrorKind::LineBreakInsideNote {
+                            beat,
+                            note_start: note.start_beat,
+                            note_end: note.start_beat.saturating_add(note.length),
+                        },
+                        Some(line),
+                    ));
+                }
+            }
+
+            // Walk notes and line breaks in file order to see the lines as written
+            let mut events: Vec<(usize, Option<&Note>)> = notes
+                .iter()
+                .map(|(note, &line)| (line, Some(*note)))
+                .chain(lines.line_breaks.iter().map(|&line| (line, None)))
+                .collect();
+            events.sort_by_key(|(line, _)| *line);
+            let mut previous_pitch: Option<i32> = None;
+            for (i, (line, note)) in events.iter().enumerate() {
+                match note {
+                    None => {
+                        // The line before the first break or the one it starts is empty
+                        let next_is_note = events.get(i + 1).is_some_and(|(_, n)| n.is_some());
+                        if i == 0 || !next_is_note {
+                            warnings.push(issue(ValidationErrorKind::EmptyLine, Some(*line)));
+                        }
+                        previous_pitch = None;
+                    }
+                    Some(note) => {
+                        if !is_pitched(note.note_type) {
+                            continue;
+                        }
+                        if let Some(from) = previous_pitch {
+                            if note.pitch.abs_diff(from) > MAX_PITCH_JUMP {
+                                warnings.push(issue(
+                                    ValidationErrorKind::PitchJump {
+                                        from,
+                                        to: note.pitch,
+                                    },
+                                    Some(*line),
+                                ));
+                            }
+                        }
+                        previous_pitch = Some(note.pitch);
+                    }
+                }
+            }
+
+            let pitched = notes.iter().filter(|(note, _)| is_pitched(note.note_type));
+            if let (Some((low, _)), Some((high, high_line))) = (
+                pitched.clone().min_by_key(|(note, _)| note.pitch),
+                pitched.max_by_key(|(note, _)| note.pitch),
+            ) {
+                if high.pitch.abs_diff(low.pitch) > MAX_PITCH_RANGE {
+                    warnings.push(issue(
+                        ValidationErrorKind::PitchRange {
+                            low: low.pitch,
+                            high: high.pitch,
+                        },
+                        Some(**high_line),
+                    ));
+                }
+            }
         }
 
-        // Validate pitch
-        if parts[2].parse::<i32>().is_err() {
-            errors.push(ValidationError {
-                kind: ValidationErrorKind::InvalidNoteFormat(format!(
-                    "Invalid pitch: {}",
-                    parts[2]
-                )),
-                line: Some(line_num),
-                context: None,
-            });
+        if song.is_multi_track() {
+            let max = song.tracks.iter().map(|t| t.notes.len()).max().unwrap_or(0);
+            for (i, track) in song.tracks.iter().enumerate() {
+                let player = i + 1;
+                let marker_line = player_lines
+                    .iter()
+                    .find(|(_, p)| *p == player)
+                    .map(|(line, _)| *line);
+                let notes = track.notes.len();
+                if notes == 0 {
+                    errors.push(issue(ValidationErrorKind::EmptyTrack(player), marker_line));
+                } else if (notes as f64) < max as f64 * MIN_TRACK_SHARE {
+                    warnings.push(issue(
+                        ValidationErrorKind::UnbalancedTracks { player, notes, max },
+                        marker_line,
+                    ));
+                }
+            }
         }
     }
 
-    fn validate_line_break(line: &str, line_num: usize, errors: &mut Vec<ValidationError>) {
-        let rest = line[1..].trim();
-        let parts: Vec<&str> = rest.split_whitespace().collect();
-
-        if parts.is_empty() {
-            errors.push(ValidationError {
-                kind: ValidationErrorKind::InvalidLineBreak("Line break needs at least a start beat".to_string()),
-                line: Some(line_num),
-                context: Some(line.to_string()),
-            });
-            return;
+    /// Decode the referenced media and compare the song's timing to the audio length
+    fn check_media(
+        dir: &Path,
+        song: &Song,
+        errors: &mut Vec<ValidationError>,
+        warnings: &mut Vec<ValidationError>,
+    ) {
+        let metadata = &song.metadata;
+        let mut audio_ms = None;
+        let media = [
+            &metadata.audio_file,
+            &metadata.vocals_file,
+            &metadata.instrumental_file,
+            &metadata.video_file,
+        ];
+        for file in media.into_iter().flatten() {
+            let path = dir.join(file);
+            // Missing files are reported already
+            if !path.is_file() {
+                continue;
+            }
+            match MediaProbe::probe(&path) {
+                Ok(info) if metadata.audio_file.as_ref() == Some(file) => {
+                    audio_ms = info.duration_ms
+                }
+                Ok(_) | Err(MediaError::Unsupported(_)) => {}
+                Err(e) => errors.push(ValidationError {
+                    kind: ValidationErrorKind::UndecodableMedia(file.clone()),
+                    line: None,
+                    context: Some(e.to_string()),
+                }),
+            }
         }
 
-        if parts[0].parse::<i32>().is_err() {
-            errors.push(ValidationError {
-                kind: ValidationErrorKind::InvalidLineBreak(format!(
-                    "Invalid start beat: {}",
-                    parts[0]
-                )),
-                line: Some(line_num),
-                context: None,
-            });
+        if let Some(video) = &metadata.video_file {
+            let extension = Path::new(video)
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default()
+                .to_lowercase();
+            if UNPLAYABLE_VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+                warnings.push(ValidationError {
+                    kind: ValidationErrorKind::UnplayableVideo(video.clone()),
+                    line: None,
+                    context: None,
+                });
+            }
         }
 
-        // Optional end beat
-        if parts.len() > 1 && parts[1].parse::<i32>().is_err() {
-            errors.push(ValidationError {
-                kind: ValidationErrorKind::InvalidLineBreak(format!(
-                    "Invalid end beat: {}",
-                    parts[1]
-                )),
-                line: Some(line_num),
-                context: None,
-            });
+        let Some(audio_ms) = audio_ms else { return };
+        let issue = |kind| ValidationError {
+            kind,
+            line: None,
+            context: metadata.audio_file.clone(),
+        };
+        if metadata.gap > audio_ms {
+            errors.push(issue(ValidationErrorKind::GapBeyondAudio {
+                gap_ms: metadata.gap,
+                audio_ms,
+            }));
+        } else if let Some(last_note_ms) = song.last_note_ms().filter(|ms| *ms > audio_ms) {
+            warnings.push(issue(ValidationErrorKind::NotesExceedAudio {
+                last_note_ms,
+                audio_ms,
+            }));
         }
     }
 
@@ -543,6 +915,11 @@ impl Validator {
     }
 }
 
+/// Freestyle and rap notes are not sung at a pitch
+fn is_pitched(note_type: NoteType) -> bool {
+    matches!(note_type, NoteType::Normal | NoteType::Golden)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -641,4 +1018,366 @@ E
         let result = Validator::validate(&txt_path);
         assert!(result.errors.iter().any(|e| matches!(e.kind, ValidationErrorKind::InvalidNoteFormat(_))));
     }
+
+    #[test]
+    fn test_valid_means_loadable() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("test.mp3"), b"dummy").unwrap();
+        let cases = [
+            // Invalid line break end beat
+            "#TITLE:T\n#ARTIST:A\n#BPM:300\n#AUDIO:test.mp3\n: 0 5 7 Hi\n- 10 x\nE\n",
+            // Invalid START value
+            "#TITLE:T\n#ARTIST:A\n#BPM:300\n#START:soon\n#AUDIO:test.mp3\n: 0 5 7 Hi\nE\n",
+            // Relative line break without shift
+            "#TITLE:T\n#ARTIST:A\n#BPM:300\n#RELATIVE:yes\n#AUDIO:test.mp3\n: 0 5 7 Hi\n- 10\nE\n",
+            // Unsupported format version
+            "#VERSION:2.0.0\n#TITLE:T\n#ARTIST:A\n#BPM:300\n#AUDIO:test.mp3\n: 0 5 7 Hi\nE\n",
+        ];
+
+        for content in cases {
+            let txt_path = create_test_file(&dir, "song.txt", content);
+            let result = Validator::validate(&txt_path);
+            let parsed = Parser::parse(content, &txt_path);
+
+            assert!(parsed.is_err(), "Parser accepted: {}", content);
+            assert!(!result.is_valid(), "Validator accepted: {}", content);
+        }
+    }
+
+    #[test]
+    fn test_reports_all_errors_in_one_pass() {
+        let dir = TempDir::new().unwrap();
+        let content = r#"#TITLE:Test
+#ARTIST:Test
+#BPM:300
+#AUDIO:test.mp3
+: abc 5 7 Hello
+: 5 x 7 World
+- y
+: 10 5 7 Fine
+E
+"#;
+        let txt_path = create_test_file(&dir, "song.txt", content);
+        std::fs::write(dir.path().join("test.mp3"), b"dummy").unwrap();
+
+        let result = Validator::validate(&txt_path);
+        let lines: Vec<_> = result.errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![Some(5), Some(6), Some(7)]);
+    }
+
+    #[test]
+    fn test_legacy_encodings_load() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("test.mp3"), b"dummy").unwrap();
+        let txt_path = dir.path().join("song.txt");
+        std::fs::write(
+            &txt_path,
+            b"\xEF\xBB\xBF#TITLE:Test\n#ARTIST:Test\n#BPM:300\n#AUDIO:test.mp3\n: 0 5 7 Caf\xC3\xA9\nE\n",
+        )
+        .unwrap();
+
+        let result = Validator::validate(&txt_path);
+        assert!(result.is_valid(), "Errors: {:?}", result.errors);
+        assert!(result
+            .warnings
+            .iter()
+            .any(|w| matches!(w.kind, ValidationErrorKind::ContainsBom)));
+
+        std::fs::write(
+            &txt_path,
+            b"#TITLE:Test\n#ARTIST:Test\n#BPM:300\n#AUDIO:test.mp3\n: 0 5 7 Caf\xE9\nE\n",
+        )
+        .unwrap();
+
+        let result = Validator::validate(&txt_path);
+        assert!(result.is_valid(), "Errors: {:?}", result.errors);
+        assert!(result
+            .warnings
+            .iter()
+            .any(|w| matches!(w.kind, ValidationErrorKind::InvalidUtf8)));
+    }
+
+    fn issues(result: &ValidationResult) -> Vec<(Option<usize>, ValidationErrorKind)> {
+        result
+            .errors
+            .iter()
+            .chain(&result.warnings)
+            .filter(|e| !matches!(e.kind, ValidationErrorKind::MissingAudio))
+            .map(|e| (e.line, e.kind.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_note_semantics() {
+        let dir = TempDir::new().unwrap();
+        let content = r#"#TITLE:Test
+#ARTIST:Test
+#BPM:5000
+: 0 0 7 Hello
+: 10 8 7 World
+: 5 6 40 Back
+- 12
+- 20
+: 30 4 7 End
+E
+"#;
+        let txt_path = create_test_file(&dir, "song.txt", content);
+
+        let result = Validator::validate(&txt_path);
+        assert_eq!(
+            issues(&result),
+            vec![
+                (Some(4), ValidationErrorKind::NonPositiveLength(0)),
+                (Some(3), ValidationErrorKind::ImplausibleBpm(5000.0)),
+                (
+                    Some(6),
+                    ValidationErrorKind::UnsortedNotes {
+                        start: 5,
+                        previous_start: 10
+                    }
+                ),
+                (
+                    Some(5),
+                    ValidationErrorKind::OverlappingNotes {
+                        start: 10,
+                        previous_end: 11
+                    }
+                ),
+                (
+                    Some(7),
+                    ValidationErrorKind::LineBreakInsideNote {
+                        beat: 12,
+                        note_start: 10,
+                        note_end: 18
+                    }
+                ),
+                (Some(6), ValidationErrorKind::PitchJump { from: 7, to: 40 }),
+                (Some(7), ValidationErrorKind::EmptyLine),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extreme_values() {
+        let dir = TempDir::new().unwrap();
+        let content = "#TITLE:Test\n#ARTIST:Test\n#BPM:300\n: 0 2 -2147483648 a\n: 2147483640 20 2147483647 b\n- 2147483645\n: 2147483647 10 0 c\nE\n";
+        let txt_path = create_test_file(&dir, "song.txt", content);
+
+        let result = Validator::validate(&txt_path);
+        assert_eq!(
+            issues(&result),
+            vec![
+                (
+                    Some(6),
+                    ValidationErrorKind::LineBreakInsideNote {
+                        beat: 2147483645,
+                        note_start: 2147483640,
+                        note_end: i32::MAX
+                    }
+                ),
+                (
+                    Some(5),
+                    ValidationErrorKind::PitchJump {
+                        from: i32::MIN,
+                        to: i32::MAX
+                    }
+                ),
+                (
+                    Some(5),
+                    ValidationErrorKind::PitchRange {
+                        low: i32::MIN,
+                        high: i32::MAX
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duet_tracks() {
+        let dir = TempDir::new().unwrap();
+        let mut content = String::from("#TITLE:Test\n#ARTIST:Test\n#BPM:300\nP1\n");
+        for i in 0..10 {
+            content.push_str(&format!(": {} 2 7 la\n", i * 4));
+        }
+        content.push_str("P2\n: 0 2 7 la\nP3\nE\n");
+        let txt_path = create_test_file(&dir, "song.txt", &content);
+
+        let result = Validator::validate(&txt_path);
+        assert_eq!(
+            issues(&result),
+            vec![(
+                Some(15),
+                ValidationErrorKind::UnbalancedTracks {
+                    player: 2,
+                    notes: 1,
+                    max: 10
+                }
+            )]
+        );
+
+        let content = "#TITLE:Test\n#ARTIST:Test\n#BPM:300\nP1\n: 0 2 7 la\nP2\nE\n";
+        let txt_path = create_test_file(&dir, "song.txt", content);
+
+        let result = Validator::validate(&txt_path);
+        assert_eq!(
+            issues(&result),
+            vec![(Some(6), ValidationErrorKind::EmptyTrack(2))]
+        );
+    }
+
+    #[test]
+    fn test_inline_suppression() {
+        let dir = TempDir::new().unwrap();
+        let content = "#TITLE:Test\n#ARTIST:Test\n#BPM:300\n#COMMENT:frank-validate-disable FV006, FV028\n: 0 2 7 la\n- 4\n- 8\nE\n";
+        let txt_path = create_test_file(&dir, "song.txt", content);
+
+        let result = Validator::validate(&txt_path);
+        assert!(result.is_valid(), "Errors: {:?}", result.errors);
+        assert!(
+            result.warnings.is_empty(),
+            "Warnings: {:?}",
+            result.warnings
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_cannot_be_suppressed() {
+        let dir = TempDir::new().unwrap();
+        let content = "#TITLE:Test\n#ARTIST:Test\n#BPM:300\n#AUDIO:test.mp3\n#COMMENT:frank-validate-disable FV012\n: 0 2 7 la\n: x 2 7 la\nE\n";
+        let txt_path = create_test_file(&dir, "song.txt", content);
+        std::fs::write(dir.path().join("test.mp3"), b"dummy").unwrap();
+
+        let result = Validator::validate(&txt_path);
+        assert_eq!(result.errors.len(), 1, "Errors: {:?}", result.errors);
+        assert_eq!(result.errors[0].kind.rule_id(), "FV012");
+    }
+
+    #[test]
+    fn test_empty_tags_can_be_suppressed() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("test.mp3"), b"dummy").unwrap();
+        let content = "#TITLE:\n#ARTIST:Test\n#BPM:300\n#AUDIO:test.mp3\n: 0 2 7 la\nE\n";
+        let txt_path = create_test_file(&dir, "song.txt", content);
+
+        let result = Validator::validate(&txt_path);
+        assert_eq!(
+            issues(&result),
+            vec![(Some(1), ValidationErrorKind::EmptyTag("TITLE".to_string()))]
+        );
+        assert_eq!(result.errors[0].kind.rule_id(), "FV038");
+
+        let content = format!("#COMMENT:frank-validate-disable FV038\n{}", content);
+        let txt_path = create_test_file(&dir, "song.txt", &content);
+        assert!(Validator::validate(&txt_path).is_valid());
+    }
+
+    #[test]
+    fn test_rule_ids_cover_every_kind() {
+        let kinds = [
+            ValidationErrorKind::InvalidUtf8,
+            ValidationErrorKind::ContainsBom,
+            ValidationErrorKind::MissingTitle,
+            ValidationErrorKind::MissingArtist,
+            ValidationErrorKind::MissingBpm,
+            ValidationErrorKind::MissingAudio,
+            ValidationErrorKind::InvalidBpm(String::new()),
+            ValidationErrorKind::InvalidGap(String::new()),
+            ValidationErrorKind::InvalidYear(String::new()),
+            ValidationErrorKind::InvalidHeader(String::new()),
+            ValidationErrorKind::InvalidNoteType(String::new()),
+            ValidationErrorKind::InvalidNoteFormat(String::new()),
+            ValidationErrorKind::InvalidLineBreak(String::new()),
+            ValidationErrorKind::AudioFileNotFound(String::new()),
+            ValidationErrorKind::VideoFileNotFound(String::new()),
+            ValidationErrorKind::CoverFileNotFound(String::new()),
+            ValidationErrorKind::BackgroundFileNotFound(String::new()),
+            ValidationErrorKind::UnsupportedAudioFormat(String::new()),
+            ValidationErrorKind::UnsupportedVideoFormat(String::new()),
+            ValidationErrorKind::UnsupportedImageFormat(String::new()),
+            ValidationErrorKind::NoNotes,
+            ValidationErrorKind::NoEndMarker,
+            ValidationErrorKind::EmptyFile,
+            ValidationErrorKind::NonPositiveLength(0),
+            ValidationErrorKind::UnsortedNotes {
+                start: 0,
+                previous_start: 0,
+            },
+            ValidationErrorKind::OverlappingNotes {
+                start: 0,
+                previous_end: 0,
+            },
+            ValidationErrorKind::LineBreakInsideNote {
+                beat: 0,
+                note_start: 0,
+                note_end: 0,
+            },
+            ValidationErrorKind::EmptyLine,
+            ValidationErrorKind::PitchJump { from: 0, to: 0 },
+            ValidationErrorKind::PitchRange { low: 0, high: 0 },
+            ValidationErrorKind::ImplausibleBpm(0.0),
+            ValidationErrorKind::EmptyTrack(0),
+            ValidationErrorKind::UnbalancedTracks {
+                player: 0,
+                notes: 0,
+                max: 0,
+            },
+            ValidationErrorKind::UndecodableMedia(String::new()),
+            ValidationErrorKind::UnplayableVideo(String::new()),
+            ValidationErrorKind::NotesExceedAudio {
+                last_note_ms: 0.0,
+                audio_ms: 0.0,
+            },
+            ValidationErrorKind::GapBeyondAudio {
+                gap_ms: 0.0,
+                audio_ms: 0.0,
+            },
+            ValidationErrorKind::EmptyTag(String::new()),
+        ];
+
+        let ids: Vec<_> = kinds.iter().map(|kind| kind.rule_id()).collect();
+        assert_eq!(ids, ValidationErrorKind::RULE_IDS);
+        for id in ValidationErrorKind::PARSE_ERROR_RULE_IDS {
+            assert!(ValidationErrorKind::RULE_IDS.contains(id), "{}", id);
+        }
+    }
+
+    #[test]
+    fn test_media_checks() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("short.wav"),
+            crate::song::media::tests::wav(1000),
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("broken.mp3"), b"dummy").unwrap();
+        std::fs::write(dir.path().join("video.avi"), b"dummy").unwrap();
+        let options = ValidationOptions { media: true };
+
+        let content =
+            "#TITLE:T\n#ARTIST:A\n#BPM:300\n#AUDIO:short.wav\n#VIDEO:video.avi\n: 0 30 7 la\nE\n";
+        let txt_path = create_test_file(&dir, "song.txt", content);
+        let result = Validator::validate_with(&txt_path, &options);
+        assert!(result.is_valid(), "Errors: {:?}", result.errors);
+        let kinds: Vec<_> = result.warnings.iter().map(|w| w.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ValidationErrorKind::UnplayableVideo("video.avi".to_string()),
+                ValidationErrorKind::NotesExceedAudio {
+                    last_note_ms: 1500.0,
+                    audio_ms: 1000.0
+                },
+            ]
+        );
+
+        let content = "#TITLE:T\n#ARTIST:A\n#BPM:300\n#GAP:2000\n#AUDIO:short.wav\n#VOCALS:broken.mp3\n: 0 4 7 la\nE\n";
+        let txt_path = create_test_file(&dir, "song.txt", content);
+        let result = Validator::validate_with(&txt_path, &options);
+        let rules: Vec<_> = result.errors.iter().map(|e| e.kind.rule_id()).collect();
+        assert_eq!(rules, vec!["FV034", "FV037"]);
+
+        // Only checked on request
+        assert!(Validator::validate(&txt_path).is_valid());
+    }
 }
@@ -0,0 +1,404 @@
+use crate::song::types::{FormatVersion, LineBreak, Note, Song, SongMetadata};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Header tags written for songs that carry no tag order of their own
+/// (e.g. songs built in code), in the order they should appear
+const CANONICAL_TAG_ORDER: &[&str] = &[
+    "VERSION",
+    "TITLE",
+    "ARTIST",
+    "MP3",
+    "AUDIO",
+    "VOCALS",
+    "INSTRUMENTAL",
+    "VIDEO",
+    "COVER",
+    "BACKGROUND",
+    "BPM",
+    "GAP",
+    "VIDEOGAP",
+    "START",
+    "END",
+    "PREVIEWSTART",
+    "MEDLEYSTARTBEAT",
+    "MEDLEYENDBEAT",
+    "GENRE",
+    "EDITION",
+    "LANGUAGE",
+    "YEAR",
+    "CREATOR",
+    "DUETSINGERP1",
+    "P1",
+    "DUETSINGERP2",
+    "P2",
+];
+
+/// Writer for UltraStar TXT files, the inverse of [`Parser`](crate::song::parser::Parser)
+pub struct Writer;
+
+impl Writer {
+    /// Serialize a song into UltraStar TXT format
+    ///
+    /// Header tags are written in the order they were read, with unknown tags
+    /// preserved verbatim. Known tags that are set but were not part of the
+    /// original file are appended in canonical order.
+    pub fn write(song: &Song) -> String {
+        let mut out = String::new();
+        let metadata = &song.metadata;
+
+        let mut written: Vec<&str> = Vec::new();
+        // Repeated unknown tags appear in the tag order once per occurrence
+        let mut extra_written = vec![false; metadata.extra_tags.len()];
+
+        for tag in &metadata.tag_order {
+            if CANONICAL_TAG_ORDER.contains(&tag.as_str()) {
+                if let Some(value) = Self::header_value(metadata, tag) {
+                    Self::write_header(&mut out, tag, &value);
+                    written.push(tag.as_str());
+                }
+                continue;
+            }
+            let next = metadata
+                .extra_tags
+                .iter()
+                .enumerate()
+                .find(|(i, (name, _))| !extra_written[*i] && name.eq_ignore_ascii_case(tag));
+            if let Some((i, (name, value))) = next {
+                Self::write_header(&mut out, name, value);
+                extra_written[i] = true;
+            }
+        }
+
+        for tag in Self::canonical_tags(metadata) {
+            // GAP defaults to 0, so only spell it out when it matters
+            if Self::is_tag_written(tag, &written) || (tag == "GAP" && metadata.gap == 0.0) {
+                continue;
+            }
+            if let Some(value) = Self::header_value(metadata, tag) {
+                Self::write_header(&mut out, tag, &value);
+            }
+        }
+
+        for (i, (tag, value)) in metadata.extra_tags.iter().enumerate() {
+            if !extra_written[i] {
+                Self::write_header(&mut out, tag, value);
+            }
+        }
+
+        // P3 and up only denote their own track when a singer is declared,
+        // otherwise P3 would be read back as "both singers"
+        for (i, track) in song.tracks.iter().enumerate().skip(2) {
+            if metadata.singer(i + 1).is_none() {
+                let singer = track.singer.as_deref().unwrap_or_default();
+                Self::write_header(&mut out, &format!("P{}", i + 1), singer);
+            }
+        }
+
+        match song.tracks.as_slice() {
+            [track] => Self::write_track(&mut out, &track.notes, &track.line_breaks),
+            tracks => {
+                for (i, track) in tracks.iter().enumerate() {
+                    let _ = writeln!(out, "P{}", i + 1);
+                    Self::write_track(&mut out, &track.notes, &track.line_breaks);
+                }
+            }
+        }
+
+        out.push_str("E\n");
+        out
+    }
+
+    /// Write a song to a TXT file, see [`Writer::write_file_with_backup`]
+    pub fn write_with_backup(song: &Song, path: &Path) -> std::io::Result<Option<PathBuf>> {
+        Self::write_file_with_backup(path, Self::write(song).as_bytes())
+    }
+
+    /// Replace a file's content, keeping the original in `<name>.bak`
+    ///
+    /// An existing backup is left alone, so repeated edits never lose the
+    /// original. The content is written to a temporary file next to `path`
+    /// and renamed over it, so the file is never left half-written.
+    ///
+    /// Returns the backup's path if there is one.
+    pub fn write_file_with_backup(path: &Path, content: &[u8]) -> std::io::Result<Option<PathBuf>> {
+        let backup = Self::backup_path(path);
+        if path.exists() && !backup.exists() {
+            std::fs::copy(path, &backup)?;
+        }
+
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".tmp");
+        let tmp = path.with_file_name(name);
+        std::fs::write(&tmp, content)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(backup.exists().then_some(backup))
+    }
+
+    /// Where the original version of a file is kept: `song.txt.bak`
+    pub fn backup_path(path: &Path) -> PathBuf {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".bak");
+        path.with_file_name(name)
+    }
+
+    /// Known tags in canonical order, with the audio and duet singer tag
+    /// spelling chosen to match the song's format version
+    fn canonical_tags(metadata: &SongMetadata) -> impl Iterator<Item = &'static str> {
+        let version = metadata.format_version();
+        let skipped: &[&str] = match version {
+            Some(v) if v >= FormatVersion::V1_1_0 => &["MP3", "DUETSINGERP1", "DUETSINGERP2"],
+            Some(_) => &["AUDIO", "DUETSINGERP1", "DUETSINGERP2"],
+            None => &["AUDIO", "P1", "P2"],
+        };
+
+        CANONICAL_TAG_ORDER
+            .iter()
+            .copied()
+            .filter(move |tag| !skipped.contains(tag))
+    }
+
+    /// Whether a tag (or one of its aliases) has already been written
+    fn is_tag_written(tag: &str, written: &[&str]) -> bool {
+        let aliases: &[&str] = match tag {
+            "MP3" | "AUDIO" => &["MP3", "AUDIO"],
+            "DUETSINGERP1" | "P1" => &["DUETSINGERP1", "P1"],
+            "DUETSINGERP2" | "P2" => &["DUETSINGERP2", "P2"],
+            _ => &[],
+        };
+
+        written.contains(&tag) || aliases.iter().any(|alias| written.contains(alias))
+    }
+
+    /// Current value of a known header tag, or `None` if the field is unset
+    fn header_value(metadata: &SongMetadata, tag: &str) -> Option<String> {
+        match tag {
+            "VERSION" => metadata.version.clone(),
+            "TITLE" => Some(metadata.title.clone()),
+            "ARTIST" => Some(metadata.artist.clone()),
+            "MP3" | "AUDIO" => metadata.audio_file.clone(),
+            "VOCALS" => metadata.vocals_file.clone(),
+            "INSTRUMENTAL" => metadata.instrumental_file.clone(),
+            "VIDEO" => metadata.video_file.clone(),
+            "COVER" => metadata.cover_file.clone(),
+            "BACKGROUND" => metadata.background_file.clone(),
+            "BPM" => Some(metadata.bpm.to_string()),
+            "GAP" => Some(metadata.gap.to_string()),
+            "VIDEOGAP" => metadata.video_gap.map(|v| v.to_string()),
+            "START" => metadata.start.map(|v| v.to_string()),
+            "END" => metadata.end.map(|v| v.to_string()),
+            "PREVIEWSTART" => metadata.preview_start.map(|v| v.to_string()),
+            "MEDLEYSTARTBEAT" => metadata.medley_start_beat.map(|v| v.to_string()),
+            "MEDLEYENDBEAT" => metadata.medley_end_beat.map(|v| v.to_string()),
+            "GENRE" => metadata.genre.clone(),
+            "EDITION" => metadata.edition.clone(),
+            "LANGUAGE" => metadata.language.clone(),
+            "YEAR" => metadata.year.map(|v| v.to_string()),
+            "CREATOR" => metadata.creator.clone(),
+            "DUETSINGERP1" | "P1" => metadata.duet_singer_p1.clone(),
+            "DUETSINGERP2" | "P2" => metadata.duet_singer_p2.clone(),
+            _ => None,
+        }
+    }
+
+    fn write_header(out: &mut String, tag: &str, value: &str) {
+        let _ = writeln!(out, "#{}:{}", tag, value);
+    }
+
+    /// Write one player's notes with line breaks interleaved by beat
+    fn write_track(out: &mut String, notes: &[Note], line_breaks: &[LineBreak]) {
+        let mut line_breaks = line_breaks.iter().peekable();
+
+        for note in notes {
+            while let Some(line_break) = line_breaks.next_if(|lb| lb.start_beat <= note.start_beat)
+            {
+                Self::write_line_break(out, line_break);
+            }
+            Self::write_note(out, note);
+        }
+
+        for line_break in line_breaks {
+            Self::write_line_break(out, line_break);
+        }
+    }
+
+    fn write_note(out: &mut String, note: &Note) {
+        let _ = writeln!(
+            out,
+            "{} {} {} {} {}",
+            note.note_type.symbol(),
+            note.start_beat,
+            note.length,
+            note.pitch,
+            note.text
+        );
+    }
+
+    fn write_line_break(out: &mut String, line_break: &LineBreak) {
+        let _ = match line_break.end_beat {
+            Some(end_beat) => writeln!(out, "- {} {}", line_break.start_beat, end_beat),
+            None => writeln!(out, "- {}", line_break.start_beat),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::song::fixtures::FIXTURES;
+    use crate::song::parser::Parser;
+    use crate::song::types::NoteType;
+    use proptest::prelude::*;
+    use std::path::PathBuf;
+
+    fn parse(content: &str) -> Song {
+        Parser::parse(content, &PathBuf::from("test.txt")).unwrap()
+    }
+
+    #[test]
+    fn test_write_keeps_first_backup() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("song.txt");
+
+        assert_eq!(
+            Writer::write_file_with_backup(&path, b"first").unwrap(),
+            None
+        );
+        let backup = Writer::write_file_with_backup(&path, b"second").unwrap();
+        assert_eq!(backup, Some(dir.path().join("song.txt.bak")));
+        Writer::write_file_with_backup(&path, b"third").unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"third");
+        assert_eq!(
+            std::fs::read(dir.path().join("song.txt.bak")).unwrap(),
+            b"first"
+        );
+        assert!(!dir.path().join("song.txt.tmp").exists());
+    }
+
+    #[test]
+    fn test_round_trip_fixtures() {
+        for (name, content) in FIXTURES {
+            let song = parse(content);
+            let written = Writer::write(&song);
+            let reparsed = Parser::parse(&written, &PathBuf::from("test.txt"))
+                .unwrap_or_else(|e| panic!("{}: {}\n{}", name, e, written));
+            assert_eq!(reparsed, song, "{} did not round-trip:\n{}", name, written);
+        }
+    }
+
+    #[test]
+    fn test_preserves_header_order_and_unknown_tags() {
+        let content = "#ARTIST:Test\n#TITLE:Order\n#AUTHOR:Someone\n#BPM:300\n#COMMENT:Keep me\n: 0 5 7 Hi\nE\n";
+        let written = Writer::write(&parse(content));
+
+        assert_eq!(written, content);
+    }
+
+    #[test]
+    fn test_preserves_repeated_unknown_tags() {
+        let content = "#TITLE:Repeat\n#ARTIST:Test\n#COMMENT:First\n#author:Someone\n#BPM:300\n#Comment:Second\n: 0 5 7 Hi\nE\n";
+        let song = parse(content);
+        let written = Writer::write(&song);
+
+        assert_eq!(written, content);
+        assert_eq!(
+            parse(&written).metadata.extra_tags,
+            song.metadata.extra_tags
+        );
+    }
+
+    #[test]
+    fn test_writes_absolute_beats_for_relative_songs() {
+        let content = "#TITLE:Rel\n#ARTIST:Test\n#RELATIVE:yes\n#BPM:300\n: 0 5 7 One\n- 6 10\n: 0 5 7 Two\nE\n";
+        let written = Writer::write(&parse(content));
+
+        assert_eq!(
+            written,
+            "#TITLE:Rel\n#ARTIST:Test\n#BPM:300\n: 0 5 7 One\n- 6\n: 10 5 7 Two\nE\n"
+        );
+    }
+
+    #[test]
+    fn test_writes_canonical_headers_for_new_songs() {
+        let mut song = parse("#TITLE:New\n#ARTIST:Test\n#BPM:300\n: 0 5 7 Hi\nE\n");
+        song.metadata.tag_order.clear();
+        song.metadata.version = Some("1.1.0".to_string());
+        song.metadata.audio_file = Some("song.ogg".to_string());
+
+        let written = Writer::write(&song);
+
+        assert_eq!(
+            written,
+            "#VERSION:1.1.0\n#TITLE:New\n#ARTIST:Test\n#AUDIO:song.ogg\n#BPM:300\n: 0 5 7 Hi\nE\n"
+        );
+    }
+
+    fn note_strategy() -> impl Strategy<Value = (char, i32, i32, String)> {
+        (
+            prop::sample::select(vec![':', '*', 'F', 'R', 'G']),
+            1..16i32,
+            -12..24i32,
+            "[ \t]?[A-Za-z'~]{1,6}[ ~]?",
+        )
+    }
+
+    fn song_strategy() -> impl Strategy<Value = String> {
+        (
+            "[A-Za-z ]{0,10}[A-Za-z]",
+            1..600u32,
+            0..5000u32,
+            prop::collection::vec(note_strategy(), 1..20),
+            prop::collection::vec(note_strategy(), 0..10),
+        )
+            .prop_map(|(title, bpm, gap, notes_p1, notes_p2)| {
+                let mut content = format!(
+                    "#TITLE:{}\n#ARTIST:Prop\n#BPM:{},5\n#GAP:{}\n",
+                    title, bpm, gap
+                );
+                for (player, notes) in [(1, &notes_p1), (2, &notes_p2)] {
+                    if notes_p2.is_empty() && player == 2 {
+                        continue;
+                    }
+                    if !notes_p2.is_empty() {
+                        content.push_str(&format!("P{}\n", player));
+                    }
+                    let mut beat = 0;
+                    for (i, (symbol, length, pitch, text)) in notes.iter().enumerate() {
+                        if i > 0 && i % 4 == 0 {
+                            content.push_str(&format!("- {}\n", beat));
+                        }
+                        content.push_str(&format!(
+                            "{} {} {} {} {}\n",
+                            symbol, beat, length, pitch, text
+                        ));
+                        beat += length + 1;
+                    }
+                }
+                content.push_str("E\n");
+                content
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_parse_write_round_trip(content in song_strategy()) {
+            let song = parse(&content);
+            let reparsed = parse(&Writer::write(&song));
+            prop_assert_eq!(reparsed, song);
+        }
+    }
+
+    #[test]
+    fn test_note_type_symbols_round_trip() {
+        for note_type in [
+            NoteType::Normal,
+            NoteType::Golden,
+            NoteType::Freestyle,
+            NoteType::Rap,
+            NoteType::GoldenRap,
+        ] {
+            assert_eq!(NoteType::from_symbol(note_type.symbol()), Some(note_type));
+        }
+    }
+}
@@ -1,10 +1,14 @@
 use crate::config::Config;
-use crate::song::{Indexer, Song, SongSummary};
+use crate::error::AppError;
+use crate::song::{AliasTable, Indexer, Parser, ScanProgress, Song, SongSummary};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, RwLock};
+use tracing::{info, warn};
 use utoipa::ToSchema;
 
 /// A queue entry representing a song request from a party guest
@@ -17,6 +21,62 @@ pub struct QueueEntry {
     pub submitter: String,
 }
 
+/// Songs that were added, changed or removed since the library was loaded
+///
+/// Both lists are empty when a client missed changes and should reload
+/// the whole song list.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct LibraryChange {
+    /// IDs of added or changed songs
+    pub updated: Vec<String>,
+    /// IDs of songs that are gone
+    pub removed: Vec<String>,
+}
+
+impl LibraryChange {
+    pub fn is_empty(&self) -> bool {
+        self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Whether a library rescan is running, or how the last one ended
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RescanState {
+    #[default]
+    Idle,
+    Running,
+    Finished,
+    Failed,
+}
+
+/// Progress of the latest library rescan
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct RescanStatus {
+    pub state: RescanState,
+    /// Song files found in the songs directory
+    pub found: usize,
+    /// Files indexed so far, parsed or taken from the index cache
+    pub parsed: usize,
+    /// Files that could not be read or parsed
+    pub failed: usize,
+    /// Unix time in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<u64>,
+    /// Unix time in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+    /// Why the rescan failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+struct Rescan {
+    status: RescanStatus,
+    progress: Arc<ScanProgress>,
+}
+
 /// Application state shared across all request handlers
 #[derive(Clone)]
 pub struct AppState {
@@ -28,6 +88,9 @@ struct AppStateInner {
     pub songs: RwLock<HashMap<String, Song>>,
     pub queue: RwLock<VecDeque<QueueEntry>>,
     pub next_queue_id: AtomicU64,
+    pub library_changes: broadcast::Sender<LibraryChange>,
+    pub aliases: RwLock<AliasTable>,
+    rescan: Mutex<Rescan>,
 }
 
 impl AppState {
@@ -38,33 +101,250 @@ impl AppState {
                 songs: RwLock::new(HashMap::new()),
                 queue: RwLock::new(VecDeque::new()),
                 next_queue_id: AtomicU64::new(1),
+                library_changes: broadcast::channel(16).0,
+                aliases: RwLock::new(AliasTable::default()),
+                rescan: Mutex::new(Rescan::default()),
             }),
         }
     }
 
-    #[allow(dead_code)]
     pub fn config(&self) -> &Config {
         &self.inner.config
     }
 
-    /// Initialize the song index by scanning the songs directory
-    pub async fn init_song_index(&self) -> crate::error::Result<()> {
-        let songs = Indexer::scan_directory(&self.inner.config.songs_directory)?;
+    /// Initialize the song index by scanning the songs directory, reusing
+    /// unchanged songs from the index cache unless `rebuild` is set
+    pub async fn init_song_index(&self, rebuild: bool) -> crate::error::Result<()> {
+        let config = &self.inner.config;
+        let scan = Indexer::scan_directory_cached(
+            &config.songs_directory,
+            &config.index_cache_path(),
+            rebuild,
+            &ScanProgress::default(),
+        )?;
+        *self.inner.aliases.write().await = AliasTable::load(&config.alias_table_path());
+
+        // Songs used to be identified by their path, and songs edited or
+        // copied while the server was down have a different ID now
+        let mut renames: Vec<_> = scan
+            .songs
+            .values()
+            .map(|song| (Parser::path_id(&song.files.txt_path), song.id.clone()))
+            .collect();
+        renames.extend(scan.renamed);
+        self.record_renames(&renames).await;
+
         let mut lock = self.inner.songs.write().await;
-        *lock = songs;
+        *lock = scan.songs;
         Ok(())
     }
 
+    /// Rescan the songs directory in the background and swap the result in
+    /// once it is complete; `None` if a rescan is already running
+    pub fn start_rescan(&self, rebuild: bool) -> Option<RescanStatus> {
+        let progress = Arc::new(ScanProgress::default());
+        {
+            let mut rescan = self.inner.rescan.lock().unwrap();
+            if rescan.status.state == RescanState::Running {
+                return None;
+            }
+            *rescan = Rescan {
+                status: RescanStatus {
+                    state: RescanState::Running,
+                    started_at: Some(unix_now()),
+                    ..Default::default()
+                },
+                progress: progress.clone(),
+            };
+        }
+
+        let state = self.clone();
+        tokio::spawn(async move {
+            let config = state.config().clone();
+            let scan = tokio::task::spawn_blocking(move || {
+                Indexer::scan_directory_cached(
+                    &config.songs_directory,
+                    &config.index_cache_path(),
+                    rebuild,
+                    &progress,
+                )
+            })
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))
+            .and_then(|scan| scan);
+
+            let error = match scan {
+                Ok(scan) => {
+                    // ID changes are picked up against the songs in memory,
+                    // which are newer than the cache
+                    let songs_directory = &state.config().songs_directory;
+                    let songs = scan.songs.into_values().collect();
+                    let change = state.replace_songs_in(songs_directory, true, songs).await;
+                    info!(
+                        "Rescan finished: {} updated, {} removed",
+                        change.updated.len(),
+                        change.removed.len()
+                    );
+                    None
+                }
+                Err(e) => {
+                    warn!("Rescan failed: {}", e);
+                    Some(e.to_string())
+                }
+            };
+
+            let mut rescan = state.inner.rescan.lock().unwrap();
+            rescan.status.state = match error {
+                None => RescanState::Finished,
+                Some(_) => RescanState::Failed,
+            };
+            rescan.status.finished_at = Some(unix_now());
+            rescan.status.error = error;
+        });
+
+        Some(self.rescan_status())
+    }
+
+    /// Progress of the running or latest rescan
+    pub fn rescan_status(&self) -> RescanStatus {
+        let rescan = self.inner.rescan.lock().unwrap();
+        let progress = &rescan.progress;
+        RescanStatus {
+            found: progress.found.load(Ordering::Relaxed),
+            parsed: progress.indexed.load(Ordering::Relaxed),
+            failed: progress.failed.load(Ordering::Relaxed),
+            ..rescan.status.clone()
+        }
+    }
+
     /// Get a list of all songs (summaries only)
     pub async fn get_song_list(&self) -> Vec<SongSummary> {
         let songs = self.inner.songs.read().await;
         songs.values().map(SongSummary::from).collect()
     }
 
-    /// Get a song by ID
+    /// Get a song by its ID or one it had before
     pub async fn get_song(&self, id: &str) -> Option<Song> {
         let songs = self.inner.songs.read().await;
-        songs.get(id).cloned()
+        let aliases = self.inner.aliases.read().await;
+        find_song(&songs, &aliases, id).cloned()
+    }
+
+    /// Get all indexed songs
+    pub async fn get_all_songs(&self) -> Vec<Song> {
+        let songs = self.inner.songs.read().await;
+        songs.values().cloned().collect()
+    }
+
+    /// Insert or replace a song in the index
+    pub async fn update_song(&self, song: Song) {
+        let path = song.files.txt_path.clone();
+        self.replace_songs(|other| other.files.txt_path == path, vec![song])
+            .await;
+    }
+
+    /// Replace the songs read from `folder` (and its subfolders if
+    /// `recursive`) with `songs`, and tell subscribers what changed
+    pub async fn replace_songs_in(
+        &self,
+        folder: &Path,
+        recursive: bool,
+        songs: Vec<Song>,
+    ) -> LibraryChange {
+        self.replace_songs(
+            |song| {
+                let path = &song.files.txt_path;
+                if recursive {
+                    path.starts_with(folder)
+                } else {
+                    path.parent() == Some(folder)
+                }
+            },
+            songs,
+        )
+        .await
+    }
+
+    /// Replace the indexed songs matching `in_scope` with `songs`
+    ///
+    /// Songs whose ID changed because their notes were edited keep
+    /// resolving under the old one.
+    async fn replace_songs(
+        &self,
+        in_scope: impl Fn(&Song) -> bool,
+        mut songs: Vec<Song>,
+    ) -> LibraryChange {
+        let mut change = LibraryChange::default();
+        let mut renames = Vec::new();
+        let mut lock = self.inner.songs.write().await;
+
+        // A copy of a song that is already indexed gets an ID of its own
+        songs.sort_by(|a, b| a.files.txt_path.cmp(&b.files.txt_path));
+        let mut kept = HashSet::new();
+        for song in &mut songs {
+            song.id = Indexer::unique_id(&song.content_id(), |id| {
+                kept.contains(id) || lock.get(id).is_some_and(|other| !in_scope(other))
+            });
+            kept.insert(song.id.clone());
+        }
+
+        let previous: HashMap<PathBuf, String> = lock
+            .values()
+            .filter(|song| in_scope(song))
+            .map(|song| (song.files.txt_path.clone(), song.id.clone()))
+            .collect();
+        lock.retain(|id, song| {
+            let gone = in_scope(song) && !kept.contains(id);
+            if gone {
+                change.removed.push(id.clone());
+            }
+            !gone
+        });
+
+        for song in songs {
+            let path = &song.files.txt_path;
+            renames.push((Parser::path_id(path), song.id.clone()));
+            if let Some(old) = previous.get(path).filter(|old| **old != song.id) {
+                renames.push((old.clone(), song.id.clone()));
+            }
+            if lock.get(&song.id) != Some(&song) {
+                change.updated.push(song.id.clone());
+                lock.insert(song.id.clone(), song);
+            }
+        }
+        drop(lock);
+
+        self.record_renames(&renames).await;
+        if !change.is_empty() {
+            // Nobody listening is fine
+            let _ = self.inner.library_changes.send(change.clone());
+        }
+        change
+    }
+
+    /// Point queue entries and aliases using an old song ID at the new one
+    async fn record_renames(&self, renames: &[(String, String)]) {
+        let mut queue = self.inner.queue.write().await;
+        for entry in queue.iter_mut() {
+            if let Some((_, new)) = renames.iter().find(|(old, _)| *old == entry.song_id) {
+                entry.song_id = new.clone();
+            }
+        }
+        drop(queue);
+
+        let mut aliases = self.inner.aliases.write().await;
+        let mut changed = false;
+        for (old, new) in renames {
+            changed |= aliases.insert(old, new);
+        }
+        if changed {
+            save_aliases(&self.inner.config, &aliases);
+        }
+    }
+
+    /// Receive a [`LibraryChange`] whenever songs are added, changed or removed
+    pub fn subscribe_library_changes(&self) -> broadcast::Receiver<LibraryChange> {
+        self.inner.library_changes.subscribe()
     }
 
     /// Search songs by query (matches title or artist)
@@ -92,11 +372,12 @@ impl AppState {
     pub async fn add_to_queue(&self, song_id: &str, submitter: String) -> Option<QueueEntry> {
         // Look up the song to get title and artist
         let songs = self.inner.songs.read().await;
-        let song = songs.get(song_id)?;
+        let aliases = self.inner.aliases.read().await;
+        let song = find_song(&songs, &aliases, song_id)?;
 
         let entry = QueueEntry {
             id: self.inner.next_queue_id.fetch_add(1, Ordering::SeqCst),
-            song_id: song_id.to_string(),
+            song_id: song.id.clone(),
             song_title: song.metadata.title.clone(),
             song_artist: song.metadata.artist.clone(),
             submitter,
@@ -121,6 +402,10 @@ impl AppState {
 
     /// Remove a queue entry by song ID (used when a song is played)
     pub async fn remove_from_queue_by_song(&self, song_id: &str) -> bool {
+        let song_id = match self.get_song(song_id).await {
+            Some(song) => song.id,
+            None => song_id.to_string(),
+        };
         let mut queue = self.inner.queue.write().await;
         if let Some(pos) = queue.iter().position(|e| e.song_id == song_id) {
             queue.remove(pos);
@@ -130,3 +415,28 @@ impl AppState {
         }
     }
 }
+
+/// Look up a song by its current ID, falling back to the alias table
+fn find_song<'a>(
+    songs: &'a HashMap<String, Song>,
+    aliases: &AliasTable,
+    id: &str,
+) -> Option<&'a Song> {
+    songs
+        .get(id)
+        .or_else(|| aliases.resolve(id).and_then(|id| songs.get(id)))
+}
+
+fn save_aliases(config: &Config, aliases: &AliasTable) {
+    let path = config.alias_table_path();
+    if let Err(e) = aliases.save(&path) {
+        warn!("Failed to write song aliases {:?}: {}", path, e);
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
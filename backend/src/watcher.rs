@@ -0,0 +1,207 @@
+use crate::error::AppError;
+use crate::song::{Indexer, Song};
+use crate::state::AppState;
+use notify_debouncer_full::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult, Debouncer, RecommendedCache};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// How long the library has to be quiet before changes are picked up, so
+/// copying a song folder is handled once rather than file by file
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// A folder to reindex after something in it changed
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Refresh {
+    folder: PathBuf,
+    /// Also reindex subfolders, for added, moved or deleted folders
+    recursive: bool,
+}
+
+impl Refresh {
+    fn scan(&self) -> crate::error::Result<Vec<Song>> {
+        if !self.folder.is_dir() {
+            return Ok(Vec::new());
+        }
+        if self.recursive {
+            Ok(Indexer::scan_directory(&self.folder)?
+                .into_values()
+                .collect())
+        } else {
+            Indexer::scan_folder(&self.folder)
+        }
+    }
+}
+
+/// Keeps the song index in sync with the songs directory while it is alive
+pub struct LibraryWatcher {
+    _debouncer: Debouncer<RecommendedWatcher, RecommendedCache>,
+}
+
+impl LibraryWatcher {
+    /// Watch the configured songs directory, reindexing songs whose TXT or
+    /// media files change and dropping deleted ones
+    pub fn spawn(state: AppState) -> notify_debouncer_full::notify::Result<Self> {
+        let root = state.config().songs_directory.clone();
+        // Events carry absolute paths; they are rewritten under the configured
+        // path only so `txt_path` of reindexed songs matches the indexed ones
+        let watched = root.canonicalize()?;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let configured = root.clone();
+        let prefix = watched.clone();
+        let handler = move |result: DebounceEventResult| match result {
+            Ok(events) => {
+                let paths: Vec<_> = events
+                    .iter()
+                    .filter(|event| !event.kind.is_access())
+                    .flat_map(|event| &event.paths)
+                    .filter_map(|path| path.strip_prefix(&prefix).ok())
+                    .map(|relative| configured.join(relative))
+                    .collect();
+                if !paths.is_empty() {
+                    let _ = tx.send(paths);
+                }
+            }
+            Err(errors) => {
+                for e in errors {
+                    warn!("Error watching songs directory: {}", e);
+                }
+            }
+        };
+
+        let mut debouncer = new_debouncer(DEBOUNCE, None, handler)?;
+        debouncer.watch(&watched, RecursiveMode::Recursive)?;
+        info!("Watching {:?} for changes", root);
+
+        tokio::spawn(async move {
+            while let Some(paths) = rx.recv().await {
+                for refresh in plan_refreshes(paths, &root) {
+                    reload(&state, refresh).await;
+                }
+            }
+        });
+
+        Ok(Self {
+            _debouncer: debouncer,
+        })
+    }
+}
+
+async fn reload(state: &AppState, refresh: Refresh) {
+    let folder = refresh.folder.clone();
+    let recursive = refresh.recursive;
+    // Parsing reads files, so keep it off the async workers
+    let songs = tokio::task::spawn_blocking(move || refresh.scan())
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))
+        .and_then(|songs| songs);
+    let songs = match songs {
+        Ok(songs) => songs,
+        Err(e) => {
+            warn!("Failed to reindex {:?}: {}", folder, e);
+            return;
+        }
+    };
+
+    let change = state.replace_songs_in(&folder, recursive, songs).await;
+    if !change.is_empty() {
+        info!(
+            "Reloaded {:?}: {} updated, {} removed",
+            folder,
+            change.updated.len(),
+            change.removed.len()
+        );
+    }
+}
+
+/// Work out which folders to reindex for a burst of changed paths
+///
+/// A changed file reindexes the songs next to it, since media files only
+/// matter to the song in their folder. Folders, and paths that no longer
+/// exist and may have been folders, are reindexed with their subfolders.
+fn plan_refreshes(paths: impl IntoIterator<Item = PathBuf>, root: &Path) -> Vec<Refresh> {
+    let mut refreshes = BTreeSet::new();
+    for path in paths.into_iter().filter(|p| p.starts_with(root)) {
+        if path.is_dir() {
+            refreshes.insert(Refresh {
+                folder: path,
+                recursive: true,
+            });
+            continue;
+        }
+        if let Some(parent) = path.parent().filter(|p| p.starts_with(root)) {
+            refreshes.insert(Refresh {
+                folder: parent.to_path_buf(),
+                recursive: false,
+            });
+        }
+        if !path.exists() {
+            refreshes.insert(Refresh {
+                folder: path,
+                recursive: true,
+            });
+        }
+    }
+
+    // Skip folders that a recursive refresh of a parent covers anyway
+    let recursive: Vec<_> = refreshes
+        .iter()
+        .filter(|r| r.recursive)
+        .map(|r| r.folder.clone())
+        .collect();
+    refreshes
+        .into_iter()
+        .filter(|r| {
+            !recursive
+                .iter()
+                .any(|folder| r.folder.starts_with(folder) && (&r.folder != folder || !r.recursive))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_plan_refreshes() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("a")).unwrap();
+        std::fs::create_dir_all(root.join("b/c")).unwrap();
+        std::fs::write(root.join("a/song.txt"), "").unwrap();
+        std::fs::write(root.join("a/song.mp3"), "").unwrap();
+        std::fs::write(root.join("b/c/song.txt"), "").unwrap();
+
+        let refresh = |folder: &str, recursive| Refresh {
+            folder: root.join(folder),
+            recursive,
+        };
+        let paths = [
+            root.join("a/song.txt"),
+            root.join("a/song.mp3"),
+            root.join("b"),
+            root.join("b/c/song.txt"),
+            root.join("gone"),
+            PathBuf::from("/elsewhere/song.txt"),
+        ];
+
+        assert_eq!(
+            plan_refreshes(paths, root),
+            vec![
+                Refresh {
+                    folder: root.to_path_buf(),
+                    recursive: false,
+                },
+                refresh("a", false),
+                refresh("b", true),
+                refresh("gone", true),
+            ]
+        );
+    }
+}
@@ -1,4 +1,10 @@
-import type { QueueEntry, Song, SongSummary } from "./types";
+import type {
+  LibraryChange,
+  Lyrics,
+  QueueEntry,
+  Song,
+  SongSummary,
+} from "./types";
 
 const API_BASE = "/api";
 
@@ -21,6 +27,10 @@ export async function getSong(id: string): Promise<Song> {
   return fetchJson<Song>(`${API_BASE}/songs/${id}`);
 }
 
+export async function getLyrics(id: string): Promise<Lyrics> {
+  return fetchJson<Lyrics>(`${API_BASE}/songs/${id}/lyrics`);
+}
+
 export async function searchSongs(query: string): Promise<SongSummary[]> {
   const params = new URLSearchParams({ q: query });
   return fetchJson<SongSummary[]>(`${API_BASE}/search?${params}`);
@@ -28,11 +38,35 @@ export async function searchSongs(query: string): Promise<SongSummary[]> {
 
 export function getFileUrl(
   songId: string,
-  fileType: "audio" | "video" | "cover" | "background",
+  fileType:
+    | "audio"
+    | "video"
+    | "cover"
+    | "background"
+    | "vocals"
+    | "instrumental",
 ): string {
   return `/files/${songId}/${fileType}`;
 }
 
+export function getLyricsFileUrl(
+  songId: string,
+  format: "lrc" | "vtt" | "ass",
+): string {
+  return `${API_BASE}/songs/${songId}/lyrics.${format}`;
+}
+
+/** Listen for library changes; returns a function that stops listening */
+export function subscribeLibraryChanges(
+  onChange: (change: LibraryChange) => void,
+): () => void {
+  const events = new EventSource(`${API_BASE}/events`);
+  events.addEventListener("library", (event) => {
+    onChange(JSON.parse((event as MessageEvent<string>).data));
+  });
+  return () => events.close();
+}
+
 // Queue API
 
 export async function getQueue(): Promise<QueueEntry[]> {
@@ -14,6 +14,7 @@ export interface LineBreak {
 }
 
 export interface SongMetadata {
+  version?: string;
   title: string;
   artist: string;
   bpm: number;
@@ -30,15 +31,80 @@ export interface SongMetadata {
   video_file?: string;
   cover_file?: string;
   background_file?: string;
+  vocals_file?: string;
+  instrumental_file?: string;
+  start?: number;
+  end?: number;
+  preview_start?: number;
+  medley_start_beat?: number;
+  medley_end_beat?: number;
+  /** [tag, value] pairs in file order; tags may repeat */
+  extra_tags?: [string, string][];
 }
 
+export interface Timing {
+  start_ms: number;
+  end_ms: number;
+}
+
+export interface TrackTimings {
+  notes: Timing[];
+  lines: Timing[];
+}
+
+export interface SongTimings {
+  ms_per_beat: number;
+  first_note_ms?: number;
+  last_note_ms?: number;
+  singing_duration_ms: number;
+  tracks: TrackTimings[];
+}
+
+export interface Track {
+  singer?: string;
+  notes: Note[];
+  line_breaks: LineBreak[];
+}
+
+export type SourceFormat = "ultrastar" | "singstar" | "performous" | "midi";
+
 export interface Song {
   id: string;
   metadata: SongMetadata;
+  tracks: Track[];
+  // First two tracks, mirrored from `tracks` for older code
   notes: Note[];
   notes_p2?: Note[];
   line_breaks: LineBreak[];
   line_breaks_p2?: LineBreak[];
+  source_format: SourceFormat;
+  timings?: SongTimings;
+}
+
+export interface Word {
+  text: string;
+  start: number;
+  end: number;
+  first_note: number;
+  note_count: number;
+}
+
+export interface Line {
+  text: string;
+  start: number;
+  end: number;
+  notes: Note[];
+  words: Word[];
+}
+
+export interface LyricsTrack {
+  player: number;
+  singer?: string;
+  lines: Line[];
+}
+
+export interface Lyrics {
+  tracks: LyricsTrack[];
 }
 
 export interface SongSummary {
@@ -49,6 +115,7 @@ export interface SongSummary {
   year?: number;
   language?: string;
   has_video: boolean;
+  track_count: number;
   is_duet: boolean;
   cover_url?: string;
 }
@@ -59,6 +126,14 @@ export interface SearchableSong extends SongSummary {
   _searchArtist: string;
 }
 
+/** Sent by the server when songs were added, changed or removed */
+export interface LibraryChange {
+  /** Added or changed songs */
+  updated: string[];
+  /** Removed songs; both lists are empty if the whole list should be reloaded */
+  removed: string[];
+}
+
 export interface QueueEntry {
   id: number;
   song_id: string;
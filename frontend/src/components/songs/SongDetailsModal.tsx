@@ -18,7 +18,7 @@ export function SongDetailsModal({
   const { micAssignments } = useAudioStore();
   const coverUrl = getFileUrl(song.id, "cover");
 
-  const isDuet = Boolean(song.notes_p2 && song.notes_p2.length > 0);
+  const isDuet = song.tracks.length > 1;
   const hasVideo = Boolean(song.metadata.video_file);
   const maxPlayers = isDuet ? 2 : 4;
 
@@ -42,8 +42,10 @@ export function SongDetailsModal({
   }, [onStart, onCancel, activePlayers.length]);
 
   // Calculate note count
-  const noteCount =
-    song.notes.length + (song.notes_p2 ? song.notes_p2.length : 0);
+  const noteCount = song.tracks.reduce(
+    (count, track) => count + track.notes.length,
+    0,
+  );
 
   return (
     <div className="fixed inset-0 z-50 flex items-center justify-center">
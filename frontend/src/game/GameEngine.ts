@@ -66,23 +66,24 @@ export class GameEngine {
     });
 
     // Create note trackers for each track
+    const [first, second] = song.tracks;
     this.noteTrackers.set(
       1,
       new NoteTracker(
-        song.notes,
-        song.line_breaks,
+        first?.notes ?? [],
+        first?.line_breaks ?? [],
         song.metadata.bpm,
         song.metadata.gap,
       ),
     );
 
     // For duets, create a separate tracker for P2
-    if (song.notes_p2 && song.notes_p2.length > 0) {
+    if (second && second.notes.length > 0) {
       this.noteTrackers.set(
         2,
         new NoteTracker(
-          song.notes_p2,
-          song.line_breaks_p2 ?? [],
+          second.notes,
+          second.line_breaks,
           song.metadata.bpm,
           song.metadata.gap,
         ),
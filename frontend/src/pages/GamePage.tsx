@@ -20,7 +20,7 @@ function GameSetup() {
   const { initAudio, refreshMicrophones, permissionGranted } = useAudioStore();
 
   // Check if this is a duet song
-  const isDuet = Boolean(song?.notes_p2 && song.notes_p2.length > 0);
+  const isDuet = (song?.tracks.length ?? 0) > 1;
   const maxPlayers = isDuet ? 2 : 4;
 
   // Refresh mics and create players from assignments on mount
@@ -132,7 +132,9 @@ function GameSetup() {
           <div className="mt-6 grid grid-cols-2 gap-4 text-sm">
             <div>
               <span className="text-gray-500">Notes:</span>
-              <span className="text-white ml-2">{song.notes.length}</span>
+              <span className="text-white ml-2">
+                {song.tracks[0]?.notes.length ?? 0}
+              </span>
             </div>
             <div>
               <span className="text-gray-500">BPM:</span>
@@ -266,12 +268,14 @@ function GamePlay() {
 
   // Calculate max score per track
   const maxScoreP1 = useMemo(
-    () => (song ? scorer.calculateMaxScore(song.notes) : 0),
+    () => scorer.calculateMaxScore(song?.tracks[0]?.notes ?? []),
     [song, scorer],
   );
   const maxScoreP2 = useMemo(
     () =>
-      song?.notes_p2 ? scorer.calculateMaxScore(song.notes_p2) : maxScoreP1,
+      song?.tracks[1]
+        ? scorer.calculateMaxScore(song.tracks[1].notes)
+        : maxScoreP1,
     [song, scorer, maxScoreP1],
   );
 
@@ -10,6 +10,7 @@ import {
   getQueue,
   removeFromQueue,
   removeFromQueueBySong,
+  subscribeLibraryChanges,
   getFileUrl,
 } from "../api/client";
 import { getPlayerColor } from "../constants/playerColors";
@@ -180,6 +181,7 @@ export function HomePage() {
     isLoading,
     error,
     fetchSongs,
+    refreshSongs,
     searchQuery,
     setSearchQuery,
   } = useSongStore();
@@ -232,6 +234,9 @@ export function HomePage() {
     fetchSongs();
   }, [fetchSongs]);
 
+  // Pick up songs added or edited while the app is open
+  useEffect(() => subscribeLibraryChanges(refreshSongs), [refreshSongs]);
+
   // Poll queue every 5 seconds
   useEffect(() => {
     const fetchQueueData = () => getQueue().then(setQueue).catch(console.error);
@@ -288,9 +293,7 @@ export function HomePage() {
     await initAudio();
 
     // Determine max players based on whether it's a duet
-    const isDuet = Boolean(
-      songForModal.notes_p2 && songForModal.notes_p2.length > 0,
-    );
+    const isDuet = songForModal.tracks.length > 1;
     const maxPlayers = isDuet ? 2 : 4;
 
     // Create players from mic assignments
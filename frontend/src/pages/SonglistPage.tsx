@@ -1,5 +1,11 @@
 import { useCallback, useEffect, useMemo, useState } from "react";
-import { addToQueue, getFileUrl, getQueue, getSongs } from "../api/client";
+import {
+  addToQueue,
+  getFileUrl,
+  getQueue,
+  getSongs,
+  subscribeLibraryChanges,
+} from "../api/client";
 import type { QueueEntry, SongSummary } from "../api/types";
 
 interface ArtistGroup {
@@ -240,6 +246,15 @@ export function SonglistPage() {
       .finally(() => setIsLoading(false));
   }, []);
 
+  // Reload songs when the library changes
+  useEffect(
+    () =>
+      subscribeLibraryChanges(() =>
+        getSongs().then(setSongs).catch(console.error),
+      ),
+    [],
+  );
+
   // Poll queue every 5 seconds
   useEffect(() => {
     const fetchQueue = () => getQueue().then(setQueue).catch(console.error);
@@ -23,6 +23,8 @@ interface SongState {
   getFilteredSongs: () => SearchableSong[];
 
   fetchSongs: () => Promise<void>;
+  // Reload the song list in the background, e.g. after the library changed
+  refreshSongs: () => Promise<void>;
   fetchSong: (id: string) => Promise<void>;
   setSearchQuery: (query: string) => void;
   clearCurrentSong: () => void;
@@ -59,6 +61,15 @@ export const useSongStore = create<SongState>((set, get) => ({
     }
   },
 
+  refreshSongs: async () => {
+    try {
+      const songs = await getSongs();
+      set({ allSongs: songs.map(toSearchableSong) });
+    } catch (error) {
+      console.error(error);
+    }
+  },
+
   fetchSong: async (id: string) => {
     set({ isLoading: true, error: null });
     try {